@@ -0,0 +1,75 @@
+use crate::serial::ComConfig;
+
+// Channel mode controller numbers
+pub const ALL_SOUND_OFF: u8 = 120;
+pub const RESET_ALL_CONTROLLERS: u8 = 121;
+pub const ALL_NOTES_OFF: u8 = 123;
+
+// Silence the synth on every channel, including notes TuneIn doesn't know about
+// Stops at the first failed write, a port that times out once will time out for the rest too
+pub fn send_panic(com_config: &mut ComConfig, note_offs: bool) -> std::io::Result<()> {
+    write_panic(note_offs, |data| com_config.try_send_bytes(data))
+}
+// Write the panic messages with the given write function
+fn write_panic(
+    note_offs: bool,
+    mut write: impl FnMut(&[u8]) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    for channel in 0..16 {
+        for controller in [ALL_NOTES_OFF, ALL_SOUND_OFF, RESET_ALL_CONTROLLERS] {
+            write(&[0xB0 | channel, controller, 0x00])?;
+        }
+        // Devices that ignore channel mode messages need every note turned off
+        if note_offs {
+            for note in 0..128 {
+                write(&[0x80 | channel, note, 0x00])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_covers_all_channels() {
+        let sent = ComConfig::new().record(|com| send_panic(com, false).unwrap());
+        assert_eq!(sent.len(), 48);
+        for channel in 0..16 {
+            let status = 0xB0 | channel as u8;
+            assert_eq!(sent[channel * 3..channel * 3 + 3], [
+                vec![status, 123, 0],
+                vec![status, 120, 0],
+                vec![status, 121, 0],
+            ]);
+        }
+    }
+
+    #[test]
+    fn panic_turns_every_note_off() {
+        let sent = ComConfig::new().record(|com| send_panic(com, true).unwrap());
+        assert_eq!(sent.len(), 16 * (3 + 128));
+        // The last channel ends with its note offs
+        let last = &sent[15 * 131..];
+        assert_eq!(last[2], vec![0xBF, 121, 0]);
+        let notes: Vec<Vec<u8>> = (0..128).map(|note| vec![0x8F, note, 0]).collect();
+        assert_eq!(last[3..], notes[..]);
+    }
+
+    #[test]
+    fn panic_stops_at_the_first_failed_write() {
+        let mut written = Vec::new();
+        let result = write_panic(true, |data| {
+            if written.len() == 5 {
+                return Err(std::io::Error::from(std::io::ErrorKind::TimedOut));
+            }
+            written.push(data.to_vec());
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert_eq!(written.len(), 5);
+        assert_eq!(written[4], vec![0x80, 1, 0]);
+    }
+}
@@ -0,0 +1,233 @@
+use rand::Rng;
+use std::time::{Duration, Instant};
+
+use crate::{dds_data::DdsData, serial::ComConfig};
+
+// Order the held notes are played in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ArpPattern {
+    Up,       //Lowest to highest
+    Down,     //Highest to lowest
+    UpDown,   //Up and back down without repeating the ends
+    Random,   //Random held note every step
+    AsPlayed, //Order the notes were pressed in
+}
+// Arpeggiator main struct
+pub struct Arpeggiator {
+    pub enabled: bool,
+    pub pattern: ArpPattern,
+    pub rate: u32,                 //Steps per second
+    pub octaves: u8,               //Number of octaves the pattern spans
+    pub gate: u8,                  //Note length in percent of a step
+    step: usize,                   //Index of the next step in the sequence
+    next_step: Instant,            //Time the next step is due
+    gate_end: Instant,             //Time the playing note is turned off
+    playing: Option<(u8, u8)>,     //Channel and note currently sounding
+}
+
+impl Arpeggiator {
+    // Create new struct with default settings
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            pattern: ArpPattern::Up,
+            rate: 8,
+            octaves: 1,
+            gate: 50,
+            step: 0,
+            next_step: Instant::now(),
+            gate_end: Instant::now(),
+            playing: None,
+        }
+    }
+    // Turn the arpeggiator on or off, the held tones only sound while it is off
+    pub fn toggle(&mut self, dds_config: &mut DdsData, com_config: &mut ComConfig) {
+        self.enabled = !self.enabled;
+        self.stop_note(com_config);
+        dds_config.set_muted(com_config, self.enabled);
+        self.step = 0;
+        self.next_step = Instant::now();
+    }
+    // Turn the arpeggiator off without a note off, the panic key already sent one
+    pub fn stop(&mut self) {
+        self.enabled = false;
+        self.playing = None;
+    }
+    // Play the next step when it is due
+    pub fn on_tick(&mut self, dds_config: &DdsData, com_config: &mut ComConfig) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if self.playing.is_some() && now >= self.gate_end {
+            self.stop_note(com_config);
+        }
+        if now < self.next_step {
+            return;
+        }
+        // A full gate turns the old note off right before the new one
+        self.stop_note(com_config);
+        let sequence = self.sequence(dds_config);
+        if !sequence.is_empty() {
+            let index = match self.pattern {
+                ArpPattern::Random => rand::rng().random_range(0..sequence.len()),
+                _ => self.step % sequence.len(),
+            };
+            let (channel, note, velocity) = sequence[index];
+            com_config.send_midi(0x90 | channel, note, velocity);
+            self.playing = Some((channel, note));
+            self.gate_end = now + self.interval() * self.gate as u32 / 100;
+            self.step = (self.step + 1) % sequence.len();
+        }
+        // Catch up instead of bursting notes if a step was missed
+        self.next_step += self.interval();
+        if self.next_step < now {
+            self.next_step = now + self.interval();
+        }
+    }
+    // Channel, note and velocity of every step in the pattern
+    fn sequence(&self, dds_config: &DdsData) -> Vec<(u8, u8, u8)> {
+        let mut notes: Vec<(u8, u8, u8)> = dds_config
+            .signal_data
+            .iter()
+            .map(|s| (s.channel, s.note, s.velocity))
+            .collect();
+        // Notes held by several keys are played once
+        let mut seen = Vec::new();
+        notes.retain(|n| {
+            let fresh = !seen.contains(&(n.0, n.1));
+            seen.push((n.0, n.1));
+            fresh
+        });
+        if self.pattern != ArpPattern::AsPlayed && self.pattern != ArpPattern::Random {
+            notes.sort_by_key(|n| n.1);
+        }
+        // Repeat the notes in the higher octaves
+        let mut sequence = Vec::new();
+        for octave in 0..self.octaves {
+            for (channel, note, velocity) in &notes {
+                let shifted = *note as u32 + 12 * octave as u32;
+                if shifted <= 127 {
+                    sequence.push((*channel, shifted as u8, *velocity));
+                }
+            }
+        }
+        match self.pattern {
+            ArpPattern::Down => sequence.reverse(),
+            ArpPattern::UpDown if sequence.len() > 2 => {
+                let down: Vec<(u8, u8, u8)> =
+                    sequence[1..sequence.len() - 1].iter().rev().copied().collect();
+                sequence.extend(down);
+            }
+            _ => {}
+        }
+        sequence
+    }
+    // Turn off the sounding note
+    fn stop_note(&mut self, com_config: &mut ComConfig) {
+        if let Some((channel, note)) = self.playing.take() {
+            com_config.send_midi(0x80 | channel, note, 0x00);
+        }
+    }
+    // Time between two steps
+    fn interval(&self) -> Duration {
+        Duration::from_secs_f64(1. / self.rate as f64)
+    }
+    // Switch to the next pattern
+    pub fn cycle_pattern(&mut self) {
+        self.pattern = match self.pattern {
+            ArpPattern::Up => ArpPattern::Down,
+            ArpPattern::Down => ArpPattern::UpDown,
+            ArpPattern::UpDown => ArpPattern::Random,
+            ArpPattern::Random => ArpPattern::AsPlayed,
+            ArpPattern::AsPlayed => ArpPattern::Up,
+        };
+    }
+    // Change the rate between 1 and 50 steps per second
+    pub fn change_rate(&mut self, raise: bool) {
+        self.rate = if raise {
+            (self.rate + 1).min(50)
+        } else {
+            (self.rate - 1).max(1)
+        };
+    }
+    // Change the octave span between 1 and 4
+    pub fn change_octaves(&mut self, raise: bool) {
+        self.octaves = if raise {
+            (self.octaves + 1).min(4)
+        } else {
+            (self.octaves - 1).max(1)
+        };
+    }
+    // Change the gate length between 10 and 100 percent
+    pub fn change_gate(&mut self, raise: bool) {
+        self.gate = if raise {
+            (self.gate + 10).min(100)
+        } else {
+            (self.gate - 10).max(10)
+        };
+    }
+    // Status line for the info panel
+    pub fn status(&self) -> String {
+        if self.enabled {
+            format!(
+                "Arp: {} | {}/s | {} oct | Gate {}%",
+                self.pattern_name(),
+                self.rate,
+                self.octaves,
+                self.gate
+            )
+        } else {
+            String::from("Arp: off")
+        }
+    }
+    // Name of the selected pattern
+    pub fn pattern_name(&self) -> &str {
+        match self.pattern {
+            ArpPattern::Up => "Up",
+            ArpPattern::Down => "Down",
+            ArpPattern::UpDown => "Up-Down",
+            ArpPattern::Random => "Random",
+            ArpPattern::AsPlayed => "As played",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Arpeggiator spanning two octaves over C4, E4 and G4 pressed in the order E, G, C
+    fn sequence(pattern: ArpPattern) -> Vec<u8> {
+        let mut dds = DdsData::new();
+        ComConfig::new().record(|com| {
+            dds.toggle_key(com, 1, &[329.63], 100);
+            dds.toggle_key(com, 2, &[392.0], 100);
+            dds.toggle_key(com, 3, &[261.63], 100);
+        });
+        let mut arp = Arpeggiator::new();
+        arp.pattern = pattern;
+        arp.octaves = 2;
+        arp.sequence(&dds).iter().map(|(_, note, _)| *note).collect()
+    }
+
+    #[test]
+    fn up_spans_the_octaves() {
+        assert_eq!(sequence(ArpPattern::Up), vec![60, 64, 67, 72, 76, 79]);
+    }
+
+    #[test]
+    fn down_starts_at_the_top() {
+        assert_eq!(sequence(ArpPattern::Down), vec![79, 76, 72, 67, 64, 60]);
+    }
+
+    #[test]
+    fn up_down_does_not_repeat_the_ends() {
+        assert_eq!(sequence(ArpPattern::UpDown), vec![60, 64, 67, 72, 76, 79, 76, 72, 67, 64]);
+    }
+
+    #[test]
+    fn as_played_keeps_the_key_order() {
+        assert_eq!(sequence(ArpPattern::AsPlayed), vec![64, 67, 60, 76, 79, 72]);
+    }
+}
@@ -0,0 +1,448 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position, Rect},
+    style::{Modifier, Style},
+    symbols,
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Borders, Clear, LineGauge, Paragraph, Widget},
+};
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// A virtual knob bound to a controller
+pub struct Knob {
+    pub name: String,    //General MIDI name of the controller
+    pub cc: u8,          //Controller number, the LSB of a 14 bit knob is cc + 32
+    pub channel: u8,     //Midi channel (0-15)
+    pub high_res: bool,  //Send 14 bit MSB/LSB pairs
+    pub value: u16,      //Current value, 0-127 or 0-16383 for 14 bit
+    default: u8,         //Value the knob starts with and returns to after a panic
+}
+// Input fields of the knob config popup
+enum KnobConfigState {
+    Controller,
+    Channel,
+    HighRes,
+}
+// Main cc panel struct
+pub struct CcPanel {
+    knobs: Vec<Knob>,
+    selected: usize,
+    config_state: KnobConfigState,
+    cc_input: Input,
+    channel_input: Input,
+    high_res_input: Input,
+}
+
+impl Knob {
+    fn new(cc: u8, default: u8) -> Self {
+        Self {
+            name: controller_name(cc),
+            cc,
+            channel: 0,
+            high_res: false,
+            value: default as u16,
+            default,
+        }
+    }
+    // Highest value of the knob
+    fn max(&self) -> u16 {
+        if self.high_res { 0x3FFF } else { 0x7F }
+    }
+    // Send the current value
+    fn send(&self, com_config: &mut ComConfig) {
+        if self.high_res {
+            com_config.send_midi(0xB0 | self.channel, self.cc, (self.value >> 7) as u8);
+            com_config.send_midi(0xB0 | self.channel, self.cc + 32, (self.value & 0x7F) as u8);
+        } else {
+            com_config.send_midi(0xB0 | self.channel, self.cc, self.value as u8);
+        }
+    }
+}
+
+impl CcPanel {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            knobs: vec![
+                Knob::new(2, 0),
+                Knob::new(7, 100),
+                Knob::new(10, 64),
+                Knob::new(11, 127),
+                Knob::new(74, 64),
+                Knob::new(71, 64),
+                Knob::new(73, 64),
+                Knob::new(72, 64),
+            ],
+            selected: 0,
+            config_state: KnobConfigState::Controller,
+            cc_input: Input::new(),
+            channel_input: Input::new(),
+            high_res_input: Input::new(),
+        }
+    }
+    // Return the knobs to their start values without sending, used after a panic
+    pub fn reset(&mut self) {
+        for knob in &mut self.knobs {
+            let shift = if knob.high_res { 7 } else { 0 };
+            knob.value = (knob.default as u16) << shift;
+        }
+    }
+    // Select the previous or next knob
+    pub fn select(&mut self, next: bool) {
+        let count = self.knobs.len();
+        self.selected = if next {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+    // Turn the selected knob, coarse steps are an eighth of the range
+    pub fn adjust(&mut self, com_config: &mut ComConfig, up: bool, coarse: bool) {
+        let knob = &mut self.knobs[self.selected];
+        let step = match (knob.high_res, coarse) {
+            (false, false) => 1,
+            (false, true) => 16,
+            (true, false) => 16,
+            (true, true) => 2048,
+        };
+        let value = if up {
+            (knob.value + step).min(knob.max())
+        } else {
+            knob.value.saturating_sub(step)
+        };
+        // Only send on change
+        if value != knob.value {
+            knob.value = value;
+            knob.send(com_config);
+        }
+    }
+    // Event handling for the config popup
+    pub fn key_event(&mut self, key: KeyEvent) -> AppState {
+        let mut app_state: AppState = AppState::KnobConfig;
+        match key.code {
+            // Toggle state
+            KeyCode::Tab => self.toggle_state(),
+            // Apply the settings to the selected knob
+            KeyCode::Enter => {
+                let mut err_flag = false;
+                let knob = &mut self.knobs[self.selected];
+                let mut cc = knob.cc;
+                let mut channel = knob.channel;
+                let mut high_res = knob.high_res;
+
+                // Empty fields keep their value
+                match self.cc_input.submit_message().trim() {
+                    "" => {}
+                    c => match c.parse::<u8>() {
+                        Ok(c) if c < 120 => cc = c,
+                        Ok(_) | Err(_) => {
+                            self.cc_input.display_error(String::from("Not a valid value"));
+                            err_flag |= true;
+                        }
+                    },
+                }
+                match self.channel_input.submit_message().trim() {
+                    "" => {}
+                    c => match c.parse::<u8>() {
+                        Ok(c) if (1..=16).contains(&c) => channel = c - 1,
+                        Ok(_) | Err(_) => {
+                            self.channel_input
+                                .display_error(String::from("Not a valid value"));
+                            err_flag |= true;
+                        }
+                    },
+                }
+                match self.high_res_input.submit_message().trim() {
+                    "" => {}
+                    "y" => high_res = true,
+                    "n" => high_res = false,
+                    _ => {
+                        self.high_res_input
+                            .display_error(String::from("Not a valid value"));
+                        err_flag |= true;
+                    }
+                }
+                // Only controllers 0-31 have an LSB partner
+                if high_res && cc >= 32 {
+                    self.high_res_input
+                        .display_error(String::from("14 bit needs CC 0-31"));
+                    err_flag |= true;
+                }
+
+                if !err_flag {
+                    // Keep the position of the knob when the resolution changes
+                    if high_res && !knob.high_res {
+                        knob.value <<= 7;
+                    } else if !high_res && knob.high_res {
+                        knob.value >>= 7;
+                    }
+                    knob.name = controller_name(cc);
+                    knob.cc = cc;
+                    knob.channel = channel;
+                    knob.high_res = high_res;
+                    app_state = AppState::Running;
+                }
+            }
+            _ => {
+                let input = match self.config_state {
+                    KnobConfigState::Controller => &mut self.cc_input,
+                    KnobConfigState::Channel => &mut self.channel_input,
+                    KnobConfigState::HighRes => &mut self.high_res_input,
+                };
+                if input.key_event(key) {
+                    app_state = AppState::Running;
+                }
+            }
+        }
+        app_state
+    }
+    // Toggle state
+    fn toggle_state(&mut self) {
+        self.config_state = match self.config_state {
+            KnobConfigState::Controller => KnobConfigState::Channel,
+            KnobConfigState::Channel => KnobConfigState::HighRes,
+            KnobConfigState::HighRes => KnobConfigState::Controller,
+        }
+    }
+    // Render the knobs as a column of faders
+    pub fn render_knobs(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::new()
+            .border_type(BorderType::Thick)
+            .borders(Borders::ALL)
+            .title("CC Knobs");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let knob_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(2); self.knobs.len()])
+            .split(inner);
+
+        for (i, knob) in self.knobs.iter().enumerate() {
+            let style = if i == self.selected {
+                Style::default().fg(theme.highlight).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            let title = format!(
+                "{} CC{}{} Ch{}",
+                knob.name,
+                knob.cc,
+                if knob.high_res { "/14bit" } else { "" },
+                knob.channel + 1
+            );
+            LineGauge::default()
+                .block(Block::new().borders(Borders::NONE).title(Line::from(title).style(style)))
+                .filled_style(theme.filled_style())
+                .unfilled_style(theme.unfilled_style())
+                .label(format!("{:>5}", knob.value))
+                .line_set(symbols::line::NORMAL)
+                .ratio(knob.value as f64 / knob.max() as f64)
+                .render(knob_layout[i], frame.buffer_mut());
+        }
+    }
+    //Render a popup for assigning the selected knob
+    pub fn show_knob_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 40);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        let knob = &self.knobs[self.selected];
+        let centered_title = format!(
+            "{:^width$}",
+            "Assign knob ".to_string() + &knob.name,
+            width = vertical_layout[0].width as usize
+        );
+
+        frame.render_widget(
+            Paragraph::new(Text::from(vec![
+                Line::from(Span::styled(
+                    centered_title,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(""),
+                Line::from(format!(
+                    " Current: CC {} | Channel {} | 14 bit {}",
+                    knob.cc,
+                    knob.channel + 1,
+                    if knob.high_res { "y" } else { "n" }
+                )),
+                Line::from(""),
+                Line::from(" • CC: (0-119) controller number."),
+                Line::from(" • Channel: (1-16) midi channel."),
+                Line::from(" • 14 bit: (y/n) send MSB on CC and LSB on CC+32, only for CC 0-31."),
+                Line::from(""),
+                Line::from(" • Note: Empty fields keep their current value."),
+            ]))
+            .block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        let fields = [
+            (&self.cc_input, "CC", vertical_layout[1]),
+            (&self.channel_input, "Channel", vertical_layout[2]),
+            (&self.high_res_input, "14 bit (y/n)", vertical_layout[3]),
+        ];
+        for (input, title, field) in &fields {
+            frame.render_widget(
+                input.get_input(String::from(*title), theme).style(Style::default()),
+                *field,
+            );
+        }
+        // Highlighting
+        let (input, title, field) = match self.config_state {
+            KnobConfigState::Controller => fields[0],
+            KnobConfigState::Channel => fields[1],
+            KnobConfigState::HighRes => fields[2],
+        };
+        frame.render_widget(input.get_input(String::from(title), theme), field);
+        frame.set_cursor_position(Position::new(
+            // Draw the cursor at the current position in the input field.
+            // This position is can be controlled via the left and right arrow key
+            field.x + input.get_index() + 1,
+            // Move one line down, from the border to the input line
+            field.y + field.height - 2,
+        ));
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        let input = match self.config_state {
+            KnobConfigState::Controller => &self.cc_input,
+            KnobConfigState::Channel => &self.channel_input,
+            KnobConfigState::HighRes => &self.high_res_input,
+        };
+        vec![&keymap::FIELDS, input.keymap()]
+    }
+}
+
+// Short General MIDI name of a controller
+fn controller_name(cc: u8) -> String {
+    let name = match cc {
+        0 => "Bank",
+        1 => "Mod",
+        2 => "Breath",
+        4 => "Foot",
+        5 => "Porta time",
+        6 => "Data",
+        7 => "Volume",
+        8 => "Balance",
+        10 => "Pan",
+        11 => "Express",
+        12 => "Effect 1",
+        13 => "Effect 2",
+        16..=19 => return format!("General {}", cc - 15),
+        32..=63 => return format!("LSB {}", cc - 32),
+        64 => "Sustain",
+        65 => "Porta",
+        66 => "Sostenuto",
+        67 => "Soft",
+        68 => "Legato",
+        69 => "Hold 2",
+        70 => "Variation",
+        71 => "Reso",
+        72 => "Release",
+        73 => "Attack",
+        74 => "Cutoff",
+        75 => "Decay",
+        76 => "Vib rate",
+        77 => "Vib depth",
+        78 => "Vib delay",
+        79 => "Sound 10",
+        80..=83 => return format!("General {}", cc - 75),
+        84 => "Porta ctrl",
+        88 => "Hi-res vel",
+        91 => "Reverb",
+        92 => "Tremolo",
+        93 => "Chorus",
+        94 => "Detune",
+        95 => "Phaser",
+        96 => "Data +",
+        97 => "Data -",
+        98 => "NRPN LSB",
+        99 => "NRPN MSB",
+        100 => "RPN LSB",
+        101 => "RPN MSB",
+        _ => "Undefined",
+    };
+    String::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Type into the config fields, Tab moves to the next field
+    fn assign(panel: &mut CcPanel, fields: &[&str]) -> AppState {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                panel.key_event(KeyEvent::from(KeyCode::Tab));
+            }
+            for c in field.chars() {
+                panel.key_event(KeyEvent::from(KeyCode::Char(c)));
+            }
+        }
+        panel.key_event(KeyEvent::from(KeyCode::Enter))
+    }
+
+    #[test]
+    fn adjust_steps_and_stops_at_the_ends() {
+        let mut panel = CcPanel::new();
+        // Breath on CC 2 starts at 0
+        let sent = ComConfig::new().record(|com| {
+            panel.adjust(com, false, false);
+            panel.adjust(com, true, false);
+            panel.adjust(com, true, true);
+            for _ in 0..8 {
+                panel.adjust(com, true, true);
+            }
+        });
+        assert_eq!(sent[..3], [vec![0xB0, 2, 1], vec![0xB0, 2, 17], vec![0xB0, 2, 33]]);
+        assert_eq!(sent.last(), Some(&vec![0xB0, 2, 127]));
+        assert_eq!(sent.len(), 9);
+    }
+
+    #[test]
+    fn high_res_knob_sends_msb_and_lsb() {
+        let mut panel = CcPanel::new();
+        assert!(matches!(assign(&mut panel, &["1", "2", "y"]), AppState::Running));
+        assert_eq!(panel.knobs[0].value, 0);
+        let sent = ComConfig::new().record(|com| {
+            panel.adjust(com, true, false);
+            panel.adjust(com, true, true);
+        });
+        // 16 is 0 0x10 and 2064 is 0x10 0x10 as MSB on CC 1 and LSB on CC 33 of channel 2
+        assert_eq!(sent, vec![
+            vec![0xB1, 1, 0],
+            vec![0xB1, 33, 0x10],
+            vec![0xB1, 1, 0x10],
+            vec![0xB1, 33, 0x10],
+        ]);
+    }
+
+    #[test]
+    fn reassigned_knob_gets_the_controller_name() {
+        let mut panel = CcPanel::new();
+        assert!(matches!(assign(&mut panel, &["64"]), AppState::Running));
+        assert_eq!(panel.knobs[0].name, "Sustain");
+        assert!(matches!(assign(&mut panel, &["20"]), AppState::Running));
+        assert_eq!(panel.knobs[0].name, "Undefined");
+    }
+}
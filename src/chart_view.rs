@@ -0,0 +1,239 @@
+use crate::{dds_data::Signal, midi_utils::{self, MiddleC}, spectrum::FLOOR_DB};
+
+// Lowest and highest note position of the chart, C-1 to C10
+const LOWEST: f64 = 0.;
+const HIGHEST: f64 = 132.;
+// Smallest shown range in semitones
+const MIN_SPAN: f64 = 6.;
+// Range shown without active notes, C0 to C9
+const DEFAULT_RANGE: (f64, f64) = (12., 120.);
+// Semitones between gridlines, the smallest one with at most 10 lines is used
+const GRID_STEPS: [f64; 9] = [1., 2., 3., 4., 6., 12., 24., 36., 48.];
+
+// Axes and visible range of the spectrum chart
+pub struct ChartView {
+    pub log_axis: bool,         //Frequency axis in semitones instead of Hz
+    db_axis: bool,              //Strength axis in dBFS instead of linear
+    manual: Option<(f64, f64)>, //Range in note positions set by zoom and pan, auto range if none
+}
+
+// Fractional midi note of a frequency
+fn freq_to_position(freq: f64) -> f64 {
+    12. * (freq / 440.).log2() + 69.
+}
+// Frequency of a fractional midi note
+fn position_to_freq(position: f64) -> f64 {
+    440. * 2f64.powf((position - 69.) / 12.)
+}
+
+impl ChartView {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            log_axis: true,
+            db_axis: false,
+            manual: None,
+        }
+    }
+    // Range in note positions, auto ranged around the active notes
+    fn range(&self, channel_data: &[Signal]) -> (f64, f64) {
+        if let Some(range) = self.manual {
+            return range;
+        }
+        let positions = channel_data.iter().map(|s| freq_to_position(s.freq));
+        let low = positions.clone().fold(f64::INFINITY, f64::min);
+        let high = positions.fold(f64::NEG_INFINITY, f64::max);
+        if !low.is_finite() {
+            return DEFAULT_RANGE;
+        }
+        // Half an octave of space around the notes and at least an octave in total
+        let center = (low + high) / 2.;
+        let half = ((high - low) / 2. + 6.).max(6.);
+        clamp_range(center - half, center + half)
+    }
+    // Range snapped to the gridlines and the semitones between them
+    pub fn grid(&self, channel_data: &[Signal]) -> (f64, f64, f64) {
+        let (low, high) = self.range(channel_data);
+        let step = GRID_STEPS
+            .iter()
+            .copied()
+            .find(|step| ((high / step).ceil() - (low / step).floor()) <= 9.)
+            .unwrap_or(GRID_STEPS[GRID_STEPS.len() - 1]);
+        ((low / step).floor() * step, (high / step).ceil() * step, step)
+    }
+    // Toggle between a log and a linear frequency axis
+    pub fn toggle_log(&mut self) {
+        self.log_axis = !self.log_axis;
+    }
+    // Toggle between a dB and a linear strength axis
+    pub fn toggle_db(&mut self) {
+        self.db_axis = !self.db_axis;
+    }
+    // Go back to the auto range
+    pub fn auto_range(&mut self) {
+        self.manual = None;
+    }
+    // Halve or double the visible range around its center
+    pub fn zoom(&mut self, zoom_in: bool, channel_data: &[Signal]) {
+        let (low, high) = self.range(channel_data);
+        let center = (low + high) / 2.;
+        let half = (high - low) / 2. * if zoom_in { 0.5 } else { 2. };
+        let half = half.max(MIN_SPAN / 2.);
+        self.manual = Some(clamp_range(center - half, center + half));
+    }
+    // Move the visible range by a quarter of its width
+    pub fn pan(&mut self, up: bool, channel_data: &[Signal]) {
+        let (low, high) = self.range(channel_data);
+        let shift = (high - low) / 4. * if up { 1. } else { -1. };
+        self.manual = Some(clamp_range(low + shift, high + shift));
+    }
+    // Position of a frequency on the x axis
+    pub fn x(&self, freq: f64) -> f64 {
+        if self.log_axis {
+            freq_to_position(freq.max(1.))
+        } else {
+            freq
+        }
+    }
+    // Bounds and labels of the x axis, the labels of the log axis sit on the gridlines
+    pub fn x_axis(&self, channel_data: &[Signal], middle_c: MiddleC) -> ([f64; 2], Vec<String>) {
+        let (low, high, step) = self.grid(channel_data);
+        if self.log_axis {
+            let count = ((high - low) / step).round() as usize;
+            let labels = (0..=count)
+                .map(|i| midi_utils::note_name((low + step * i as f64) as u8, middle_c))
+                .collect();
+            ([low, high], labels)
+        } else {
+            let (low, high) = (position_to_freq(low), position_to_freq(high));
+            let labels = [low, (low + high) / 2., high]
+                .iter()
+                .map(|f| format!("{:.0}", f))
+                .collect();
+            ([low, high], labels)
+        }
+    }
+    // Gridlines at the label positions in axis units
+    pub fn gridlines(&self, channel_data: &[Signal]) -> Vec<f64> {
+        let (low, high, step) = self.grid(channel_data);
+        let count = ((high - low) / step).round() as usize;
+        (0..=count)
+            .map(|i| low + step * i as f64)
+            .map(|p| if self.log_axis { p } else { position_to_freq(p) })
+            .collect()
+    }
+    // Strength of a velocity on the y axis (0-1)
+    pub fn velocity_y(&self, velocity: u8) -> f64 {
+        if velocity == 0 {
+            return 0.;
+        }
+        self.db_y(20. * (velocity as f64 / 127.).log10())
+    }
+    // Strength of a level in dBFS on the y axis (0-1)
+    pub fn db_y(&self, db: f64) -> f64 {
+        if self.db_axis {
+            ((db - FLOOR_DB) / -FLOOR_DB).clamp(0., 1.)
+        } else {
+            10f64.powf(db / 20.)
+        }
+    }
+    // Title and labels of the y axis
+    pub fn y_axis(&self) -> (&'static str, Vec<String>) {
+        if self.db_axis {
+            (
+                "dBFS",
+                vec![
+                    format!("{}", FLOOR_DB),
+                    format!("{}", FLOOR_DB / 2.),
+                    "0".to_string(),
+                ],
+            )
+        } else {
+            (
+                "Velocity",
+                vec!["0".to_string(), "64".to_string(), "127".to_string()],
+            )
+        }
+    }
+}
+
+// Keep a range inside the chart without changing its width
+fn clamp_range(low: f64, high: f64) -> (f64, f64) {
+    let span = (high - low).min(HIGHEST - LOWEST);
+    let low = low.clamp(LOWEST, HIGHEST - span);
+    (low, low + span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn signal(freq: f64) -> Signal {
+        Signal {
+            key: 0,
+            freq,
+            velocity: 100,
+            note: midi_utils::freq_to_note_id(freq),
+            channel: 0,
+            slot: 0,
+            started: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn clamp_range_keeps_width() {
+        assert_eq!(clamp_range(20., 40.), (20., 40.));
+        assert_eq!(clamp_range(-10., 10.), (0., 20.));
+        assert_eq!(clamp_range(125., 145.), (112., 132.));
+        // Wider than the chart
+        assert_eq!(clamp_range(-50., 200.), (LOWEST, HIGHEST));
+    }
+
+    #[test]
+    fn grid_of_default_and_single_note() {
+        let view = ChartView::new();
+        assert_eq!(view.grid(&[]), (12., 120., 12.));
+        // A4 gets half an octave on both sides
+        assert_eq!(view.range(&[signal(440.)]), (63., 75.));
+        assert_eq!(view.grid(&[signal(440.)]), (62., 76., 2.));
+    }
+
+    #[test]
+    fn grid_covers_range_with_few_lines() {
+        let mut view = ChartView::new();
+        let notes = [signal(30.), signal(5000.)];
+        let check = |view: &ChartView| {
+            let (low, high) = view.range(&notes);
+            let (grid_low, grid_high, step) = view.grid(&notes);
+            assert!(grid_low <= low && grid_high >= high);
+            assert_eq!(grid_low % step, 0.);
+            assert!((grid_high - grid_low) / step <= 9.);
+        };
+        check(&view);
+        for _ in 0..8 {
+            view.zoom(true, &notes);
+            check(&view);
+            view.pan(true, &notes);
+            check(&view);
+        }
+        for _ in 0..8 {
+            view.zoom(false, &notes);
+            check(&view);
+            view.pan(false, &notes);
+            check(&view);
+        }
+    }
+
+    #[test]
+    fn zoom_stops_at_min_span() {
+        let mut view = ChartView::new();
+        for _ in 0..10 {
+            view.zoom(true, &[]);
+        }
+        let (low, high) = view.range(&[]);
+        assert_eq!(high - low, MIN_SPAN);
+        view.auto_range();
+        assert_eq!(view.range(&[]), DEFAULT_RANGE);
+    }
+}
@@ -0,0 +1,177 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::Keymap;
+use crate::popup_utils::popup_area;
+use crate::theme::Theme;
+
+// Chords a single key can play
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChordType {
+    Off,        //Single notes
+    Major,      //Root, major third, fifth
+    Minor,      //Root, minor third, fifth
+    Seventh,    //Major triad with a minor seventh
+    Sus,        //Root, fourth, fifth
+    Diminished, //Root, minor third, diminished fifth
+    Custom,     //User defined intervals
+}
+// Main chord config struct
+pub struct ChordConfig {
+    pub chord: ChordType,
+    custom_intervals: Vec<i32>, //Semitones above the root for the custom chord
+    input: Input,
+}
+
+impl ChordConfig {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            chord: ChordType::Off,
+            custom_intervals: vec![0, 4, 7, 11],
+            input: Input::new(),
+        }
+    }
+    // Keys played on top of a root key
+    pub fn chord_keys(&self, root: i32) -> Vec<i32> {
+        self.intervals().iter().map(|i| root + i).collect()
+    }
+    // Semitones above the root of the selected chord
+    fn intervals(&self) -> Vec<i32> {
+        match self.chord {
+            ChordType::Off => vec![0],
+            ChordType::Major => vec![0, 4, 7],
+            ChordType::Minor => vec![0, 3, 7],
+            ChordType::Seventh => vec![0, 4, 7, 10],
+            ChordType::Sus => vec![0, 5, 7],
+            ChordType::Diminished => vec![0, 3, 6],
+            ChordType::Custom => self.custom_intervals.clone(),
+        }
+    }
+    // Switch to the next chord
+    pub fn cycle_chord(&mut self) {
+        self.chord = match self.chord {
+            ChordType::Off => ChordType::Major,
+            ChordType::Major => ChordType::Minor,
+            ChordType::Minor => ChordType::Seventh,
+            ChordType::Seventh => ChordType::Sus,
+            ChordType::Sus => ChordType::Diminished,
+            ChordType::Diminished => ChordType::Custom,
+            ChordType::Custom => ChordType::Off,
+        };
+    }
+    // Status line for the info panel
+    pub fn status(&self) -> String {
+        "Chord: ".to_string() + &self.chord_name()
+    }
+    // Name of the selected chord
+    fn chord_name(&self) -> String {
+        match self.chord {
+            ChordType::Off => String::from("off"),
+            ChordType::Major => String::from("Major"),
+            ChordType::Minor => String::from("Minor"),
+            ChordType::Seventh => String::from("7th"),
+            ChordType::Sus => String::from("Sus"),
+            ChordType::Diminished => String::from("Diminished"),
+            ChordType::Custom => format!("Custom {:?}", self.custom_intervals),
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent) -> AppState {
+        let mut app_state: AppState = AppState::Chord;
+        match key.code {
+            // Submit the intervals
+            KeyCode::Enter => {
+                let intervals: Result<Vec<i32>, _> = self
+                    .input
+                    .submit_message()
+                    .split([' ', ','])
+                    .filter(|i| !i.is_empty())
+                    .map(|i| i.parse::<i32>())
+                    .collect();
+                match intervals {
+                    // Check if input is valid
+                    Ok(mut i) if !i.is_empty() && i.iter().all(|i| (-48..=48).contains(i)) => {
+                        // The same interval twice would hold one note from the same key twice
+                        let mut seen = Vec::new();
+                        i.retain(|i| {
+                            let fresh = !seen.contains(i);
+                            seen.push(*i);
+                            fresh
+                        });
+                        self.custom_intervals = i;
+                        self.chord = ChordType::Custom;
+                        app_state = AppState::Running;
+                    }
+                    Ok(_) | Err(_) => self.input.display_error(String::from("Not a valid value")),
+                }
+            }
+            _ => {
+                if self.input.key_event(key) {
+                    app_state = AppState::Running;
+                }
+            }
+        }
+        app_state
+    }
+    //Render a popup for the custom chord
+    pub fn show_chord_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 40);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(3)])
+            .split(area);
+
+        let centered_title = format!(
+            "{:^width$}",
+            "Custom chord",
+            width = vertical_layout[0].width as usize
+        );
+
+        frame.render_widget(
+            Paragraph::new(Text::from(vec![
+                Line::from(Span::styled(
+                    centered_title,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(""),
+                Line::from(" Enter the intervals of the chord in semitones above the root:"),
+                Line::from(""),
+                Line::from(" • Example: 0 4 7 11 plays a major seventh chord."),
+                Line::from(" • Current: ".to_string() + &format!("{:?}", self.custom_intervals)),
+                Line::from(""),
+                Line::from(" • Note: The synth plays at most 10 tones, extra voices are dropped."),
+            ]))
+            .block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        frame.render_widget(
+            self.input.get_input(String::from("Intervals"), theme),
+            vertical_layout[1],
+        );
+        frame.set_cursor_position(Position::new(
+            // Draw the cursor at the current position in the input field.
+            // This position is can be controlled via the left and right arrow key
+            vertical_layout[1].x + self.input.get_index() + 1,
+            // Move one line down, from the border to the input line
+            vertical_layout[1].y + vertical_layout[1].height - 2,
+        ));
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        vec![self.input.keymap()]
+    }
+}
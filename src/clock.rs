@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use crate::{serial::ComConfig, transport::Transport};
+
+// Timing clock pulses per quarter note
+const PPQN: f64 = 24.;
+// Taps further apart than this start a new tempo
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Midi real time and system common messages
+const TIMING_CLOCK: u8 = 0xF8;
+const START: u8 = 0xFA;
+const CONTINUE: u8 = 0xFB;
+const STOP: u8 = 0xFC;
+const SONG_POSITION: u8 = 0xF2;
+
+// Clock main struct
+pub struct Clock {
+    pub enabled: bool,     //Send clock and transport messages
+    beats: f64,            //Position of the clock in beats, free running while stopped
+    pulse: i64,            //Last pulse that was sent
+    last_tick: Instant,    //Time of the last tick
+    was_playing: bool,     //Transport state at the last tick
+    taps: Vec<Instant>,    //Recent tap tempo presses
+}
+
+impl Clock {
+    // Create new struct with default settings
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            beats: 0.,
+            pulse: 0,
+            last_tick: Instant::now(),
+            was_playing: false,
+            taps: Vec::new(),
+        }
+    }
+    // Send transport changes and the clock pulses that are due
+    pub fn on_tick(&mut self, transport: &Transport, com_config: &mut ComConfig) {
+        let now = Instant::now();
+        let elapsed = now - self.last_tick;
+        self.last_tick = now;
+
+        // Follow the transport while it plays, keep the tempo running while stopped.
+        // Pulses go out while stopped too, as the MIDI spec allows, so receivers can
+        // show the tempo and start in time
+        if transport.playing {
+            self.beats = transport.position();
+        } else {
+            self.beats += elapsed.as_secs_f64() * transport.bpm / 60.;
+        }
+        let pulse = (self.beats * PPQN).floor() as i64;
+
+        if transport.playing != self.was_playing {
+            self.was_playing = transport.playing;
+            if self.enabled {
+                self.send_transport(transport, com_config);
+            }
+            self.pulse = pulse - 1;
+        }
+        // Skip ahead after a restart or a stall instead of bursting pulses
+        if pulse < self.pulse || pulse - self.pulse > PPQN as i64 {
+            self.pulse = pulse;
+        }
+        while self.pulse < pulse {
+            self.pulse += 1;
+            if self.enabled {
+                com_config.send_bytes(&[TIMING_CLOCK]);
+            }
+        }
+    }
+    // Send start, stop or continue for a transport change
+    fn send_transport(&self, transport: &Transport, com_config: &mut ComConfig) {
+        if !transport.playing {
+            com_config.send_bytes(&[STOP]);
+        } else if transport.position() < 1. / PPQN {
+            com_config.send_bytes(&[START]);
+        } else {
+            self.send_song_position(transport, com_config);
+            com_config.send_bytes(&[CONTINUE]);
+        }
+    }
+    // Send the transport position in sixteenth notes
+    pub fn send_song_position(&self, transport: &Transport, com_config: &mut ComConfig) {
+        com_config.send_bytes(&song_position(transport.position()));
+    }
+    // Set the tempo from the average time between the last taps
+    pub fn tap(&mut self, transport: &mut Transport) {
+        self.tap_at(Instant::now(), transport);
+    }
+    // Tap at the given time
+    fn tap_at(&mut self, now: Instant, transport: &mut Transport) {
+        if self.taps.last().is_some_and(|t| now - *t > TAP_TIMEOUT) {
+            self.taps.clear();
+        }
+        self.taps.push(now);
+        if self.taps.len() > 5 {
+            self.taps.remove(0);
+        }
+        if self.taps.len() >= 2 {
+            let span = *self.taps.last().unwrap() - self.taps[0];
+            let interval = span.as_secs_f64() / (self.taps.len() - 1) as f64;
+            transport.set_bpm(60. / interval);
+        }
+    }
+    // Turn the clock on or off, a playing receiver is stopped when the clock goes off
+    pub fn toggle(&mut self, transport: &Transport, com_config: &mut ComConfig) {
+        self.enabled = !self.enabled;
+        if !self.enabled && transport.playing {
+            com_config.send_bytes(&[STOP]);
+        }
+    }
+    // Status line for the info panel, tempo with a dot per beat of the bar
+    pub fn status(&self, transport: &Transport) -> String {
+        let (bar, beat) = transport.bar_beat();
+        let beat_dots: String = (1..=4)
+            .map(|b| if b == beat && transport.playing { '●' } else { '○' })
+            .collect();
+        format!(
+            "BPM {:.1} | {}.{} {} | Clock {}",
+            transport.bpm,
+            bar,
+            beat,
+            beat_dots,
+            if self.enabled { "on" } else { "off" }
+        )
+    }
+}
+
+// Song Position Pointer for a position in beats, 14 bit count of sixteenth notes
+fn song_position(beats: f64) -> [u8; 3] {
+    let sixteenths = ((beats * 4.).floor() as u16).min(0x3FFF);
+    [SONG_POSITION, (sixteenths & 0x7F) as u8, (sixteenths >> 7) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tap_averages_the_last_taps() {
+        let mut clock = Clock::new();
+        let mut transport = Transport::new();
+        let start = Instant::now();
+        // 0.5 s, 0.4 s and 0.6 s apart average to 120 BPM
+        for ms in [0, 500, 900, 1500] {
+            clock.tap_at(start + Duration::from_millis(ms), &mut transport);
+        }
+        assert!((transport.bpm - 120.).abs() < 1e-9);
+        // Only the last five taps count, 1.5 s from 0.5 s to 2 s are 4 intervals of 0.375 s
+        for ms in [1750, 2000] {
+            clock.tap_at(start + Duration::from_millis(ms), &mut transport);
+        }
+        assert!((transport.bpm - 160.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tap_timeout_starts_a_new_tempo() {
+        let mut clock = Clock::new();
+        let mut transport = Transport::new();
+        let start = Instant::now();
+        clock.tap_at(start, &mut transport);
+        clock.tap_at(start + Duration::from_millis(250), &mut transport);
+        assert!((transport.bpm - 240.).abs() < 1e-9);
+        // A single tap after the timeout keeps the tempo, the next one sets it
+        let late = start + Duration::from_millis(250) + TAP_TIMEOUT + Duration::from_millis(1);
+        clock.tap_at(late, &mut transport);
+        assert!((transport.bpm - 240.).abs() < 1e-9);
+        clock.tap_at(late + Duration::from_secs(1), &mut transport);
+        assert!((transport.bpm - 60.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn song_position_counts_sixteenths() {
+        assert_eq!(song_position(0.), [0xF2, 0x00, 0x00]);
+        // 33 beats are 132 sixteenths, 0x84 is 0x04 0x01 as LSB and MSB
+        assert_eq!(song_position(33.1), [0xF2, 0x04, 0x01]);
+        assert_eq!(song_position(1e6), [0xF2, 0x7F, 0x7F]);
+    }
+
+    #[test]
+    fn turning_off_stops_a_playing_receiver() {
+        let mut clock = Clock::new();
+        let mut transport = Transport::new();
+        let sent = ComConfig::new().record(|com| {
+            clock.toggle(&transport, com);
+            clock.toggle(&transport, com);
+        });
+        assert!(sent.is_empty());
+        transport.start();
+        let sent = ComConfig::new().record(|com| {
+            clock.toggle(&transport, com);
+            clock.toggle(&transport, com);
+        });
+        assert_eq!(sent, vec![vec![STOP]]);
+    }
+}
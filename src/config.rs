@@ -0,0 +1,88 @@
+use serde::Deserialize;
+use std::{env, fs, path::PathBuf};
+
+use crate::layout_utils::LayoutConfig;
+use crate::midi_utils::MiddleC;
+use crate::theme::UserTheme;
+
+// Instrument names of a device bank
+#[derive(Deserialize, Clone)]
+pub struct DeviceBank {
+    pub name: String,         //Name shown in the program browser
+    #[serde(default)]
+    pub bank_msb: u8,         //Bank select MSB (CC0)
+    #[serde(default)]
+    pub bank_lsb: u8,         //Bank select LSB (CC32)
+    pub programs: Vec<String>, //Program names, the index is the program number
+}
+// User configuration loaded from tunein.toml
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub devices: Vec<DeviceBank>, //Custom instrument lists
+    pub middle_c: MiddleC,        //Octave naming of middle C, "C4" or "C3"
+    pub layout: LayoutConfig,     //Pane arrangement of the main view
+    pub theme: String,            //Name of the colour theme
+    pub themes: Vec<UserTheme>,   //Custom colour themes
+}
+
+impl Config {
+    // Load the first config file found, defaults if there is none
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = config_paths().into_iter().find(|p| p.is_file()) else {
+            return Ok(Self::default());
+        };
+        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+    // Write the layout into the loaded config file, or a new one in the user config directory
+    pub fn save_layout(layout: &LayoutConfig) -> Result<PathBuf, String> {
+        let paths = config_paths();
+        // An explicit path comes first, then the user config directory
+        let path = paths
+            .iter()
+            .find(|p| p.is_file())
+            .or_else(|| env::var("TUNEIN_CONFIG").is_ok().then(|| &paths[0]))
+            .or_else(|| paths.iter().find(|p| p.ends_with("tunein/config.toml")))
+            .cloned()
+            .unwrap_or_else(|| PathBuf::from("tunein.toml"));
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        // Edit the document so comments and the other settings stay untouched
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| format!("{}: {}", path.display(), e))?;
+        let mut hidden = toml_edit::Array::new();
+        for pane in &layout.hidden {
+            hidden.push(pane.name());
+        }
+        if !document.contains_table("layout") {
+            document["layout"] = toml_edit::table();
+        }
+        document["layout"]["arrangement"] = toml_edit::value(layout.arrangement.name());
+        document["layout"]["hidden"] = toml_edit::value(hidden);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, document.to_string()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+// Places a config file is looked for, in order
+fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Ok(path) = env::var("TUNEIN_CONFIG") {
+        paths.push(PathBuf::from(path));
+    }
+    paths.push(PathBuf::from("tunein.toml"));
+    if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+        paths.push(PathBuf::from(dir).join("tunein").join("config.toml"));
+    }
+    if let Ok(dir) = env::var("HOME") {
+        paths.push(PathBuf::from(dir).join(".config").join("tunein").join("config.toml"));
+    }
+    if let Ok(dir) = env::var("APPDATA") {
+        paths.push(PathBuf::from(dir).join("tunein").join("config.toml"));
+    }
+    paths
+}
@@ -1,9 +1,26 @@
+use std::time::Instant;
+
 use crate::{midi_utils, serial::ComConfig};
-use rand::Rng;
+
+// Tones the synth can play at once
+pub const VOICES: usize = 10;
+// A tone currently played by the synth
+#[derive(Clone)]
+pub struct Signal {
+    pub key: i32,         //Midi key that played the tone
+    pub freq: f64,        //Requested frequency
+    pub velocity: u8,     //Velocity the tone was sent with
+    pub note: u8,         //Midi note the tone was sent as
+    pub channel: u8,      //Midi channel the tone was sent on
+    pub slot: usize,      //DDS slot of the tone, kept while the tone is held
+    pub started: Instant, //Time the tone was turned on
+}
 // DDS main struct
 pub struct DdsData {
-    pub signal_data: Vec<(f64, f64)>, //Current DDS data
-    rand: bool,
+    pub signal_data: Vec<Signal>, //Current DDS data
+    last_channel: u8, //Last channel used for a detuned tone
+    muted: bool,      //Tones are only held, no note on/off is sent for them
+    bend_range: f64,  //Pitch bend range of the synth in semitones, the detunes are based on it
 }
 
 impl DdsData {
@@ -11,49 +28,206 @@ impl DdsData {
     pub fn new() -> Self {
         Self {
             signal_data: Vec::new(),
-            rand: false,
+            last_channel: 0,
+            muted: false,
+            bend_range: midi_utils::PITCH_BEND_RANGE,
         }
     }
     // Add a signal to the dds vec and send the midi message for it
-    pub fn add_signal(&mut self, com_config: &mut ComConfig, freq: f64, mut strength: f64) {
-        if self.rand {
-            strength = rand::rng().random_range(0.0..255.0);
-        }
-        // Check for duplicats and if ther is space left
-        if self.signal_data.len() < 10 {
-            // Send the midi Message to turn tone on
-            com_config.send_midi(0x90, midi_utils::freq_to_note_id(freq), strength as u8);
+    pub fn add_signal(&mut self, com_config: &mut ComConfig, key: i32, freq: f64, velocity: u8) {
+        // Check if there is space left
+        if let Some(slot) = self.free_slot() {
+            let (note, bend) = midi_utils::freq_to_note_bend(freq, self.bend_range);
+            // Tones off the equal tempered grid get their own channel so the bend
+            // doesn't detune the other tones
+            let channel = if bend == midi_utils::PITCH_BEND_CENTER {
+                0
+            } else {
+                let channel = self.free_channel();
+                com_config.send_midi(0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8);
+                channel
+            };
+            // Send the midi Message to turn tone on, unless another key already holds the note
+            if !self.muted && self.holders(channel, note) == 0 {
+                com_config.send_midi(0x90 | channel, note, velocity);
+            }
             // Add to vec
-            self.signal_data.push((freq, strength));
+            self.signal_data.push(Signal {
+                key,
+                freq,
+                velocity,
+                note,
+                channel,
+                slot,
+                started: Instant::now(),
+            });
         }
     }
     // Remove a signal from the dds vec and turn the tone off
     pub fn remove_signal(&mut self, com_config: &mut ComConfig, freq: f64) {
         // Searche for signal
         for i in 0..self.signal_data.len() {
-            if self.signal_data[i].0 == freq {
-                // Turn tone off
-                com_config.send_midi(0x80, midi_utils::freq_to_note_id(freq), 0x00);
-                // Remove entry
-                self.signal_data.remove(i);
+            if self.signal_data[i].freq == freq {
+                // Remove entry and turn the tone off if no other key holds the note
+                let signal = self.signal_data.remove(i);
+                if !self.muted && self.holders(signal.channel, signal.note) == 0 {
+                    com_config.send_midi(0x80 | signal.channel, signal.note, 0x00);
+                }
                 return;
             }
         }
     }
-    // Toggle a signal
-    pub fn toggle_signal(&mut self, com_config: &mut ComConfig, freq: f64, strength: f64) {
-        // Search if a signal with same freq exists
+    // Toggle all signals of a key, a key plays several tones in chord mode
+    pub fn toggle_key(&mut self, com_config: &mut ComConfig, key: i32, freqs: &[f64], velocity: u8) {
+        // Search if the key is already playing and release all of its tones together
+        if self.signal_data.iter().any(|s| s.key == key) {
+            let (released, held): (Vec<Signal>, Vec<Signal>) =
+                self.signal_data.drain(..).partition(|s| s.key == key);
+            self.signal_data = held;
+            // Notes still held by another key keep sounding
+            for signal in released {
+                if !self.muted && self.holders(signal.channel, signal.note) == 0 {
+                    com_config.send_midi(0x80 | signal.channel, signal.note, 0x00);
+                }
+            }
+            return;
+        }
+
+        // If the key wasn't playing, turn its tones on
+        for freq in freqs {
+            self.add_signal(com_config, key, *freq, velocity);
+        }
+    }
+    // Forget all tones and unmute without sending anything, used after a panic
+    pub fn clear(&mut self) {
+        self.signal_data.clear();
+        self.muted = false;
+    }
+    // Mute or unmute the held tones and turn them off or on again
+    pub fn set_muted(&mut self, com_config: &mut ComConfig, muted: bool) {
+        if self.muted == muted {
+            return;
+        }
+        self.muted = muted;
+        for (i, signal) in self.signal_data.iter().enumerate() {
+            // Send once per note, a chord can hold the same note from several keys
+            if self.signal_data[..i]
+                .iter()
+                .any(|s| s.channel == signal.channel && s.note == signal.note)
+            {
+                continue;
+            }
+            if muted {
+                com_config.send_midi(0x80 | signal.channel, signal.note, 0x00);
+            } else {
+                com_config.send_midi(0x90 | signal.channel, signal.note, signal.velocity);
+            }
+        }
+    }
+    // Channels in use with the bend that detunes their tones, the first channel is always in use
+    pub fn channel_bends(&self) -> Vec<(u8, u16)> {
+        let mut bends = vec![(0, midi_utils::PITCH_BEND_CENTER)];
         for signal in &self.signal_data {
-            if signal.0 == freq {
-                self.remove_signal(com_config, freq);
-                return;
+            if !bends.iter().any(|(channel, _)| *channel == signal.channel) {
+                let (_, bend) = midi_utils::freq_to_note_bend(signal.freq, self.bend_range);
+                bends.push((signal.channel, bend));
             }
         }
+        bends
+    }
+    // Use the pitch bend range the synth was set to, the caller sends the new bends
+    pub fn set_bend_range(&mut self, bend_range: f64) {
+        self.bend_range = bend_range;
+    }
+    // Lowest DDS slot no held tone uses
+    pub fn free_slot(&self) -> Option<usize> {
+        (0..VOICES).find(|slot| !self.signal_data.iter().any(|s| s.slot == *slot))
+    }
+    // Number of held tones sounding a note on a channel
+    fn holders(&self, channel: u8, note: u8) -> usize {
+        self.signal_data
+            .iter()
+            .filter(|s| s.channel == channel && s.note == note)
+            .count()
+    }
+    // Rotate through the member channels 2-16 (MPE style) and skip the ones in use
+    fn free_channel(&mut self) -> u8 {
+        for _ in 0..15 {
+            self.last_channel = self.last_channel % 15 + 1;
+            if !self.signal_data.iter().any(|s| s.channel == self.last_channel) {
+                break;
+            }
+        }
+        self.last_channel
+    }
+}
 
-        // If signal wasn't in array, turn it on
-        self.add_signal(com_config, freq, strength);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_note_is_released_by_the_last_key() {
+        let mut dds = DdsData::new();
+        // Both chords hold A5
+        let on = ComConfig::new().record(|com| {
+            dds.toggle_key(com, 1, &[440.0, 880.0], 100);
+            dds.toggle_key(com, 2, &[880.0, 1760.0], 100);
+        });
+        assert_eq!(on, vec![vec![0x90, 69, 100], vec![0x90, 81, 100], vec![0x90, 93, 100]]);
+        let first = ComConfig::new().record(|com| dds.toggle_key(com, 1, &[], 100));
+        assert_eq!(first, vec![vec![0x80, 69, 0]]);
+        let last = ComConfig::new().record(|com| dds.toggle_key(com, 2, &[], 100));
+        assert_eq!(last, vec![vec![0x80, 81, 0], vec![0x80, 93, 0]]);
+        assert!(dds.signal_data.is_empty());
+    }
+
+    #[test]
+    fn remove_signal_keeps_shared_note() {
+        let mut dds = DdsData::new();
+        ComConfig::new().record(|com| {
+            dds.toggle_key(com, 1, &[440.0], 100);
+            dds.toggle_key(com, 2, &[440.0], 100);
+        });
+        let first = ComConfig::new().record(|com| dds.remove_signal(com, 440.0));
+        assert!(first.is_empty());
+        let last = ComConfig::new().record(|com| dds.remove_signal(com, 440.0));
+        assert_eq!(last, vec![vec![0x80, 69, 0]]);
+    }
+
+    #[test]
+    fn tones_keep_their_slot() {
+        let mut dds = DdsData::new();
+        ComConfig::new().record(|com| {
+            dds.toggle_key(com, 1, &[440.0], 100);
+            dds.toggle_key(com, 2, &[880.0], 100);
+            dds.toggle_key(com, 1, &[], 100);
+            dds.toggle_key(com, 3, &[1760.0], 100);
+        });
+        let slots: Vec<(i32, usize)> = dds.signal_data.iter().map(|s| (s.key, s.slot)).collect();
+        assert_eq!(slots, vec![(2, 1), (3, 0)]);
+        assert_eq!(dds.free_slot(), Some(2));
+    }
+
+    #[test]
+    fn mute_sends_shared_note_once() {
+        let mut dds = DdsData::new();
+        ComConfig::new().record(|com| {
+            dds.toggle_key(com, 1, &[440.0], 100);
+            dds.toggle_key(com, 2, &[440.0, 880.0], 90);
+        });
+        let muted = ComConfig::new().record(|com| dds.set_muted(com, true));
+        assert_eq!(muted, vec![vec![0x80, 69, 0], vec![0x80, 81, 0]]);
+        let unmuted = ComConfig::new().record(|com| dds.set_muted(com, false));
+        assert_eq!(unmuted, vec![vec![0x90, 69, 100], vec![0x90, 81, 90]]);
     }
-    pub fn toggle_rand(&mut self){
-        self.rand = !self.rand;
+
+    #[test]
+    fn clear_unmutes() {
+        let mut dds = DdsData::new();
+        ComConfig::new().record(|com| dds.set_muted(com, true));
+        dds.clear();
+        let on = ComConfig::new().record(|com| dds.toggle_key(com, 1, &[440.0], 100));
+        assert_eq!(on, vec![vec![0x90, 69, 100]]);
     }
 }
@@ -0,0 +1,568 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+use std::{
+    f64::consts::PI,
+    fs::File,
+    io::{BufWriter, Seek, Write},
+    thread::{self, JoinHandle},
+};
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::midi_input::{MidiMessage, MidiParser};
+use crate::midi_utils::{self, PITCH_BEND_CENTER, PITCH_BEND_RANGE};
+use crate::popup_utils::popup_area;
+use crate::sequencer::Sequencer;
+use crate::serial::ComConfig;
+use crate::transport::Transport;
+use crate::theme::Theme;
+
+// Selectable sample rates
+const SAMPLE_RATES: [u32; 4] = [22050, 44100, 48000, 96000];
+// Silence rendered after the last event in seconds
+const TAIL: f64 = 0.5;
+// Longest rendering in seconds
+const MAX_LENGTH: f64 = 600.;
+
+// Waveform stored in the lookup table
+#[derive(Clone, Copy)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Sawtooth,
+    Triangle,
+}
+// Events the model is driven with
+#[derive(Clone, Copy)]
+enum Source {
+    Session,  //Recorded bytes sent by TuneIn
+    Sequence, //The sequencer pattern
+}
+// Parameters of the modelled synth
+#[derive(Clone)]
+pub struct ModelConfig {
+    pub voices: usize,         //Simultaneous tones
+    pub accumulator_bits: u32, //Width of the phase accumulator
+    pub table_bits: u32,       //The lookup table has 2^table_bits entries
+    pub dac_bits: u32,         //Resolution of the table entries and the output
+    pub sample_rate: u32,
+    pub waveform: Waveform,
+}
+// A playing tone of the model
+struct Voice {
+    channel: u8,
+    note: u8,
+    phase: u64,     //Phase accumulator
+    increment: u64, //Tuning word added every sample
+    amplitude: f64, //Velocity scaled to 0-1
+}
+// Main dds model struct
+pub struct DdsModel {
+    config: ModelConfig,
+    source: Source,
+    loops: u32,                      //Times the sequence is rendered
+    session: Vec<(f64, Vec<u8>)>,    //Last recorded session
+    rendering: bool,                 //Path input is open
+    input: Input,                    //Input for the wav path
+    result: Option<String>,          //Outcome of the last rendering
+    job: Option<(String, JoinHandle<Result<f64, String>>)>, //Path and thread of a running rendering
+}
+
+impl Waveform {
+    // Value at a phase from 0 to 1, between -1 and 1
+    pub fn sample(&self, phase: f64) -> f64 {
+        match self {
+            Waveform::Sine => (2. * PI * phase).sin(),
+            Waveform::Square => if phase < 0.5 { 1. } else { -1. },
+            Waveform::Sawtooth => 2. * phase - 1.,
+            Waveform::Triangle => 1. - 4. * (phase - 0.5).abs(),
+        }
+    }
+    // The following waveform
+    pub fn next(&self) -> Self {
+        match self {
+            Waveform::Sine => Waveform::Square,
+            Waveform::Square => Waveform::Sawtooth,
+            Waveform::Sawtooth => Waveform::Triangle,
+            Waveform::Triangle => Waveform::Sine,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Waveform::Sine => "Sine",
+            Waveform::Square => "Square",
+            Waveform::Sawtooth => "Sawtooth",
+            Waveform::Triangle => "Triangle",
+        }
+    }
+}
+
+impl ModelConfig {
+    // Tuning word of a frequency, rounded like the hardware does
+    fn increment(&self, freq: f64) -> u64 {
+        (freq * (1u64 << self.accumulator_bits) as f64 / self.sample_rate as f64).round() as u64
+    }
+    // Largest value of the dac
+    fn dac_max(&self) -> f64 {
+        ((1u64 << (self.dac_bits - 1)) - 1) as f64
+    }
+    // Lookup table with entries quantized to the dac resolution
+    fn table(&self) -> Vec<f64> {
+        let size = 1usize << self.table_bits;
+        (0..size)
+            .map(|i| {
+                let value = self.waveform.sample(i as f64 / size as f64);
+                (value * self.dac_max()).round() / self.dac_max()
+            })
+            .collect()
+    }
+}
+
+// Create the wav file a rendering with a config is written to
+fn wav_writer(config: &ModelConfig, path: &str) -> Result<WavWriter<BufWriter<File>>, String> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: config.sample_rate,
+        bits_per_sample: if config.dac_bits <= 16 { 16 } else { 24 },
+        sample_format: SampleFormat::Int,
+    };
+    WavWriter::create(path, spec).map_err(|e| e.to_string())
+}
+
+// Render timed midi bytes through the model into a wav writer, returns the length in seconds
+pub fn render<W: Write + Seek>(
+    config: &ModelConfig,
+    events: &[(f64, Vec<u8>)],
+    mut writer: WavWriter<W>,
+) -> Result<f64, String> {
+    let Some((last, _)) = events.last() else {
+        return Err(String::from("No events to render"));
+    };
+    let length = (last + TAIL).min(MAX_LENGTH);
+    let bits = writer.spec().bits_per_sample;
+    let wav_max = ((1u32 << (bits - 1)) - 1) as f64;
+
+    let table = config.table();
+    let mask = (1u64 << config.accumulator_bits) - 1;
+    let shift = config.accumulator_bits - config.table_bits;
+    let mut voices: Vec<Voice> = Vec::new(); //In note on order, the oldest voice is stolen
+    let mut bends = [PITCH_BEND_CENTER; 16];
+    let mut ranges = [PITCH_BEND_RANGE; 16]; //Bend range per channel, set with RPN 0
+    let mut rpns = [[127u8; 2]; 16]; //Selected RPN per channel, MSB and LSB
+    let mut parser = MidiParser::new();
+    let mut next_event = 0;
+
+    let samples = (length * config.sample_rate as f64) as u64;
+    for sample in 0..samples {
+        let time = sample as f64 / config.sample_rate as f64;
+        // Apply the events that are due
+        while next_event < events.len() && events[next_event].0 <= time {
+            for message in parser.feed(&events[next_event].1) {
+                let MidiMessage::Channel(data) = message else {
+                    continue;
+                };
+                let channel = data[0] & 0x0F;
+                match (data[0] & 0xF0, data.get(1).copied(), data.get(2).copied()) {
+                    (0x90, Some(note), Some(velocity)) if velocity > 0 => {
+                        voices.retain(|v| v.channel != channel || v.note != note);
+                        if voices.len() >= config.voices {
+                            voices.remove(0);
+                        }
+                        voices.push(Voice {
+                            channel,
+                            note,
+                            phase: 0,
+                            increment: config.increment(bent_freq(
+                                note,
+                                bends[channel as usize],
+                                ranges[channel as usize],
+                            )),
+                            amplitude: velocity as f64 / 127.,
+                        });
+                    }
+                    (0x80 | 0x90, Some(note), _) => {
+                        voices.retain(|v| v.channel != channel || v.note != note)
+                    }
+                    // All sound off and all notes off
+                    (0xB0, Some(120 | 123), _) => voices.retain(|v| v.channel != channel),
+                    (0xB0, Some(101), Some(value)) => rpns[channel as usize][0] = value,
+                    (0xB0, Some(100), Some(value)) => rpns[channel as usize][1] = value,
+                    // Data entry for RPN 0 sets the bend range
+                    (0xB0, Some(6), Some(range)) if rpns[channel as usize] == [0, 0] => {
+                        ranges[channel as usize] = range as f64;
+                        let bend = bends[channel as usize];
+                        for voice in voices.iter_mut().filter(|v| v.channel == channel) {
+                            voice.increment =
+                                config.increment(bent_freq(voice.note, bend, range as f64));
+                        }
+                    }
+                    (0xE0, Some(lsb), Some(msb)) => {
+                        let bend = (msb as u16) << 7 | lsb as u16;
+                        bends[channel as usize] = bend;
+                        let range = ranges[channel as usize];
+                        for voice in voices.iter_mut().filter(|v| v.channel == channel) {
+                            voice.increment = config.increment(bent_freq(voice.note, bend, range));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            next_event += 1;
+        }
+        // Mix the voices like a dac shared by all of them
+        let mut mix = 0.;
+        for voice in voices.iter_mut() {
+            mix += table[(voice.phase >> shift) as usize] * voice.amplitude;
+            voice.phase = (voice.phase + voice.increment) & mask;
+        }
+        let output = (mix / config.voices as f64 * config.dac_max()).round() / config.dac_max();
+        writer
+            .write_sample((output * wav_max).round() as i32)
+            .map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(length)
+}
+
+// Frequency of a note with the bend and bend range of its channel
+fn bent_freq(note: u8, bend: u16, range: f64) -> f64 {
+    let semitones = (bend as f64 - PITCH_BEND_CENTER as f64) / PITCH_BEND_CENTER as f64 * range;
+    midi_utils::note_to_freq(note) * 2f64.powf(semitones / 12.)
+}
+
+impl DdsModel {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            config: ModelConfig {
+                voices: 10,
+                accumulator_bits: 32,
+                table_bits: 10,
+                dac_bits: 12,
+                sample_rate: 48000,
+                waveform: Waveform::Sine,
+            },
+            source: Source::Session,
+            loops: 4,
+            session: Vec::new(),
+            rendering: false,
+            input: Input::new(),
+            result: None,
+            job: None,
+        }
+    }
+    // Start rendering the selected source into a file, long renderings would block the ui
+    fn render_to(
+        &mut self,
+        path: &str,
+        sequencer: &Sequencer,
+        transport: &Transport,
+    ) -> Result<(), String> {
+        if self.job.is_some() {
+            return Err(String::from("A rendering is still running"));
+        }
+        let events = match self.source {
+            Source::Session => self.session.clone(),
+            Source::Sequence => sequencer.events(transport.bpm, self.loops),
+        };
+        if events.is_empty() {
+            return Err(String::from("No events to render"));
+        }
+        // The file is created here so a bad path is reported in the input
+        let writer = wav_writer(&self.config, path)?;
+        let config = self.config.clone();
+        let handle = thread::spawn(move || render(&config, &events, writer));
+        self.job = Some((path.to_string(), handle));
+        self.result = Some(format!("Rendering to {}", path));
+        Ok(())
+    }
+    // Collect the outcome of a finished rendering
+    pub fn on_tick(&mut self) {
+        if !self.job.as_ref().is_some_and(|(_, handle)| handle.is_finished()) {
+            return;
+        }
+        let Some((path, handle)) = self.job.take() else {
+            return;
+        };
+        self.result = Some(match handle.join() {
+            Ok(Ok(length)) => format!("Rendered {:.1} s to {}", length, path),
+            Ok(Err(e)) => format!("Rendering {} failed: {}", path, e),
+            Err(_) => format!("Rendering {} failed", path),
+        });
+    }
+    // Event handling
+    pub fn key_event(
+        &mut self,
+        key: KeyEvent,
+        com_config: &mut ComConfig,
+        sequencer: &Sequencer,
+        transport: &Transport,
+    ) -> AppState {
+        let mut app_state: AppState = AppState::Model;
+        // Path input for rendering
+        if self.rendering {
+            match key.code {
+                KeyCode::Enter => {
+                    let path = self.input.submit_message();
+                    match self.render_to(path.trim(), sequencer, transport) {
+                        Ok(()) => self.rendering = false,
+                        Err(e) => self.input.display_error(e),
+                    }
+                }
+                _ => {
+                    if self.input.key_event(key) {
+                        self.rendering = false;
+                    }
+                }
+            }
+            return app_state;
+        }
+        let config = &mut self.config;
+        match key.code {
+            // Quit, a recording continues
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Render
+            KeyCode::Enter => self.rendering = true,
+            // Record a session
+            KeyCode::Char('r') => {
+                if com_config.is_recording() {
+                    self.session = com_config.stop_recording();
+                    self.result = Some(format!("Recorded {} messages", self.session.len()));
+                } else {
+                    com_config.start_recording();
+                }
+            }
+            // Source
+            KeyCode::Char('s') => {
+                self.source = match self.source {
+                    Source::Session => Source::Sequence,
+                    Source::Sequence => Source::Session,
+                }
+            }
+            KeyCode::Char('[') => self.loops = (self.loops - 1).max(1),
+            KeyCode::Char(']') => self.loops = (self.loops + 1).min(64),
+            // Model parameters
+            KeyCode::Char('v') => config.voices = (config.voices - 1).max(1),
+            KeyCode::Char('V') => config.voices = (config.voices + 1).min(16),
+            KeyCode::Char('a') => {
+                config.accumulator_bits = (config.accumulator_bits - 1).max(config.table_bits.max(16))
+            }
+            KeyCode::Char('A') => config.accumulator_bits = (config.accumulator_bits + 1).min(48),
+            KeyCode::Char('t') => config.table_bits = (config.table_bits - 1).max(4),
+            KeyCode::Char('T') => {
+                config.table_bits = (config.table_bits + 1).min(16.min(config.accumulator_bits))
+            }
+            KeyCode::Char('b') => config.dac_bits = (config.dac_bits - 1).max(4),
+            KeyCode::Char('B') => config.dac_bits = (config.dac_bits + 1).min(24),
+            KeyCode::Char('f') => {
+                let index = SAMPLE_RATES
+                    .iter()
+                    .position(|r| *r == config.sample_rate)
+                    .unwrap_or(0);
+                config.sample_rate = SAMPLE_RATES[(index + 1) % SAMPLE_RATES.len()];
+            }
+            KeyCode::Char('w') => config.waveform = config.waveform.next(),
+            _ => {}
+        }
+        app_state
+    }
+    //Render the model settings
+    pub fn show_model_popup(&mut self, frame: &mut Frame, com_config: &ComConfig, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 60);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(3)])
+            .split(area);
+
+        let config = &self.config;
+        let source = match self.source {
+            Source::Session => format!("Recorded session ({} messages)", self.session.len()),
+            Source::Sequence => format!("Sequencer pattern, {} loops", self.loops),
+        };
+        // Smallest frequency step of the accumulator
+        let resolution = config.sample_rate as f64 / (1u64 << config.accumulator_bits) as f64;
+        let recording = if com_config.recording_full() {
+            Span::styled("Recording full, later messages are dropped", Style::default().fg(theme.error))
+        } else if com_config.is_recording() {
+            Span::styled("Recording", Style::default().fg(theme.error))
+        } else {
+            Span::raw("Not recording")
+        };
+        let text = Text::from(vec![
+            Line::from(Span::styled(
+                " Reference DDS model",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(format!(" Voices: {}", config.voices)),
+            Line::from(format!(
+                " Phase accumulator: {} bit ({:.6} Hz per step)",
+                config.accumulator_bits, resolution
+            )),
+            Line::from(format!(
+                " Lookup table: {} entries | Waveform: {}",
+                1u64 << config.table_bits,
+                config.waveform.name()
+            )),
+            Line::from(format!(" DAC: {} bit", config.dac_bits)),
+            Line::from(format!(" Sample rate: {} Hz", config.sample_rate)),
+            Line::from(""),
+            Line::from(format!(" Source: {}", source)),
+            Line::from(vec![Span::raw(" Session: "), recording]),
+            Line::from(format!(" {}", self.result.clone().unwrap_or_default())),
+            Line::from(""),
+            Line::from(" • Records every message TuneIn sends, also without a connected port."),
+            Line::from(" • Detuned tones use the pitch bend of their channel."),
+        ]);
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        // Path input for rendering
+        frame.render_widget(
+            self.input.get_input(String::from("Render to (.wav)"), theme),
+            vertical_layout[1],
+        );
+        if self.rendering {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[1].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[1].y + vertical_layout[1].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.rendering {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::MODEL]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+    use std::{io::Cursor, time::Duration};
+
+    fn note(seconds: f64) -> Vec<(f64, Vec<u8>)> {
+        vec![(0., vec![0x90, 69, 127]), (seconds, vec![0x80, 69, 0])]
+    }
+
+    // Render into memory and read the samples back
+    fn render_samples(config: &ModelConfig, events: &[(f64, Vec<u8>)]) -> (f64, Vec<i32>) {
+        let mut data = Cursor::new(Vec::new());
+        let writer = WavWriter::new(&mut data, wav_spec(config)).unwrap();
+        let length = render(config, events, writer).unwrap();
+        data.set_position(0);
+        let samples = WavReader::new(data).unwrap().samples().map(|s| s.unwrap()).collect();
+        (length, samples)
+    }
+
+    fn wav_spec(config: &ModelConfig) -> WavSpec {
+        WavSpec {
+            channels: 1,
+            sample_rate: config.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        }
+    }
+
+    #[test]
+    fn renders_note_and_tail() {
+        let model = DdsModel::new();
+        let (length, samples) = render_samples(&model.config, &note(0.1));
+        assert!((length - 0.1 - TAIL).abs() < 1e-9);
+        assert_eq!(samples.len(), (length * 48000.) as usize);
+        assert!(samples[..4800].iter().any(|s| *s != 0));
+        assert!(samples[4801..].iter().all(|s| *s == 0));
+    }
+
+    // Rising zero crossings in the first second
+    fn periods(samples: &[i32]) -> usize {
+        samples[..48000].windows(2).filter(|w| w[0] < 0 && w[1] >= 0).count()
+    }
+
+    // Different sample values in the first second
+    fn levels(samples: &[i32]) -> usize {
+        let mut levels = samples[..48000].to_vec();
+        levels.sort();
+        levels.dedup();
+        levels.len()
+    }
+
+    #[test]
+    fn renders_the_frequency_of_the_tuning_word() {
+        let mut config = DdsModel::new().config;
+        let (_, samples) = render_samples(&config, &note(1.));
+        assert!(periods(&samples).abs_diff(440) <= 1);
+        // A 12 bit accumulator rounds A4 to 38 * 48000 / 4096 = 445.3 Hz
+        config.accumulator_bits = 12;
+        assert_eq!(config.increment(440.), 38);
+        let (_, samples) = render_samples(&config, &note(1.));
+        assert!(periods(&samples).abs_diff(445) <= 1);
+    }
+
+    #[test]
+    fn small_table_and_dac_quantize_the_output() {
+        let mut config = DdsModel::new().config;
+        let (_, fine) = render_samples(&config, &note(1.));
+        assert!(levels(&fine) > 100);
+        // 16 table entries give at most 16 levels
+        config.table_bits = 4;
+        let (_, table) = render_samples(&config, &note(1.));
+        assert!(levels(&table) <= 16);
+        assert_ne!(table, fine);
+        // A 4 bit dac steps by 1/7, one voice of ten peaks at 0.1 and rounds to -1, 0 and 1 step
+        config.table_bits = 10;
+        config.dac_bits = 4;
+        let (_, dac) = render_samples(&config, &note(1.));
+        assert_eq!(levels(&dac), 3);
+        assert_ne!(dac, fine);
+    }
+
+    #[test]
+    fn rejects_empty_events() {
+        let model = DdsModel::new();
+        let writer = WavWriter::new(Cursor::new(Vec::new()), wav_spec(&model.config)).unwrap();
+        assert!(render(&model.config, &[], writer).is_err());
+    }
+
+    #[test]
+    fn renders_in_the_background() {
+        let mut model = DdsModel::new();
+        model.session = note(0.2);
+        let path = std::env::temp_dir().join(format!("tunein-model-{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+        model
+            .render_to(path, &Sequencer::new(), &Transport::new())
+            .unwrap();
+        assert!(model.render_to(path, &Sequencer::new(), &Transport::new()).is_err());
+        for _ in 0..500 {
+            model.on_tick();
+            if model.job.is_none() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = std::fs::remove_file(path);
+        assert!(model.result.unwrap().starts_with("Rendered 0.7 s"));
+    }
+}
@@ -0,0 +1,522 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph, Row, Table},
+};
+use std::{
+    fs::File,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use crate::AppState;
+use crate::all_notes_off;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::midi_input::data_length;
+use crate::midi_utils;
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// Cases sent in one batch
+const BATCH_SIZE: u32 = 16;
+// Pause between two batches
+const BATCH_INTERVAL: Duration = Duration::from_millis(10);
+// Time the device gets to answer the liveness probe
+const LIVENESS_TIMEOUT: Duration = Duration::from_millis(500);
+// Universal identity request, answered or echoed by a device that is still alive
+const IDENTITY_REQUEST: [u8; 6] = [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+// Real time bytes put inside messages, system reset is left out so the device state stays comparable
+const REAL_TIME: [u8; 5] = [0xF8, 0xFA, 0xFB, 0xFC, 0xFE];
+// Status bytes without a meaning in the spec
+const UNDEFINED: [u8; 4] = [0xF4, 0xF5, 0xF9, 0xFD];
+// Data values at the edges of the range
+const EDGE_VALUES: [u8; 6] = [0, 1, 63, 64, 126, 127];
+
+// Kinds of generated streams
+#[derive(Clone, Copy)]
+enum FuzzCase {
+    Valid,             //Channel message with random values
+    EdgeValues,        //Channel message with values at the edges of the range
+    RunningStatus,     //Several messages sharing one status byte
+    SysEx,             //Complete sysex with random length
+    Truncated,         //Channel message missing data bytes
+    StrayData,         //Data bytes without a status
+    UnterminatedSysEx, //Sysex without F7
+    RealTimeInside,    //Real time byte in the middle of a message
+    OutOfRange,        //Data byte with the high bit set
+    Undefined,         //Undefined status byte
+}
+const CASES: [(FuzzCase, &str); 10] = [
+    (FuzzCase::Valid, "Valid"),
+    (FuzzCase::EdgeValues, "Edge values"),
+    (FuzzCase::RunningStatus, "Running status"),
+    (FuzzCase::SysEx, "SysEx"),
+    (FuzzCase::Truncated, "Truncated"),
+    (FuzzCase::StrayData, "Stray data"),
+    (FuzzCase::UnterminatedSysEx, "Unterminated SysEx"),
+    (FuzzCase::RealTimeInside, "Real time inside"),
+    (FuzzCase::OutOfRange, "Out of range"),
+    (FuzzCase::Undefined, "Undefined status"),
+];
+// When a run ends
+#[derive(Clone, Copy)]
+enum FuzzLimit {
+    Time,  //Run time in seconds
+    Count, //Number of generated cases
+}
+// Main fuzzer struct
+pub struct Fuzzer {
+    seed: u64,                      //Seed of the next run
+    rng: StdRng,                    //Generator of the current run
+    limit: FuzzLimit,
+    duration: u64,                  //Run time in seconds
+    count: u32,                     //Cases per run
+    check_liveness: bool,           //Send an identity request after every batch
+    running: bool,
+    start: Instant,                 //Start of the current run
+    next_batch: Instant,            //Earliest time for the next batch
+    probe_sent: Option<Instant>,    //Liveness probe waiting for an answer
+    alive: bool,                    //The device answered the probe
+    cases: u32,                     //Cases sent in this run
+    batches: u32,                   //Batches sent in this run
+    bytes: u64,                     //Bytes sent in this run
+    write_errors: u32,
+    case_counts: [u32; CASES.len()],
+    log: Option<File>,              //Log of the seed and all sent batches
+    result: Option<String>,         //Outcome of the last run
+    entering_seed: bool,            //Seed input is open
+    input: Input,                   //Input for the seed
+}
+
+impl Fuzzer {
+    //Constructor with a random seed
+    pub fn new() -> Self {
+        let seed = rand::random();
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            limit: FuzzLimit::Time,
+            duration: 10,
+            count: 1000,
+            check_liveness: true,
+            running: false,
+            start: Instant::now(),
+            next_batch: Instant::now(),
+            probe_sent: None,
+            alive: false,
+            cases: 0,
+            batches: 0,
+            bytes: 0,
+            write_errors: 0,
+            case_counts: [0; CASES.len()],
+            log: None,
+            result: None,
+            entering_seed: false,
+            input: Input::new(),
+        }
+    }
+    // Start a run with the current seed
+    fn start(&mut self, com_config: &ComConfig) {
+        if !com_config.is_connected() {
+            self.result = Some(String::from("No port connected"));
+            return;
+        }
+        let path = format!("tunein-fuzz-{}.log", self.seed);
+        let mut log = match File::create(&path) {
+            Ok(log) => log,
+            Err(e) => {
+                self.result = Some(format!("{}: {}", path, e));
+                return;
+            }
+        };
+        let _ = writeln!(log, "seed = {}", self.seed);
+        self.log = Some(log);
+        self.rng = StdRng::seed_from_u64(self.seed);
+        self.start = Instant::now();
+        self.next_batch = Instant::now();
+        self.probe_sent = None;
+        self.cases = 0;
+        self.batches = 0;
+        self.bytes = 0;
+        self.write_errors = 0;
+        self.case_counts = [0; CASES.len()];
+        self.result = Some(format!("Logging to {}", path));
+        self.running = true;
+    }
+    // End the run with a result and release what the fuzzed messages left on
+    fn finish(&mut self, com_config: &mut ComConfig, result: String) {
+        // Notes, controllers and pitch bend of any channel may still be set
+        if all_notes_off::send_panic(com_config, false).is_err() {
+            self.write_errors += 1;
+        }
+        self.end(result);
+    }
+    // Stop a run without releasing anything, the panic key sends its own panic
+    pub fn stop(&mut self) {
+        if self.running {
+            self.end(format!("Stopped after {} cases (seed {})", self.cases, self.seed));
+        }
+    }
+    // Close the log and keep the result
+    fn end(&mut self, result: String) {
+        if let Some(log) = self.log.as_mut() {
+            let _ = writeln!(log, "# {}", result);
+        }
+        self.log = None;
+        self.running = false;
+        self.result = Some(result);
+    }
+    // Mark the device alive if the probe came back
+    pub fn receive(&mut self, message: &[u8]) {
+        if self.probe_sent.is_some() && matches!(message, [0xF0, 0x7E, _, 0x06, ..]) {
+            self.alive = true;
+        }
+    }
+    // Send the next batch and check the liveness of the device
+    pub fn on_tick(&mut self, com_config: &mut ComConfig) {
+        if !self.running {
+            return;
+        }
+        if let Some(sent) = self.probe_sent {
+            if self.alive {
+                self.probe_sent = None;
+            } else if sent.elapsed() > LIVENESS_TIMEOUT {
+                self.finish(com_config, format!(
+                    "Device stopped answering after batch {} (seed {})",
+                    self.batches, self.seed
+                ));
+                return;
+            } else {
+                return;
+            }
+        }
+        let done = match self.limit {
+            FuzzLimit::Time => self.start.elapsed() >= Duration::from_secs(self.duration),
+            FuzzLimit::Count => self.cases >= self.count,
+        };
+        if done {
+            self.finish(com_config, format!(
+                "Finished {} cases in {} batches (seed {})",
+                self.cases, self.batches, self.seed
+            ));
+            return;
+        }
+        if Instant::now() < self.next_batch {
+            return;
+        }
+
+        // Generate the batch
+        let cases = match self.limit {
+            FuzzLimit::Time => BATCH_SIZE,
+            FuzzLimit::Count => BATCH_SIZE.min(self.count - self.cases),
+        };
+        let mut stream = Vec::new();
+        for _ in 0..cases {
+            let index = self.rng.random_range(0..CASES.len());
+            stream.extend(generate(&mut self.rng, CASES[index].0));
+            self.case_counts[index] += 1;
+        }
+        self.cases += cases;
+        self.batches += 1;
+        self.bytes += stream.len() as u64;
+        if let Some(log) = self.log.as_mut() {
+            let _ = writeln!(log, "batch {}: {}", self.batches, midi_utils::format_hex(&stream));
+        }
+        if com_config.try_send_bytes(&stream).is_err() {
+            self.write_errors += 1;
+        }
+        self.next_batch = Instant::now() + BATCH_INTERVAL;
+
+        if self.check_liveness {
+            self.alive = false;
+            self.probe_sent = Some(Instant::now());
+            if com_config.try_send_bytes(&IDENTITY_REQUEST).is_err() {
+                self.write_errors += 1;
+            }
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, com_config: &mut ComConfig) -> AppState {
+        let mut app_state: AppState = AppState::Fuzz;
+        // Seed input
+        if self.entering_seed {
+            match key.code {
+                KeyCode::Enter => match self.input.submit_message().trim().parse() {
+                    Ok(seed) => {
+                        self.seed = seed;
+                        self.entering_seed = false;
+                    }
+                    Err(_) => self.input.display_error(String::from("Not a valid seed")),
+                },
+                _ => {
+                    if self.input.key_event(key) {
+                        self.entering_seed = false;
+                    }
+                }
+            }
+            return app_state;
+        }
+        match key.code {
+            // Quit, a running fuzzer continues
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Start or stop
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if self.running {
+                    self.finish(com_config, format!(
+                        "Stopped after {} cases (seed {})",
+                        self.cases, self.seed
+                    ));
+                } else {
+                    self.start(com_config);
+                }
+            }
+            // Seed
+            KeyCode::Char('n') if !self.running => self.seed = rand::random(),
+            KeyCode::Char('s') if !self.running => self.entering_seed = true,
+            // Limit
+            KeyCode::Char('t') if !self.running => {
+                self.limit = match self.limit {
+                    FuzzLimit::Time => FuzzLimit::Count,
+                    FuzzLimit::Count => FuzzLimit::Time,
+                }
+            }
+            KeyCode::Char('-') if !self.running => match self.limit {
+                FuzzLimit::Time => self.duration = (self.duration.saturating_sub(5)).max(5),
+                FuzzLimit::Count => self.count = (self.count.saturating_sub(500)).max(500),
+            },
+            KeyCode::Char('+') if !self.running => match self.limit {
+                FuzzLimit::Time => self.duration = (self.duration + 5).min(3600),
+                FuzzLimit::Count => self.count = (self.count + 500).min(1_000_000),
+            },
+            // Liveness check
+            KeyCode::Char('l') if !self.running => self.check_liveness = !self.check_liveness,
+            _ => {}
+        }
+        app_state
+    }
+    //Render the fuzzer screen
+    pub fn show_fuzz_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 70, 70);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(9),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        let state = if self.running {
+            Span::styled("Running", Style::default().fg(theme.highlight))
+        } else {
+            Span::raw("Stopped")
+        };
+        let limit = match self.limit {
+            FuzzLimit::Time => format!("{} s", self.duration),
+            FuzzLimit::Count => format!("{} cases", self.count),
+        };
+        let result_style = if self.result.as_deref().is_some_and(|r| r.starts_with("Device")) {
+            Style::default().fg(theme.error)
+        } else {
+            Style::default()
+        };
+        let text = Text::from(vec![
+            Line::from(vec![
+                Span::styled(" MIDI fuzzer ", Style::default().add_modifier(Modifier::BOLD)),
+                state,
+            ]),
+            Line::from(format!(
+                " Seed: {} | Limit: {} | Liveness check: {}",
+                self.seed,
+                limit,
+                if self.check_liveness { "On" } else { "Off" }
+            )),
+            Line::from(format!(
+                " Cases: {} | Batches: {} | Bytes: {} | Write errors: {}",
+                self.cases, self.batches, self.bytes, self.write_errors
+            )),
+            Line::from(Span::styled(
+                format!(" {}", self.result.clone().unwrap_or_default()),
+                result_style,
+            )),
+            Line::from(""),
+            Line::from(" • The same seed always generates the same stream."),
+            Line::from(" • SysEx cases use the non-commercial id 7D, the liveness probe is an identity request."),
+        ]);
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        // Sent cases per kind
+        let rows = CASES
+            .iter()
+            .zip(self.case_counts)
+            .map(|((_, name), count)| Row::new(vec![name.to_string(), count.to_string()]));
+        frame.render_widget(
+            Table::new(rows, [Constraint::Length(20), Constraint::Fill(1)]).block(
+                Block::bordered()
+                    .title("Cases")
+                    .border_type(BorderType::Thick),
+            ),
+            vertical_layout[1],
+        );
+
+        frame.render_widget(
+            self.input.get_input(String::from("Seed"), theme),
+            vertical_layout[2],
+        );
+        if self.entering_seed {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[2].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[2].y + vertical_layout[2].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.entering_seed {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::FUZZER]
+        }
+    }
+}
+
+// Random channel message, values are picked by the given function
+fn channel_message(rng: &mut StdRng, value: fn(&mut StdRng) -> u8) -> Vec<u8> {
+    let status = rng.random_range(0x80..=0xEF);
+    let mut message = vec![status];
+    for _ in 0..data_length(status) {
+        message.push(value(rng));
+    }
+    message
+}
+fn random_value(rng: &mut StdRng) -> u8 {
+    rng.random_range(0..=0x7F)
+}
+fn edge_value(rng: &mut StdRng) -> u8 {
+    EDGE_VALUES[rng.random_range(0..EDGE_VALUES.len())]
+}
+// Random data bytes
+fn data_bytes(rng: &mut StdRng, max: usize) -> Vec<u8> {
+    let count = rng.random_range(0..=max);
+    (0..count).map(|_| random_value(rng)).collect()
+}
+
+// Generate the bytes of a case
+fn generate(rng: &mut StdRng, case: FuzzCase) -> Vec<u8> {
+    match case {
+        FuzzCase::Valid => channel_message(rng, random_value),
+        FuzzCase::EdgeValues => channel_message(rng, edge_value),
+        FuzzCase::RunningStatus => {
+            let mut message = channel_message(rng, random_value);
+            let length = data_length(message[0]);
+            for _ in 0..rng.random_range(1..=4) {
+                message.extend((0..length).map(|_| random_value(rng)));
+            }
+            message
+        }
+        FuzzCase::SysEx => {
+            let mut message = vec![0xF0, 0x7D];
+            message.extend(data_bytes(rng, 64));
+            message.push(0xF7);
+            message
+        }
+        FuzzCase::Truncated => {
+            let mut message = channel_message(rng, random_value);
+            let keep = rng.random_range(1..message.len());
+            message.truncate(keep);
+            message
+        }
+        FuzzCase::StrayData => {
+            let mut data = data_bytes(rng, 7);
+            data.push(random_value(rng));
+            data
+        }
+        FuzzCase::UnterminatedSysEx => {
+            let mut message = vec![0xF0, 0x7D];
+            message.extend(data_bytes(rng, 32));
+            message
+        }
+        FuzzCase::RealTimeInside => {
+            let mut message = channel_message(rng, random_value);
+            let position = rng.random_range(1..=message.len());
+            message.insert(position, REAL_TIME[rng.random_range(0..REAL_TIME.len())]);
+            message
+        }
+        FuzzCase::OutOfRange => {
+            let mut message = channel_message(rng, random_value);
+            let position = rng.random_range(1..message.len());
+            message[position] |= 0x80;
+            message
+        }
+        FuzzCase::Undefined => {
+            let mut message = vec![UNDEFINED[rng.random_range(0..UNDEFINED.len())]];
+            message.extend(data_bytes(rng, 2));
+            message
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Many generated streams of a case, to cover the random choices
+    fn streams(case: FuzzCase) -> Vec<Vec<u8>> {
+        let mut rng = StdRng::seed_from_u64(1);
+        (0..500).map(|_| generate(&mut rng, case)).collect()
+    }
+
+    #[test]
+    fn same_seed_generates_same_bytes() {
+        for seed in [0, 42, u64::MAX] {
+            let run = || {
+                let mut rng = StdRng::seed_from_u64(seed);
+                (0..100)
+                    .flat_map(|i| generate(&mut rng, CASES[i % CASES.len()].0))
+                    .collect::<Vec<u8>>()
+            };
+            assert_eq!(run(), run());
+        }
+    }
+
+    #[test]
+    fn truncated_messages_miss_data_bytes() {
+        for message in streams(FuzzCase::Truncated) {
+            assert!((0x80..=0xEF).contains(&message[0]));
+            assert!(message.len() < 1 + data_length(message[0]));
+        }
+    }
+
+    #[test]
+    fn out_of_range_has_a_status_in_the_data() {
+        for message in streams(FuzzCase::OutOfRange) {
+            assert!((0x80..=0xEF).contains(&message[0]));
+            assert_eq!(message.len(), 1 + data_length(message[0]));
+            assert!(message[1..].iter().any(|b| *b >= 0x80));
+        }
+    }
+
+    #[test]
+    fn unterminated_sysex_has_no_end() {
+        for message in streams(FuzzCase::UnterminatedSysEx) {
+            assert_eq!(message[..2], [0xF0, 0x7D]);
+            assert!(message[1..].iter().all(|b| *b < 0x80));
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use crossterm::event::KeyEvent;
 use ratatui::{
     crossterm::event::{KeyCode, KeyEventKind},
-    style::{Color, Style},
+    style::Style,
     widgets::{Block, BorderType, Paragraph},
 };
 
+use crate::keymap::{self, Keymap};
+use crate::theme::Theme;
+
 /// Input holds the state of the user input
 pub struct Input {
     /// Current value of the input box
@@ -97,7 +100,7 @@ impl Input {
     pub fn submit_message(&mut self) -> String {
         let tmp_input = self.input.clone();
         self.clear_input();
-        return tmp_input;
+        tmp_input
     }
     // Clear input field
     pub fn clear_input(&mut self) {
@@ -147,15 +150,15 @@ impl Input {
             },
             InputMode::Editing => {}
         };
-        return should_exit;
+        should_exit
     }
     // Return current input for rendering
-    pub fn get_input(&self, title: String) -> Paragraph<'_> {
+    pub fn get_input(&self, title: String, theme: &Theme) -> Paragraph<'_> {
         Paragraph::new(self.input.as_str())
             .style(match self.input_mode {
                 InputMode::Normal => Style::default(),
-                InputMode::Error => Style::default().fg(Color::Red),
-                InputMode::Editing => Style::default().fg(Color::Yellow),
+                InputMode::Error => Style::default().fg(theme.error),
+                InputMode::Editing => Style::default().fg(theme.highlight),
             })
             .block(
                 Block::bordered()
@@ -168,21 +171,11 @@ impl Input {
         self.character_index.try_into().unwrap()
     }
     // Shortcuts for differnt modes
-    pub fn get_shortcuts(&self) -> &str {
+    pub fn keymap(&self) -> &'static Keymap {
         match self.input_mode {
-            InputMode::Editing => {
-                "Normal Mode: Esc | \
-                Submit: Enter | \
-                Normal Text writing"
-            }
-            InputMode::Normal => {
-                "Quit Config: q | \
-                Input Mode: i | \
-                Submit: Enter | \
-                Delet Char: Backspace/x |\
-                Clear input: d"
-            }
-            InputMode::Error => "Press any key",
+            InputMode::Editing => &keymap::INPUT_EDITING,
+            InputMode::Normal => &keymap::INPUT_NORMAL,
+            InputMode::Error => &keymap::INPUT_ERROR,
         }
     }
 }
@@ -0,0 +1,500 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, Clear, Paragraph, Row, Table, TableState},
+};
+use std::rc::Rc;
+
+use crate::popup_utils::popup_area;
+use crate::theme::Theme;
+
+// A key or a group of keys and what they do
+pub struct Binding {
+    pub keys: &'static [&'static str], //Alternative keys or the two directions of a setting
+    pub label: &'static str,           //Short name on the shortcut line
+    pub help: &'static str,            //Description in the help popup and the docs
+    pub shortcut: bool,                //Shown on the shortcut line, the help lists every binding
+}
+// Bindings of a screen or of a mode of it
+pub struct Keymap {
+    pub title: &'static str,
+    pub bindings: &'static [Binding],
+}
+// Help popup with the bindings of the current screen
+pub struct Help {
+    pub open: bool,
+    scroll: usize, //First shown row
+}
+
+const fn bind(
+    keys: &'static [&'static str],
+    label: &'static str,
+    help: &'static str,
+) -> Binding {
+    Binding {
+        keys,
+        label,
+        help,
+        shortcut: true,
+    }
+}
+// A binding only listed in the help, for screens with more keys than the shortcut line fits
+const fn help_only(
+    keys: &'static [&'static str],
+    label: &'static str,
+    help: &'static str,
+) -> Binding {
+    Binding {
+        keys,
+        label,
+        help,
+        shortcut: false,
+    }
+}
+
+// Keys that work on every screen
+pub static GLOBAL: Keymap = Keymap {
+    title: "Global",
+    bindings: &[
+        help_only(&["Ctrl+c"], "Force quit", "Close the application"),
+        bind(&["?"], "Help", "Show the Shortcuts of the current Screen (not while typing)"),
+    ],
+};
+pub static RUNNING: Keymap = Keymap {
+    title: "Main View",
+    bindings: &[
+        bind(&["q"], "Quit", "Close the application"),
+        bind(&["m"], "Manual", "Open Manual Mode"),
+        bind(&["p"], "Com Config", "Open Serial Port Configuration"),
+        bind(&["Home row"], "Play tone", "Play or Release a Note with the Home Row (s d f g h j k l)"),
+        bind(&["c"], "Clear notes", "Release all held Notes"),
+        bind(&["v", "V"], "Velocity", "Decrease / Increase Velocity"),
+        help_only(&["u"], "Velocity curve", "Cycle Velocity Curve (Fixed, Linear, Exponential, Logarithmic)"),
+        help_only(&["r"], "Toggle random Velocity", "Toggle Random Velocity"),
+        help_only(&["U"], "Random distribution", "Cycle Random Distribution (Uniform, Triangular, Gaussian)"),
+        help_only(&["[", "]"], "Random min", "Decrease / Increase Random Minimum"),
+        help_only(&["{", "}"], "Random max", "Decrease / Increase Random Maximum"),
+        bind(&["n", "N"], "Octave", "Decrease / Increase Octave"),
+        help_only(&["t"], "Tuning", "Open Tuning (Scala `.scl`/`.kbm`)"),
+        help_only(&["a"], "Arpeggiator", "Toggle Arpeggiator"),
+        help_only(&["A"], "Arp pattern", "Cycle Arpeggiator Pattern (Up, Down, Up-Down, Random, As played)"),
+        help_only(&[",", "."], "Arp rate", "Decrease / Increase Arpeggiator Rate"),
+        help_only(&["y", "Y"], "Arp octaves", "Decrease / Increase Arpeggiator Octave Span"),
+        help_only(&["<", ">"], "Arp gate", "Decrease / Increase Arpeggiator Gate Length"),
+        help_only(&["z"], "Chord", "Cycle Chord Mode (off, Major, Minor, 7th, Sus, Diminished, Custom)"),
+        help_only(&["Z"], "Custom chord", "Enter Custom Chord Intervals"),
+        help_only(&["e"], "Sequencer", "Open Step Sequencer"),
+        help_only(&["Space"], "Play/Stop", "Play / Stop the Transport (sends Start / Stop)"),
+        help_only(&["B"], "Continue", "Continue the Transport (sends Song Position and Continue)"),
+        help_only(&["b"], "Tap tempo", "Tap Tempo"),
+        help_only(&["-", "+"], "BPM", "Decrease / Increase BPM"),
+        help_only(&["C"], "Clock out", "Toggle MIDI Clock Output"),
+        bind(&["x"], "Panic", "Panic: All Notes Off, All Sound Off and Reset All Controllers on every channel"),
+        help_only(&["X"], "Panic with note offs", "Panic and send a note off for all 128 notes on every channel"),
+        help_only(&["Left", "Right"], "Select knob", "Select CC Knob"),
+        help_only(&["Up", "Down"], "Turn knob", "Turn CC Knob (fine)"),
+        help_only(&["PageUp", "PageDown"], "Turn knob coarse", "Turn CC Knob (coarse)"),
+        help_only(&["K"], "Assign knob", "Assign CC Number, Channel and 14 bit Mode of the Selected Knob"),
+        help_only(&["i", "o"], "Pitch bend", "Pitch Bend Down / Up (springs back to center when released)"),
+        help_only(&["I", "O"], "Mod wheel", "Mod Wheel Down / Up (CC1)"),
+        help_only(&["(", ")"], "Bend range", "Decrease / Increase Pitch Bend Range"),
+        help_only(&["R"], "Send bend range", "Send the Pitch Bend Range (RPN 0)"),
+        help_only(&["P"], "Programs", "Open Program Browser"),
+        help_only(&["S"], "SysEx", "Open SysEx Librarian"),
+        help_only(&["T"], "Stress test", "Open Throughput Stress Test"),
+        help_only(&["L"], "Latency", "Open Round Trip Latency Measurement"),
+        help_only(&["F"], "Fuzzer", "Open MIDI Fuzzer"),
+        help_only(&["G"], "Sweeps", "Open Note and Velocity Sweeps"),
+        help_only(&["w"], "WAV analysis", "Open WAV Analysis"),
+        help_only(&["W"], "DDS model", "Open Reference DDS Model"),
+        help_only(&["1-0"], "Voice waveform", "Cycle the Scope Waveform of DDS Slot 1 - 10 (Sine, Square, Sawtooth, Triangle)"),
+        help_only(&["*", "/"], "Scope zoom", "Shorten / Lengthen the Scope Time Window"),
+        help_only(&["E"], "Log/linear frequency", "Toggle the FFT Chart between a Log (note names) and a Linear (Hz) Frequency Axis"),
+        help_only(&["D"], "dB/linear strength", "Toggle the FFT Chart between a dB and a Linear Strength Axis"),
+        help_only(&[";", "'"], "Chart zoom", "Zoom the FFT Chart Out / In"),
+        help_only(&["Home", "End"], "Chart pan", "Pan the FFT Chart Down / Up"),
+        help_only(&["H"], "Chart auto range", "Auto Range the FFT Chart to the Held Notes again"),
+        help_only(&["M"], "Middle C as C4/C3", "Name Middle C (60) as C4 or C3"),
+        help_only(&["Tab"], "Arrangement", "Cycle Pane Arrangement (standard, spectrum, channels)"),
+        help_only(&["F1-F6"], "Collapse panes", "Collapse / Show the Info, FFT, Scope, Wheels and Knobs, Piano and Channel Panes"),
+        help_only(&["F7"], "Theme", "Cycle Colour Theme (dark, light, high-contrast, monochrome and the themes from the config)"),
+        help_only(&["F10"], "Save layout", "Save the Arrangement and Collapsed Panes to the Config File"),
+    ],
+};
+pub static HELP: Keymap = Keymap {
+    title: "Help",
+    bindings: &[
+        bind(&["q", "Esc", "?"], "Close help", "Close the Help"),
+        bind(&["Up", "Down", "k", "j"], "Scroll", "Scroll the Help"),
+        bind(&["PageUp", "PageDown"], "Page", "Scroll the Help by a Page"),
+    ],
+};
+pub static MANUAL: Keymap = Keymap {
+    title: "Manual Mode",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Manual", "Close Manual Mode"),
+        bind(&["Tab"], "Switch input field", "Navigate between packet fields"),
+        bind(&["Enter"], "Send", "Send current custom MIDI packet"),
+        bind(&["c"], "Clear inputs", "Clear all packet fields"),
+    ],
+};
+pub static COM_CONFIG: Keymap = Keymap {
+    title: "Serial Port Configuration",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Config", "Close the Configuration"),
+        bind(&["Tab"], "Switch to Baud entry", "Switch between the Port List and the Baud Rate"),
+        bind(&["Enter"], "Submit", "Connect to the Selected Port"),
+        bind(&["Down", "j"], "Next Entry", "Select the next Port"),
+        bind(&["Up", "k"], "Prev Entry", "Select the previous Port"),
+        bind(&["r"], "Rescan serialports", "Rescan the Serial Ports"),
+    ],
+};
+// Forms with more than one text field
+pub static FIELDS: Keymap = Keymap {
+    title: "Forms",
+    bindings: &[bind(
+        &["Tab"],
+        "Switch input field",
+        "Switch to the next Field of the Baud Rate, Tuning and Knob Forms",
+    )],
+};
+pub static INPUT_NORMAL: Keymap = Keymap {
+    title: "Text Field",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit", "Leave the Field without submitting"),
+        bind(&["i"], "Input Mode", "Start typing"),
+        bind(&["Enter"], "Submit", "Submit the Field"),
+        bind(&["Backspace", "x"], "Delete char", "Delete the Character before the Cursor"),
+        bind(&["d"], "Clear input", "Clear the Field"),
+    ],
+};
+pub static INPUT_EDITING: Keymap = Keymap {
+    title: "Text Field while typing",
+    bindings: &[
+        bind(&["Esc"], "Normal Mode", "Stop typing"),
+        bind(&["Enter"], "Submit", "Submit the Field"),
+        bind(&["Left", "Right"], "Cursor", "Move the Cursor"),
+        bind(&["Backspace"], "Delete char", "Delete the Character before the Cursor"),
+    ],
+};
+pub static INPUT_ERROR: Keymap = Keymap {
+    title: "Text Field with an Error",
+    bindings: &[bind(&["Any key"], "Continue", "Clear the Error and start typing again")],
+};
+pub static SEQUENCER: Keymap = Keymap {
+    title: "Sequencer",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Sequencer", "Leave the Sequencer (the pattern keeps playing)"),
+        bind(&["Arrows", "hjkl"], "Move", "Move the cursor"),
+        bind(&["Enter"], "Toggle step", "Toggle the selected step"),
+        bind(&["Space"], "Play/Stop", "Play / Stop"),
+        help_only(&["v", "V"], "Velocity", "Decrease / Increase Step Velocity"),
+        help_only(&["g", "G"], "Gate", "Decrease / Increase Step Gate"),
+        help_only(&["n", "N"], "Note", "Decrease / Increase Track Note"),
+        help_only(&["-", "+"], "BPM", "Decrease / Increase BPM"),
+        help_only(&["[", "]"], "Swing", "Decrease / Increase Swing"),
+        bind(&["a", "d"], "Add/Delete track", "Add / Delete Track"),
+        bind(&["s", "o"], "Save/Load", "Save / Load Pattern (`.toml`)"),
+    ],
+};
+pub static PROGRAMS: Keymap = Keymap {
+    title: "Program Browser",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Programs", "Close the Program Browser"),
+        bind(&["Enter"], "Send", "Send Bank Select and Program Change"),
+        bind(&["Down", "j"], "Next Entry", "Select the next Program"),
+        bind(&["Up", "k"], "Prev Entry", "Select the previous Program"),
+        bind(&["Left", "Right", "h", "l"], "Switch list", "Switch between General MIDI, Drum Kits and Device Lists"),
+        bind(&["-", "+"], "Channel", "Decrease / Increase Channel"),
+    ],
+};
+pub static SYSEX: Keymap = Keymap {
+    title: "SysEx Librarian",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit SysEx", "Close the SysEx Librarian (queued messages keep sending)"),
+        bind(&["Up", "Down", "j", "k"], "Select", "Select Message"),
+        help_only(&["PageUp", "PageDown"], "Scroll", "Scroll the Hex View"),
+        bind(&["Enter"], "Send", "Send the Selected Message"),
+        bind(&["a"], "Send all", "Send all Messages with the set Delay"),
+        help_only(&["s"], "Stop", "Stop Sending"),
+        help_only(&["-", "+"], "Delay", "Decrease / Increase the Delay between Messages"),
+        help_only(&["c"], "Capture", "Toggle Capture of Incoming SysEx"),
+        bind(&["d", "x"], "Delete/Clear", "Delete the Selected / all Messages"),
+        bind(&["o", "w"], "Load/Save", "Load a `.syx` File / Save all Messages to a `.syx` File"),
+        bind(&["b"], "Build", "Build a Message from Hex Bytes (bytes after `|` get a Roland checksum)"),
+    ],
+};
+pub static STRESS: Keymap = Keymap {
+    title: "Stress Test",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Stress Test", "Stop the Test and Close the Stress Test"),
+        bind(&["Enter", "Space"], "Start/Stop", "Start / Stop the Test"),
+        bind(&["-", "+"], "Rate", "Decrease / Increase the Message Rate (line speed, 10 - 10000 msg/s)"),
+        bind(&["[", "]"], "Duration", "Decrease / Increase the Duration by 1 s"),
+        bind(&["{", "}"], "Duration x10", "Decrease / Increase the Duration by 10 s"),
+    ],
+};
+pub static LATENCY: Keymap = Keymap {
+    title: "Latency",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Latency", "Close the Latency Measurement (a running measurement continues)"),
+        bind(&["Enter", "Space"], "Start/Stop", "Start / Stop the Measurement"),
+        bind(&["k"], "Probe kind", "Switch the Probe Kind (SysEx, Note On)"),
+        bind(&["[", "]"], "Count", "Decrease / Increase the Probe Count"),
+        bind(&["-", "+"], "Interval", "Decrease / Increase the Interval between Probes"),
+        bind(&["w"], "Export", "Export the Round Trip Times and Summary as CSV"),
+    ],
+};
+pub static FUZZER: Keymap = Keymap {
+    title: "Fuzzer",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Fuzzer", "Close the Fuzzer (a running fuzzer continues)"),
+        bind(&["Enter", "Space"], "Start/Stop", "Start / Stop a Run"),
+        bind(&["n"], "New seed", "Pick a new Random Seed"),
+        bind(&["s"], "Enter seed", "Enter a Seed to Reproduce a Run"),
+        bind(&["t"], "Time/Count limit", "Switch between a Time and a Case Count Limit"),
+        bind(&["-", "+"], "Limit", "Decrease / Increase the Limit"),
+        bind(&["l"], "Liveness check", "Toggle the Liveness Check (identity request after every batch)"),
+    ],
+};
+pub static SWEEP: Keymap = Keymap {
+    title: "Sweeps",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Sweep", "Close the Sweeps (a running sweep continues)"),
+        bind(&["Enter", "Space"], "Start", "Start the Sweep"),
+        bind(&["m"], "Mode", "Cycle Mode (Notes, Velocities, Notes x Velocities)"),
+        bind(&["[", "]"], "Range low", "Decrease / Increase the Lowest Note of the Range"),
+        bind(&["{", "}"], "Range high", "Decrease / Increase the Highest Note of the Range"),
+        bind(&["n", "N"], "Note", "Decrease / Increase the Fixed Note"),
+        bind(&["v", "V"], "Velocity", "Decrease / Increase the Fixed Velocity"),
+        help_only(&["s", "S"], "Velocity step", "Decrease / Increase the Velocity Step of the Matrix"),
+        help_only(&["l", "L"], "Length", "Decrease / Increase the Note Length"),
+        help_only(&["g", "G"], "Gap", "Decrease / Increase the Gap between Notes"),
+        help_only(&["-", "+"], "Channel", "Decrease / Increase the Channel"),
+    ],
+};
+pub static SWEEP_RUNNING: Keymap = Keymap {
+    title: "Sweeps while running",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Sweep (keeps running)", "Close the Sweeps, the sweep keeps running"),
+        bind(&["Enter", "Space"], "Stop", "Stop the Sweep"),
+    ],
+};
+pub static ANALYSIS: Keymap = Keymap {
+    title: "WAV Analysis",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Analysis", "Close the Analysis (the spectrum stays on the chart)"),
+        bind(&["o"], "Load", "Load a Recording (`.wav`)"),
+        bind(&["c"], "Clear", "Remove the Recording and its Spectrum"),
+        bind(&["f", "F"], "FFT size", "Decrease / Increase the FFT Size"),
+        bind(&["w"], "Window", "Cycle Window Function (Blackman-Harris, Hann, Rectangular)"),
+        bind(&["a"], "Average/Single window", "Switch between the Average of the whole File and a Single Window"),
+        bind(&["Left", "Right", "h", "l"], "Window position", "Move the Single Window"),
+    ],
+};
+pub static MODEL: Keymap = Keymap {
+    title: "DDS Model",
+    bindings: &[
+        bind(&["q", "Esc"], "Quit Model", "Close the Model (a session recording continues)"),
+        bind(&["Enter"], "Render", "Render the Source to a `.wav` File"),
+        bind(&["r"], "Record session", "Start / Stop recording the Session (every message sent)"),
+        bind(&["s"], "Source", "Switch the Source between the recorded Session and the Sequencer Pattern"),
+        help_only(&["[", "]"], "Loops", "Decrease / Increase the Loops of the Pattern"),
+        bind(&["v", "V"], "Voices", "Decrease / Increase the Voices"),
+        help_only(&["a", "A"], "Accumulator", "Decrease / Increase the Phase Accumulator Bits"),
+        help_only(&["t", "T"], "Table size", "Decrease / Increase the Lookup Table Size"),
+        help_only(&["b", "B"], "DAC bits", "Decrease / Increase the DAC Bits"),
+        bind(&["f"], "Sample rate", "Cycle Sample Rate (22050, 44100, 48000, 96000 Hz)"),
+        bind(&["w"], "Waveform", "Cycle Waveform (Sine, Square, Sawtooth, Triangle)"),
+    ],
+};
+
+// Every keymap in the order of the docs
+pub static ALL: [&Keymap; 19] = [
+    &GLOBAL,
+    &RUNNING,
+    &HELP,
+    &MANUAL,
+    &COM_CONFIG,
+    &FIELDS,
+    &INPUT_NORMAL,
+    &INPUT_EDITING,
+    &INPUT_ERROR,
+    &SEQUENCER,
+    &PROGRAMS,
+    &SYSEX,
+    &STRESS,
+    &LATENCY,
+    &FUZZER,
+    &SWEEP,
+    &SWEEP_RUNNING,
+    &ANALYSIS,
+    &MODEL,
+];
+impl Binding {
+    // Keys as shown on the shortcut line
+    fn short_keys(&self) -> String {
+        // A slash key would be lost between the separators
+        let separator = if self.keys.contains(&"/") { " and " } else { "/" };
+        self.keys.join(separator)
+    }
+    // Keys as shown in the help and the docs
+    fn long_keys(&self) -> String {
+        self.keys.join(" / ")
+    }
+}
+
+// Shortcut line of the given keymaps
+pub fn shortcut_line(keymaps: &[&Keymap]) -> String {
+    keymaps
+        .iter()
+        .flat_map(|keymap| keymap.bindings.iter())
+        .filter(|binding| binding.shortcut)
+        .map(|binding| format!("{}: {}", binding.label, binding.short_keys()))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+// Render the shortcut line of the given keymaps
+pub fn render_shortcuts(frame: &mut Frame, layout: Rc<[Rect]>, keymaps: &[&Keymap], theme: &Theme) {
+    let paragraph = Paragraph::new(shortcut_line(keymaps)).style(Style::new().fg(theme.accent));
+    frame.render_widget(paragraph, layout[1]);
+}
+
+// Markdown document of every keymap
+pub fn markdown() -> String {
+    let mut doc = String::from(
+        "# TuneIn Keyboard Shortcuts\n\n\
+         This document is generated from the keybindings of TuneIn with \
+         `tunein --keybindings > SHORTCUTS.md`, press `?` in TuneIn to see the \
+         shortcuts of the current screen.\n",
+    );
+    for keymap in ALL {
+        doc.push_str(&format!(
+            "\n## {}\n\n| Shortcut | Description |\n| :------- | :---------- |\n",
+            keymap.title
+        ));
+        for binding in keymap.bindings {
+            let keys: Vec<String> = binding
+                .keys
+                .iter()
+                .map(|key| format!("`{}`", key.replace('|', "\\|")))
+                .collect();
+            doc.push_str(&format!(
+                "| {} | {} |\n",
+                keys.join(" / "),
+                binding.help.replace('|', "\\|")
+            ));
+        }
+    }
+    doc
+}
+
+impl Help {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            scroll: 0,
+        }
+    }
+    // Open the help at the top
+    pub fn open(&mut self) {
+        self.open = true;
+        self.scroll = 0;
+    }
+    // Event handling while the help is open
+    pub fn key_event(&mut self, key: KeyEvent) {
+        match key.code {
+            // Close
+            KeyCode::Char('q') | KeyCode::Char('?') | KeyCode::Esc => self.open = false,
+            // Scroll
+            KeyCode::Char('j') | KeyCode::Down => self.scroll += 1,
+            KeyCode::Char('k') | KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageDown => self.scroll += 10,
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            _ => {}
+        }
+    }
+    // Render a popup with the given keymaps followed by the global keys
+    pub fn show_help_popup(&mut self, frame: &mut Frame, keymaps: &[&Keymap], theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 80, 80);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+
+        let mut rows = Vec::new();
+        for keymap in keymaps.iter().chain([&&GLOBAL]) {
+            rows.push(
+                Row::new(vec![keymap.title.to_string()])
+                    .style(Style::new().fg(theme.highlight).add_modifier(Modifier::BOLD)),
+            );
+            for binding in keymap.bindings {
+                rows.push(Row::new(vec![binding.long_keys(), binding.help.to_string()]));
+            }
+        }
+        let key_width = ALL
+            .iter()
+            .flat_map(|keymap| keymap.bindings.iter())
+            .map(|binding| binding.long_keys().len())
+            .max()
+            .unwrap_or(0) as u16;
+        // Stop scrolling once the last row is on the screen
+        let visible = area.height.saturating_sub(2) as usize;
+        self.scroll = self.scroll.min(rows.len().saturating_sub(visible));
+        let table = Table::new(rows, [Constraint::Length(key_width), Constraint::Fill(1)])
+            .column_spacing(2)
+            .block(
+                Block::bordered()
+                    .title(Line::from("Help"))
+                    .border_type(BorderType::Thick),
+            );
+        let mut state = TableState::new().with_offset(self.scroll);
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcut_line_starts_with_help() {
+        let line = shortcut_line(&[&GLOBAL, &RUNNING]);
+        assert!(line.starts_with("Help: ? | Quit: q | "), "{}", line);
+        assert!(!line.contains("Ctrl+c"));
+        assert!(!line.contains("Theme"));
+    }
+
+    #[test]
+    fn shortcut_lines_fit_a_wide_terminal() {
+        for keymap in ALL {
+            let line = shortcut_line(&[&GLOBAL, keymap]);
+            assert!(line.len() <= 140, "{}: {} characters", keymap.title, line.len());
+        }
+    }
+
+    #[test]
+    fn no_key_is_bound_twice() {
+        for keymap in ALL {
+            // The global keys are shown with every screen but the help
+            let mut shown = vec![keymap];
+            if !std::ptr::eq(keymap, &HELP) && !std::ptr::eq(keymap, &GLOBAL) {
+                shown.push(&GLOBAL);
+            }
+            let mut seen = Vec::new();
+            for key in shown.iter().flat_map(|k| k.bindings.iter()).flat_map(|b| b.keys.iter()) {
+                assert!(!seen.contains(key), "{}: {} bound twice", keymap.title, key);
+                seen.push(*key);
+            }
+        }
+    }
+
+    #[test]
+    fn shortcuts_document_is_up_to_date() {
+        // Regenerate with tunein --keybindings > SHORTCUTS.md
+        assert_eq!(markdown(), include_str!("../SHORTCUTS.md"));
+    }
+}
@@ -0,0 +1,511 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Bar, BarChart, BarGroup, Block, BorderType, Clear, Paragraph},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, Instant},
+};
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// Header of sysex probes, non-commercial id followed by "TL"
+const SYSEX_HEADER: [u8; 4] = [0xF0, 0x7D, 0x54, 0x4C];
+// Status byte of note probes, note on on channel 16
+const NOTE_STATUS: u8 = 0x9F;
+// Number of unique note probes, velocity 0 is a note off
+const NOTE_PROBES: u32 = 128 * 127;
+// Probes without an echo after this time count as lost
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+// Number of histogram bars
+const HISTOGRAM_BINS: usize = 16;
+
+// Messages used as probes
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ProbeKind {
+    SysEx, //Sysex with a 14 bit sequence number
+    Note,  //Note on with the sequence number as note and velocity
+}
+// Main latency tool struct
+pub struct LatencyTool {
+    kind: ProbeKind,
+    count: u32,                         //Probes per run
+    interval: u64,                      //Time between probes in ms
+    running: bool,
+    sent: u32,                          //Probes sent in this run
+    last_probe: Instant,                //Time the last probe was sent
+    pending: HashMap<u32, Instant>,     //Probes waiting for their echo
+    samples: Vec<(u32, f64)>,           //Sequence number and round trip time in ms
+    lost: u32,                          //Probes that timed out
+    releases: Vec<u8>,                  //Notes of answered or lost note probes to turn off
+    exporting: bool,                    //Path input is open
+    input: Input,                       //Input for the export path
+}
+
+impl LatencyTool {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            kind: ProbeKind::SysEx,
+            count: 100,
+            interval: 50,
+            running: false,
+            sent: 0,
+            last_probe: Instant::now(),
+            pending: HashMap::new(),
+            samples: Vec::new(),
+            lost: 0,
+            releases: Vec::new(),
+            exporting: false,
+            input: Input::new(),
+        }
+    }
+    // Encode a probe for a sequence number
+    fn probe(&self, seq: u32) -> Vec<u8> {
+        match self.kind {
+            ProbeKind::SysEx => {
+                let mut probe = SYSEX_HEADER.to_vec();
+                probe.extend([(seq >> 7) as u8 & 0x7F, seq as u8 & 0x7F, 0xF7]);
+                probe
+            }
+            ProbeKind::Note => vec![NOTE_STATUS, (seq % 128) as u8, (seq / 128 % 127) as u8 + 1],
+        }
+    }
+    // Decode the sequence number of an echoed probe
+    fn sequence(&self, message: &[u8]) -> Option<u32> {
+        match (self.kind, message) {
+            (ProbeKind::SysEx, [0xF0, 0x7D, 0x54, 0x4C, msb, lsb, 0xF7]) => {
+                Some((*msb as u32) << 7 | *lsb as u32)
+            }
+            (ProbeKind::Note, [NOTE_STATUS, note, velocity]) if *velocity > 0 => {
+                Some(*note as u32 + (*velocity as u32 - 1) * 128)
+            }
+            _ => None,
+        }
+    }
+    // Most probes a run can have without reusing a sequence number
+    fn max_count(&self) -> u32 {
+        match self.kind {
+            ProbeKind::SysEx => 0x4000,
+            ProbeKind::Note => NOTE_PROBES,
+        }
+    }
+    // Start a new run
+    fn start(&mut self, com_config: &ComConfig) {
+        if !com_config.is_connected() {
+            return;
+        }
+        self.samples.clear();
+        self.pending.clear();
+        self.sent = 0;
+        self.lost = 0;
+        self.running = true;
+    }
+    // Send due probes and expire old ones
+    pub fn on_tick(&mut self, com_config: &mut ComConfig) {
+        let now = Instant::now();
+        let expired: Vec<u32> = self
+            .pending
+            .iter()
+            .filter(|(_, sent)| now.duration_since(**sent) >= PROBE_TIMEOUT)
+            .map(|(seq, _)| *seq)
+            .collect();
+        for seq in expired {
+            self.pending.remove(&seq);
+            self.lost += 1;
+            self.release(seq);
+        }
+        self.send_releases(com_config);
+        if !self.running {
+            return;
+        }
+        if self.sent >= self.count {
+            // The run ends once every probe was answered or timed out
+            if self.pending.is_empty() {
+                self.stop(com_config);
+            }
+            return;
+        }
+        if self.sent > 0 && self.last_probe.elapsed() < Duration::from_millis(self.interval) {
+            return;
+        }
+        let seq = self.sent;
+        let probe = self.probe(seq);
+        self.last_probe = Instant::now();
+        if com_config.try_send_bytes(&probe).is_ok() {
+            self.pending.insert(seq, self.last_probe);
+        } else {
+            self.lost += 1;
+        }
+        self.sent += 1;
+    }
+    // Stop the run, probes still waiting count as lost and every note probe is released
+    fn stop(&mut self, com_config: &mut ComConfig) {
+        self.running = false;
+        self.lost += self.pending.len() as u32;
+        let pending: Vec<u32> = self.pending.drain().map(|(seq, _)| seq).collect();
+        for seq in pending {
+            self.release(seq);
+        }
+        self.send_releases(com_config);
+        if self.kind == ProbeKind::Note {
+            let _ = com_config.try_send_bytes(&[0xBF, 123, 0]);
+        }
+    }
+    // Queue the note off of a note probe that was answered or lost
+    fn release(&mut self, seq: u32) {
+        if self.kind == ProbeKind::Note {
+            self.releases.push(self.probe(seq)[1]);
+        }
+    }
+    // Turn off the released note probes
+    fn send_releases(&mut self, com_config: &mut ComConfig) {
+        for note in self.releases.drain(..) {
+            let _ = com_config.try_send_bytes(&[0x80 | (NOTE_STATUS & 0x0F), note, 0]);
+        }
+    }
+    // Match an echoed message with its probe, received is the time the bytes were read
+    pub fn receive(&mut self, message: &[u8], received: Instant) {
+        let Some(seq) = self.sequence(message) else {
+            return;
+        };
+        if let Some(sent) = self.pending.remove(&seq) {
+            self.samples
+                .push((seq, received.duration_since(sent).as_secs_f64() * 1000.));
+            self.release(seq);
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, com_config: &mut ComConfig) -> AppState {
+        let mut app_state: AppState = AppState::Latency;
+        // Path input for the export
+        if self.exporting {
+            match key.code {
+                KeyCode::Enter => {
+                    let path = self.input.submit_message();
+                    match self.export(path.trim()) {
+                        Ok(()) => self.exporting = false,
+                        Err(e) => self.input.display_error(e),
+                    }
+                }
+                _ => {
+                    if self.input.key_event(key) {
+                        self.exporting = false;
+                    }
+                }
+            }
+            return app_state;
+        }
+        match key.code {
+            // Quit, a running measurement continues
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Start or stop
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if self.running {
+                    self.stop(com_config);
+                } else {
+                    self.start(com_config);
+                }
+            }
+            // Probe kind
+            KeyCode::Char('k') if !self.running => {
+                self.kind = match self.kind {
+                    ProbeKind::SysEx => ProbeKind::Note,
+                    ProbeKind::Note => ProbeKind::SysEx,
+                };
+                self.count = self.count.min(self.max_count());
+            }
+            // Probe count
+            KeyCode::Char('[') if !self.running => self.count = (self.count.saturating_sub(10)).max(10),
+            KeyCode::Char(']') if !self.running => self.count = (self.count + 10).min(self.max_count()),
+            // Interval between probes
+            KeyCode::Char('-') if !self.running => self.interval = (self.interval.saturating_sub(5)).max(1),
+            KeyCode::Char('+') if !self.running => self.interval = (self.interval + 5).min(1000),
+            // Export
+            KeyCode::Char('w') if !self.samples.is_empty() => self.exporting = true,
+            _ => {}
+        }
+        app_state
+    }
+    // Write the samples and a summary as CSV
+    fn export(&self, path: &str) -> Result<(), String> {
+        let mut csv = String::from("sequence,rtt_ms\n");
+        for (seq, rtt) in &self.samples {
+            csv += &format!("{},{:.3}\n", seq, rtt);
+        }
+        if let Some(stats) = LatencyStats::new(&self.samples) {
+            csv += &format!(
+                "# sent {} | received {} | lost {} | min {:.3} | mean {:.3} | p50 {:.3} | p90 {:.3} | p99 {:.3} | max {:.3} | stddev {:.3} | jitter {:.3}\n",
+                self.sent,
+                self.samples.len(),
+                self.lost,
+                stats.min,
+                stats.mean,
+                stats.p50,
+                stats.p90,
+                stats.p99,
+                stats.max,
+                stats.stddev,
+                stats.jitter
+            );
+        }
+        fs::write(path, csv).map_err(|e| e.to_string())
+    }
+    //Render the latency screen
+    pub fn show_latency_popup(&mut self, frame: &mut Frame, com_config: &ComConfig, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 80, 70);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(9),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        let state = if self.running {
+            Span::styled("Running", Style::default().fg(theme.highlight))
+        } else if !com_config.is_connected() {
+            Span::styled("No port connected", Style::default().fg(theme.error))
+        } else {
+            Span::raw("Stopped")
+        };
+        let kind = match self.kind {
+            ProbeKind::SysEx => "SysEx (F0 7D 54 4C seq F7)",
+            ProbeKind::Note => "Note on, channel 16",
+        };
+        let stats = LatencyStats::new(&self.samples);
+        let stats_lines = match &stats {
+            Some(s) => vec![
+                Line::from(format!(
+                    " Min {:.2} | Mean {:.2} | Max {:.2} ms",
+                    s.min, s.mean, s.max
+                )),
+                Line::from(format!(
+                    " P50 {:.2} | P90 {:.2} | P99 {:.2} ms",
+                    s.p50, s.p90, s.p99
+                )),
+                Line::from(format!(
+                    " Std dev {:.2} | Jitter {:.2} ms (mean difference of consecutive probes)",
+                    s.stddev, s.jitter
+                )),
+            ],
+            None => vec![Line::from(" No echoes received yet")],
+        };
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled(" Round trip latency ", Style::default().add_modifier(Modifier::BOLD)),
+                state,
+            ]),
+            Line::from(format!(
+                " Probe: {} | Count: {} | Interval: {} ms",
+                kind, self.count, self.interval
+            )),
+            Line::from(format!(
+                " Sent {} | Received {} | Lost {} | Pending {}",
+                self.sent,
+                self.samples.len(),
+                self.lost,
+                self.pending.len()
+            )),
+        ];
+        lines.extend(stats_lines);
+        frame.render_widget(
+            Paragraph::new(Text::from(lines)).block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        // Histogram of the round trip times
+        let bars: Vec<Bar> = match &stats {
+            Some(s) => {
+                let width = ((s.max - s.min) / HISTOGRAM_BINS as f64).max(0.01);
+                let mut bins = [0u64; HISTOGRAM_BINS];
+                for (_, rtt) in &self.samples {
+                    let bin = ((rtt - s.min) / width) as usize;
+                    bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+                }
+                bins.iter()
+                    .enumerate()
+                    .map(|(i, count)| {
+                        Bar::default()
+                            .value(*count)
+                            .label(Line::from(format!("{:.1}", s.min + width * i as f64)))
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let bar_width = (vertical_layout[1].width.saturating_sub(2) / HISTOGRAM_BINS as u16)
+            .saturating_sub(1)
+            .max(1);
+        frame.render_widget(
+            BarChart::default()
+                .block(
+                    Block::bordered()
+                        .title("Histogram (ms)")
+                        .border_type(BorderType::Thick),
+                )
+                .data(BarGroup::default().bars(&bars))
+                .bar_width(bar_width)
+                .bar_gap(1)
+                .bar_style(Style::default().fg(theme.bars)),
+            vertical_layout[1],
+        );
+
+        // Path input for the export
+        frame.render_widget(
+            self.input.get_input(String::from("Export (.csv)"), theme),
+            vertical_layout[2],
+        );
+        if self.exporting {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[2].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[2].y + vertical_layout[2].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.exporting {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::LATENCY]
+        }
+    }
+}
+
+// Summary of the round trip times in ms
+struct LatencyStats {
+    min: f64,
+    max: f64,
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    stddev: f64,
+    jitter: f64, //Mean absolute difference between consecutive probes
+}
+
+impl LatencyStats {
+    fn new(samples: &[(u32, f64)]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = samples.iter().map(|(_, rtt)| *rtt).collect();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / count;
+        let variance = sorted.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count;
+        // Nearest rank percentile
+        let percentile = |p: f64| sorted[((p / 100. * count).ceil() as usize).clamp(1, sorted.len()) - 1];
+        let jitter = if samples.len() > 1 {
+            samples
+                .windows(2)
+                .map(|w| (w[1].1 - w[0].1).abs())
+                .sum::<f64>()
+                / (count - 1.)
+        } else {
+            0.
+        };
+        Some(Self {
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            mean,
+            p50: percentile(50.),
+            p90: percentile(90.),
+            p99: percentile(99.),
+            stddev: variance.sqrt(),
+            jitter,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_tool() -> LatencyTool {
+        let mut tool = LatencyTool::new();
+        tool.kind = ProbeKind::Note;
+        tool
+    }
+
+    // Bytes sent by one tick
+    fn tick(tool: &mut LatencyTool) -> Vec<Vec<u8>> {
+        ComConfig::new().record(|com| tool.on_tick(com))
+    }
+
+    #[test]
+    fn probes_round_trip() {
+        for kind in [ProbeKind::SysEx, ProbeKind::Note] {
+            let mut tool = LatencyTool::new();
+            tool.kind = kind;
+            for seq in [0, 1, 127, 128, 1000, tool.max_count() - 1] {
+                assert_eq!(tool.sequence(&tool.probe(seq)), Some(seq));
+            }
+        }
+        // A note probe never has velocity 0
+        let tool = note_tool();
+        assert!((0..NOTE_PROBES).all(|seq| tool.probe(seq)[2] > 0));
+        assert_eq!(tool.sequence(&[NOTE_STATUS, 60, 0]), None);
+    }
+
+    #[test]
+    fn round_trip_uses_read_time() {
+        let mut tool = LatencyTool::new();
+        let sent = Instant::now();
+        tool.pending.insert(5, sent);
+        tool.receive(&tool.probe(5), sent + Duration::from_millis(7));
+        assert_eq!(tool.samples.len(), 1);
+        assert!((tool.samples[0].1 - 7.).abs() < 1e-9);
+        // A second echo of the same probe is ignored
+        tool.receive(&tool.probe(5), sent + Duration::from_millis(9));
+        assert_eq!(tool.samples.len(), 1);
+    }
+
+    #[test]
+    fn answered_note_probes_are_released() {
+        let mut tool = note_tool();
+        let sent = Instant::now();
+        tool.pending.insert(200, sent);
+        tool.receive(&tool.probe(200), sent);
+        assert_eq!(tick(&mut tool), vec![vec![0x8F, 72, 0]]);
+        assert!(tick(&mut tool).is_empty());
+    }
+
+    #[test]
+    fn lost_note_probes_are_released() {
+        let mut tool = note_tool();
+        tool.pending.insert(3, Instant::now() - PROBE_TIMEOUT);
+        assert_eq!(tick(&mut tool), vec![vec![0x8F, 3, 0]]);
+        assert_eq!(tool.lost, 1);
+    }
+
+    #[test]
+    fn stop_releases_waiting_note_probes() {
+        let mut tool = note_tool();
+        tool.running = true;
+        tool.pending.insert(130, Instant::now());
+        let sent = ComConfig::new().record(|com| tool.stop(com));
+        assert_eq!(sent, vec![vec![0x8F, 2, 0], vec![0xBF, 123, 0]]);
+        assert_eq!(tool.lost, 1);
+        assert!(tool.pending.is_empty());
+    }
+}
@@ -4,48 +4,311 @@ use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
 };
+use serde::Deserialize;
+
+// Smallest terminal the main view is drawn in
+pub const MIN_WIDTH: u16 = 60;
+pub const MIN_HEIGHT: u16 = 20;
+// Below this width the info column and the wheels and knobs are collapsed
+const NARROW_WIDTH: u16 = 120;
+// From this width the info column gets a fixed width instead of a share
+const WIDE_WIDTH: u16 = 180;
+// Below this height the piano is collapsed
+const SHORT_HEIGHT: u16 = 32;
+// Smallest size of a channel pane
+const CHANNEL_WIDTH: u16 = 20;
+const CHANNEL_HEIGHT: u16 = 9;
+// Channels of the synth
+const CHANNELS: usize = 10;
+
+// Panes of the main view that can be collapsed
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pane {
+    Info,     //Info and communication column
+    Fft,      //Spectrum chart
+    Scope,    //Oscilloscope
+    Controls, //Wheels and cc knobs
+    Piano,    //Piano keyboard
+    Channels, //Channel panes
+}
+// Arrangement of the panes
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Arrangement {
+    #[default]
+    Standard, //Everything
+    Spectrum, //The chart takes the place of the channels
+    Channels, //The channels take the place of the chart row
+}
+// Layout settings loaded from the [layout] table of the config
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub arrangement: Arrangement,
+    pub hidden: Vec<Pane>, //Panes collapsed by the user
+}
+// Areas of the main view, collapsed panes have none
+pub struct MainLayout {
+    pub base_layer: Rc<[Rect]>,        //Main area and shortcut line
+    pub general: Option<Rc<[Rect]>>,   //Info and communication
+    pub status: Option<Rect>,          //One line with the connection while the info column is collapsed
+    pub fft: Option<Rect>,
+    pub scope: Option<Rect>,
+    pub wheels: Option<Rect>,
+    pub knobs: Option<Rect>,
+    pub piano: Option<Rect>,
+    pub channels: Option<Rc<[Rect]>>,
+    pub hidden_channels: usize,        //Channel panes cut off for lack of space
+    pub too_small: bool,               //The terminal is below the minimum size
+}
+
+impl Pane {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pane::Info => "info",
+            Pane::Fft => "fft",
+            Pane::Scope => "scope",
+            Pane::Controls => "controls",
+            Pane::Piano => "piano",
+            Pane::Channels => "channels",
+        }
+    }
+}
+
+impl Arrangement {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Arrangement::Standard => "standard",
+            Arrangement::Spectrum => "spectrum",
+            Arrangement::Channels => "channels",
+        }
+    }
+    // The following arrangement
+    pub fn next(&self) -> Self {
+        match self {
+            Arrangement::Standard => Arrangement::Spectrum,
+            Arrangement::Spectrum => Arrangement::Channels,
+            Arrangement::Channels => Arrangement::Standard,
+        }
+    }
+    // Panes the arrangement leaves out
+    fn hides(&self, pane: Pane) -> bool {
+        match self {
+            Arrangement::Standard => false,
+            Arrangement::Spectrum => pane == Pane::Channels,
+            Arrangement::Channels => matches!(pane, Pane::Fft | Pane::Scope | Pane::Controls),
+        }
+    }
+}
+
+impl LayoutConfig {
+    // Collapse or show a pane
+    pub fn toggle(&mut self, pane: Pane) {
+        if self.hidden.contains(&pane) {
+            self.hidden.retain(|p| *p != pane);
+        } else {
+            self.hidden.push(pane);
+        }
+    }
+    // Whether a pane is drawn in an area, small terminals collapse the less important panes
+    fn shown(&self, pane: Pane, area: Rect) -> bool {
+        let collapsed = match pane {
+            Pane::Info | Pane::Controls => area.width < NARROW_WIDTH,
+            Pane::Piano => area.height < SHORT_HEIGHT,
+            _ => false,
+        };
+        !collapsed && !self.arrangement.hides(pane) && !self.hidden.contains(&pane)
+    }
+}
+
 // Generate the main Layout
-pub fn generate_main_layout(frame: &mut Frame) -> (Rc<[Rect]>, Rc<[Rect]>, Rc<[Rect]>, Rc<[Rect]>) {
+pub fn generate_main_layout(frame: &mut Frame, config: &LayoutConfig) -> MainLayout {
+    let area = frame.area();
     // Base layer
     let base_layer = Layout::default()
         .direction(Direction::Vertical)
         .constraints(vec![Constraint::Fill(1), Constraint::Length(1)])
-        .split(frame.area());
-    let vertical_temp_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(20), Constraint::Percentage(80)])
-        .split(base_layer[0]);
+        .split(area);
+    let mut layout = MainLayout {
+        base_layer: base_layer.clone(),
+        general: None,
+        status: None,
+        fft: None,
+        scope: None,
+        wheels: None,
+        knobs: None,
+        piano: None,
+        channels: None,
+        hidden_channels: 0,
+        too_small: area.width < MIN_WIDTH || area.height < MIN_HEIGHT,
+    };
+    if layout.too_small {
+        return layout;
+    }
+    let shown = |pane: Pane| config.shown(pane, area);
 
-    let general_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Percentage(50); 2])
-        .split(vertical_temp_layout[0]);
+    // Info column on the left
+    let mut main_area = base_layer[0];
+    if shown(Pane::Info) {
+        let info_width = if area.width >= WIDE_WIDTH {
+            Constraint::Length(40)
+        } else {
+            Constraint::Percentage(20)
+        };
+        let vertical_temp_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![info_width, Constraint::Fill(1)])
+            .split(base_layer[0]);
+        layout.general = Some(
+            Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(vec![Constraint::Percentage(50); 2])
+                .split(vertical_temp_layout[0]),
+        );
+        main_area = vertical_temp_layout[1];
+    } else {
+        let status_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Length(1), Constraint::Fill(1)])
+            .split(main_area);
+        layout.status = Some(status_layout[0]);
+        main_area = status_layout[1];
+    }
 
+    // Chart row, piano keyboard and channels below each other
+    let chart_row = [Pane::Fft, Pane::Scope, Pane::Controls]
+        .iter()
+        .any(|pane| shown(*pane));
+    let columns = (main_area.width / CHANNEL_WIDTH).clamp(2, CHANNELS as u16) as usize;
+    let rows = CHANNELS.div_ceil(columns);
+    let mut constraints = Vec::new();
+    if chart_row {
+        constraints.push(Constraint::Fill(1));
+    }
+    if shown(Pane::Piano) {
+        constraints.push(Constraint::Length(5));
+    }
+    if shown(Pane::Channels) {
+        constraints.push(if chart_row {
+            Constraint::Length((rows as u16 * CHANNEL_HEIGHT).min(main_area.height / 2))
+        } else {
+            Constraint::Fill(1)
+        });
+    }
     let main_layout = Layout::default()
         .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Percentage(50); 2])
-        .split(vertical_temp_layout[1]);
+        .constraints(constraints)
+        .split(main_area);
+    let mut rows_left = main_layout.iter();
 
-    let channel_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints(vec![Constraint::Percentage(50); 2])
-        .split(main_layout[1]);
-
-    let mut upper_channel_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(20); 5])
-        .split(channel_layout[0])
-        .to_vec();
-
-    let mut lower_channel_layout = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints(vec![Constraint::Percentage(20); 5])
-        .split(channel_layout[1])
-        .to_vec();
-
-    let mut channel_layout = Vec::<Rect>::new();
-    channel_layout.append(&mut upper_channel_layout);
-    channel_layout.append(&mut lower_channel_layout);
-
-    return (base_layer, general_layout, main_layout, channel_layout.into());
+    // FFT chart with the scope, wheels and cc knobs next to it
+    if chart_row {
+        let row = *rows_left.next().unwrap_or(&main_area);
+        let panes: Vec<(Pane, u16)> = [(Pane::Fft, 8), (Pane::Scope, 5), (Pane::Controls, 7)]
+            .into_iter()
+            .filter(|(pane, _)| shown(*pane))
+            .collect();
+        let fft_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(panes.iter().map(|(_, weight)| Constraint::Fill(*weight)))
+            .split(row);
+        for ((pane, _), rect) in panes.iter().zip(fft_layout.iter()) {
+            match pane {
+                Pane::Fft => layout.fft = Some(*rect),
+                Pane::Scope => layout.scope = Some(*rect),
+                _ => {
+                    let controls = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints(vec![Constraint::Fill(2), Constraint::Fill(5)])
+                        .split(*rect);
+                    layout.wheels = Some(controls[0]);
+                    layout.knobs = Some(controls[1]);
+                }
+            }
+        }
+    }
+    if shown(Pane::Piano) {
+        layout.piano = rows_left.next().copied();
+    }
+
+    // Channel grid, as many columns as fit and only the rows with space
+    if let Some(channel_area) = rows_left.next().filter(|_| shown(Pane::Channels)) {
+        let visible_rows = rows.min((channel_area.height / CHANNEL_HEIGHT).max(1) as usize);
+        layout.hidden_channels = CHANNELS.saturating_sub(visible_rows * columns);
+        let row_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Ratio(1, visible_rows as u32); visible_rows])
+            .split(*channel_area);
+        let mut channel_layout = Vec::<Rect>::new();
+        for row in row_layout.iter() {
+            let mut row_channels = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Ratio(1, columns as u32); columns])
+                .split(*row)
+                .to_vec();
+            channel_layout.append(&mut row_channels);
+        }
+        channel_layout.truncate(CHANNELS);
+        layout.channels = Some(channel_layout.into());
+    }
+
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{Terminal, backend::TestBackend};
+
+    // Layout of a terminal size, the channel panes and the cut off ones
+    fn layout(width: u16, height: u16, config: &LayoutConfig) -> (MainLayout, Vec<Rect>) {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        let mut result = None;
+        terminal
+            .draw(|frame| result = Some(generate_main_layout(frame, config)))
+            .unwrap();
+        let layout = result.unwrap();
+        let channels = layout.channels.as_deref().unwrap_or_default().to_vec();
+        (layout, channels)
+    }
+
+    #[test]
+    fn narrow_terminal_keeps_status_line() {
+        let (narrow, _) = layout(100, 40, &LayoutConfig::default());
+        assert!(narrow.general.is_none());
+        assert_eq!(narrow.status.map(|s| s.height), Some(1));
+        let (wide, _) = layout(200, 60, &LayoutConfig::default());
+        assert!(wide.general.is_some());
+        assert!(wide.status.is_none());
+    }
+
+    #[test]
+    fn cut_channels_are_counted() {
+        for (width, height) in [(60, 20), (100, 40), (130, 45), (200, 60), (300, 80)] {
+            for arrangement in [Arrangement::Standard, Arrangement::Channels] {
+                let config = LayoutConfig {
+                    arrangement,
+                    hidden: Vec::new(),
+                };
+                let (main, channels) = layout(width, height, &config);
+                assert_eq!(channels.len() + main.hidden_channels, CHANNELS);
+                // Panes are only squeezed when not even one row fits
+                if channels.len() > CHANNELS / 2 {
+                    assert!(channels.iter().all(|c| c.height >= CHANNEL_HEIGHT));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn channels_arrangement_shows_every_channel() {
+        let config = LayoutConfig {
+            arrangement: Arrangement::Channels,
+            hidden: Vec::new(),
+        };
+        let (main, channels) = layout(200, 60, &config);
+        assert_eq!(channels.len(), CHANNELS);
+        assert_eq!(main.hidden_channels, 0);
+    }
 }
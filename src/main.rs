@@ -7,23 +7,87 @@ use std::{
     time::{Duration, Instant},
 };
 
+use crate::arpeggiator::Arpeggiator;
+use crate::cc_panel::CcPanel;
+use crate::chart_view::ChartView;
+use crate::chord::ChordConfig;
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::dds_model::DdsModel;
+use crate::fuzzer::Fuzzer;
+use crate::keymap::{Help, Keymap};
+use crate::latency::LatencyTool;
+use crate::layout_utils::Pane;
 use crate::manual_packets::ManualPackets;
+use crate::midi_input::{MidiMessage, MidiParser};
+use crate::piano::Piano;
+use crate::program_browser::ProgramBrowser;
+use crate::scope::Scope;
+use crate::sequencer::Sequencer;
+use crate::spectrum::SpectrumAnalyzer;
+use crate::stress::StressTest;
+use crate::sweep::Sweep;
+use crate::sysex::SysExLibrarian;
+use crate::theme::Themes;
+use crate::transport::Transport;
+use crate::tuning::TuningConfig;
+use crate::velocity::Velocity;
+use crate::wheels::Wheels;
 
+mod all_notes_off;
+mod arpeggiator;
+mod cc_panel;
+mod chart_view;
+mod chord;
+mod clock;
+mod config;
 mod dds_data;
+mod dds_model;
+mod fuzzer;
 mod input;
+mod keymap;
+mod latency;
 mod layout_utils;
+mod midi_input;
 mod midi_utils;
 mod render_utils;
+mod scope;
 mod serial;
+mod spectrum;
+mod stress;
+mod sweep;
+mod sysex;
+mod test_runner;
+mod theme;
 mod manual_packets;
+mod piano;
 mod popup_utils;
+mod program_browser;
+mod sequencer;
+mod transport;
+mod tuning;
+mod velocity;
+mod wheels;
 
 //Entry Point
 fn main() -> Result<()> {
+    // Run a hardware test suite without the TUI
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("test") {
+        std::process::exit(test_runner::run_cli(&args[2..]));
+    }
+    // Print every keybinding as markdown
+    if args.get(1).map(String::as_str) == Some("--keybindings") {
+        print!("{}", keymap::markdown());
+        return Ok(());
+    }
+    // Load the user config before taking over the terminal so errors stay readable
+    let config = Config::load().map_err(std::io::Error::other)?;
+    let themes = Themes::load(&config.theme, &config.themes).map_err(std::io::Error::other)?;
     // Create a ratatui terminal
     let terminal = ratatui::init();
     // Start the main loop
-    let result = TuneIn::new().run(terminal);
+    let result = TuneIn::new(&config, themes).run(terminal);
     // Stop execution
     ratatui::restore();
     result
@@ -38,6 +102,30 @@ pub enum AppState {
     Manual = 1,
     // Config state for selecting and configuring the com connection
     ComConfig = 2,
+    // Config state for loading a Scala tuning
+    Tuning = 3,
+    // Config state for entering a custom chord
+    Chord = 4,
+    // Step sequencer screen
+    Sequencer = 5,
+    // Config state for assigning a cc knob
+    KnobConfig = 6,
+    // Program change browser
+    Programs = 7,
+    // SysEx librarian
+    SysEx = 8,
+    // Throughput stress test
+    Stress = 9,
+    // Round trip latency measurement
+    Latency = 10,
+    // MIDI fuzzer
+    Fuzz = 11,
+    // Note and velocity sweeps
+    Sweep = 12,
+    // Spectrum analysis of a recorded wav
+    Analysis = 13,
+    // Reference dds model rendering to wav
+    Model = 14,
 }
 
 // Main App struct
@@ -46,20 +134,70 @@ struct TuneIn {
     dds_config: dds_data::DdsData,                  //DDS main struct
     com_config: serial::ComConfig,                  //Com main struct
     manual_config: manual_packets::ManualPackets,   //Manual mode main struct
-    current_attenu: f64,                            //Current attenuatino for sending MIDI
+    tuning_config: tuning::TuningConfig,            //Tuning main struct
+    velocity: velocity::Velocity,                   //Velocity model for sending MIDI
     current_octave: i32,                            //Current octave for sending MIDI
+    middle_c: midi_utils::MiddleC,                  //Octave naming of middle C in every note name
+    arp: arpeggiator::Arpeggiator,                  //Arpeggiator for the held notes
+    chord_config: chord::ChordConfig,               //Chord played by a single key
+    transport: transport::Transport,                //Shared tempo and play position
+    sequencer: sequencer::Sequencer,                //Step sequencer main struct
+    clock: clock::Clock,                            //Midi clock output
+    cc_panel: cc_panel::CcPanel,                    //Virtual cc knobs
+    wheels: wheels::Wheels,                         //Pitch bend and mod wheel
+    programs: program_browser::ProgramBrowser,      //Program change browser
+    sysex: sysex::SysExLibrarian,                   //SysEx librarian
+    stress: stress::StressTest,                     //Throughput stress test
+    latency: latency::LatencyTool,                  //Round trip latency measurement
+    fuzzer: fuzzer::Fuzzer,                         //MIDI fuzzer
+    sweep: sweep::Sweep,                            //Note and velocity sweeps
+    analyzer: spectrum::SpectrumAnalyzer,           //Spectrum analysis of a recording
+    model: dds_model::DdsModel,                     //Reference dds model
+    scope: scope::Scope,                            //Oscilloscope of the summed voices
+    chart_view: chart_view::ChartView,              //Axes and range of the fft chart
+    piano: piano::Piano,                            //Piano keyboard of the held and received notes
+    layout: layout_utils::LayoutConfig,             //Pane arrangement of the main view
+    layout_status: String,                          //Arrangement and result of saving it
+    themes: theme::Themes,                          //Colour themes
+    help: keymap::Help,                             //Help popup with the shortcuts
+    midi_in: midi_input::MidiParser,                //Parser for received bytes
 }
 
 impl TuneIn {
     // Create a new struct with default settings
-    fn new() -> Self {
+    fn new(config: &Config, themes: Themes) -> Self {
         Self {
             state: AppState::ComConfig,
             dds_config: DdsData::new(),
             com_config: ComConfig::new(),
             manual_config: ManualPackets::new(),
-            current_attenu: 185.,
+            tuning_config: TuningConfig::new(),
+            velocity: Velocity::new(),
             current_octave: 1,
+            middle_c: config.middle_c,
+            arp: Arpeggiator::new(),
+            chord_config: ChordConfig::new(),
+            transport: Transport::new(),
+            sequencer: Sequencer::new(),
+            clock: Clock::new(),
+            cc_panel: CcPanel::new(),
+            wheels: Wheels::new(),
+            programs: ProgramBrowser::new(&config.devices),
+            sysex: SysExLibrarian::new(),
+            stress: StressTest::new(),
+            latency: LatencyTool::new(),
+            fuzzer: Fuzzer::new(),
+            sweep: Sweep::new(),
+            analyzer: SpectrumAnalyzer::new(),
+            model: DdsModel::new(),
+            scope: Scope::new(),
+            chart_view: ChartView::new(),
+            piano: Piano::new(),
+            layout: config.layout.clone(),
+            themes,
+            help: Help::new(),
+            layout_status: format!("Layout: {}", config.layout.arrangement.name()),
+            midi_in: MidiParser::new(),
         }
     }
     // Run the Programm
@@ -88,6 +226,10 @@ impl TuneIn {
                     }
                     // Check who handle events currently
                     match self.state {
+                        // Keys of the help popup
+                        _ if self.help.open => self.help.key_event(key),
+                        // Open the help, ? is a normal character while typing
+                        _ if key.code == KeyCode::Char('?') && !self.typing() => self.help.open(),
                         // Running state
                         AppState::Running => match key.code {
                             //Quit
@@ -101,92 +243,176 @@ impl TuneIn {
                                 self.state = AppState::ComConfig;
                                 self.com_config.scan_serialports();
                             }
+                            //Change state to tuning config
+                            KeyCode::Char('t') => {
+                                self.state = AppState::Tuning;
+                            }
                             //Send Midi message
-                            KeyCode::Char('s') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    261.63 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
+                            KeyCode::Char(c @ ('s' | 'd' | 'f' | 'g' | 'h' | 'j' | 'k' | 'l')) => {
+                                self.toggle_note(
+                                    60 + 12 * self.current_octave + midi_utils::key_semitone(c),
                                 );
                             }
-                            KeyCode::Char('d') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    293.66 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            //Change velocity
+                            KeyCode::Char('v') => self.velocity.decrease(),
+                            KeyCode::Char('V') => self.velocity.increase(),
+                            KeyCode::Char('u') => self.velocity.cycle_curve(),
+                            KeyCode::Char('U') => self.velocity.cycle_distribution(),
+                            KeyCode::Char('[') => self.velocity.change_random_min(false),
+                            KeyCode::Char(']') => self.velocity.change_random_min(true),
+                            KeyCode::Char('{') => self.velocity.change_random_max(false),
+                            KeyCode::Char('}') => self.velocity.change_random_max(true),
+                            //Change octave
+                            KeyCode::Char('n') if self.current_octave > midi_utils::LOWEST_OCTAVE => {
+                                self.current_octave -= 1;
                             }
-                            KeyCode::Char('f') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    329.63 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            KeyCode::Char('N') if self.current_octave < midi_utils::HIGHEST_OCTAVE => {
+                                self.current_octave += 1;
                             }
-                            KeyCode::Char('g') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    349.23 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            KeyCode::Char('c') => {
+                                for tone in self.dds_config.signal_data.clone() {
+                                    self.dds_config.remove_signal(&mut self.com_config, tone.freq);
+                                }
                             }
-                            KeyCode::Char('h') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    392.00 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            KeyCode::Char('r') => {
+                                self.velocity.toggle_rand();
                             }
-                            KeyCode::Char('j') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    440.00 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            //Arpeggiator
+                            KeyCode::Char('a') => {
+                                self.arp.toggle(&mut self.dds_config, &mut self.com_config);
                             }
-                            KeyCode::Char('k') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    493.88 * f64::powi(2., self.current_octave),
-                                    self.current_attenu,
-                                );
+                            KeyCode::Char('A') => self.arp.cycle_pattern(),
+                            KeyCode::Char(',') => self.arp.change_rate(false),
+                            KeyCode::Char('.') => self.arp.change_rate(true),
+                            KeyCode::Char('y') => self.arp.change_octaves(false),
+                            KeyCode::Char('Y') => self.arp.change_octaves(true),
+                            KeyCode::Char('<') => self.arp.change_gate(false),
+                            KeyCode::Char('>') => self.arp.change_gate(true),
+                            //Chord mode
+                            KeyCode::Char('z') => self.chord_config.cycle_chord(),
+                            KeyCode::Char('Z') => {
+                                self.state = AppState::Chord;
                             }
-                            KeyCode::Char('l') => {
-                                self.dds_config.toggle_signal(
-                                    &mut self.com_config,
-                                    261.63 * f64::powi(2., self.current_octave + 1),
-                                    self.current_attenu,
-                                );
+                            //Change state to sequencer
+                            KeyCode::Char('e') => {
+                                self.state = AppState::Sequencer;
                             }
-                            //Change attenuatino
-                            KeyCode::Char('v') => {
-                                if self.current_attenu > 0. {
-                                    self.current_attenu -= 5.;
+                            //Transport and clock
+                            KeyCode::Char(' ') => {
+                                if self.transport.playing {
+                                    self.transport.stop();
+                                } else {
+                                    self.transport.start();
                                 }
                             }
-                            KeyCode::Char('V') => {
-                                if self.current_attenu < 255. {
-                                    self.current_attenu += 5.;
-                                }
+                            KeyCode::Char('B') if !self.transport.playing => {
+                                self.transport.resume();
                             }
-                            //Change octave
-                            KeyCode::Char('n') => {
-                                if self.current_octave > -6 {
-                                    self.current_octave -= 1;
-                                }
+                            KeyCode::Char('b') => self.clock.tap(&mut self.transport),
+                            KeyCode::Char('-') => self.transport.set_bpm(self.transport.bpm - 1.),
+                            KeyCode::Char('+') => self.transport.set_bpm(self.transport.bpm + 1.),
+                            KeyCode::Char('C') => self.clock.toggle(&self.transport, &mut self.com_config),
+                            //Panic
+                            KeyCode::Char('x') => self.panic(false),
+                            KeyCode::Char('X') => self.panic(true),
+                            //CC knobs
+                            KeyCode::Left => self.cc_panel.select(false),
+                            KeyCode::Right => self.cc_panel.select(true),
+                            KeyCode::Up => self.cc_panel.adjust(&mut self.com_config, true, false),
+                            KeyCode::Down => self.cc_panel.adjust(&mut self.com_config, false, false),
+                            KeyCode::PageUp => self.cc_panel.adjust(&mut self.com_config, true, true),
+                            KeyCode::PageDown => {
+                                self.cc_panel.adjust(&mut self.com_config, false, true)
                             }
-                            KeyCode::Char('N') => {
-                                if self.current_octave < 4 {
-                                    self.current_octave += 1;
-                                }
+                            KeyCode::Char('K') => {
+                                self.state = AppState::KnobConfig;
                             }
-                            KeyCode::Char('c') => {
-                                for tone in self.dds_config.signal_data.clone() {
-                                    self.dds_config.remove_signal(&mut self.com_config, tone.0);
+                            //Pitch bend and mod wheel
+                            KeyCode::Char('i') => self.wheels.bend(&mut self.com_config, &self.dds_config, false),
+                            KeyCode::Char('o') => self.wheels.bend(&mut self.com_config, &self.dds_config, true),
+                            KeyCode::Char('I') => self.wheels.modulate(&mut self.com_config, &self.dds_config, false),
+                            KeyCode::Char('O') => self.wheels.modulate(&mut self.com_config, &self.dds_config, true),
+                            KeyCode::Char('(') => self.wheels.change_range(false),
+                            KeyCode::Char(')') => self.wheels.change_range(true),
+                            KeyCode::Char('R') => self.wheels.send_bend_range(&mut self.com_config, &mut self.dds_config),
+                            //Change state to program browser
+                            KeyCode::Char('P') => {
+                                self.state = AppState::Programs;
+                            }
+                            //Change state to sysex librarian
+                            KeyCode::Char('S') => {
+                                self.state = AppState::SysEx;
+                            }
+                            //Change state to stress test
+                            KeyCode::Char('T') => {
+                                self.state = AppState::Stress;
+                            }
+                            //Change state to latency measurement
+                            KeyCode::Char('L') => {
+                                self.state = AppState::Latency;
+                            }
+                            //Change state to fuzzer
+                            KeyCode::Char('F') => {
+                                self.state = AppState::Fuzz;
+                            }
+                            //Change state to sweeps
+                            KeyCode::Char('G') => {
+                                self.state = AppState::Sweep;
+                            }
+                            //Change state to wav analysis
+                            KeyCode::Char('w') => {
+                                self.state = AppState::Analysis;
+                            }
+                            //Change state to dds model
+                            KeyCode::Char('W') => {
+                                self.state = AppState::Model;
+                            }
+                            //Waveform of a DDS slot, 0 is the tenth slot
+                            KeyCode::Char(c @ '0'..='9') => {
+                                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                                self.scope.cycle_waveform((digit + 9) % 10);
+                            }
+                            //Scope time window
+                            KeyCode::Char('*') => self.scope.zoom(true),
+                            KeyCode::Char('/') => self.scope.zoom(false),
+                            //Fft chart axes and range
+                            KeyCode::Char('E') => self.chart_view.toggle_log(),
+                            KeyCode::Char('D') => self.chart_view.toggle_db(),
+                            KeyCode::Char('H') => self.chart_view.auto_range(),
+                            KeyCode::Char(';') => {
+                                self.chart_view.zoom(false, &self.dds_config.signal_data)
+                            }
+                            KeyCode::Char('\'') => {
+                                self.chart_view.zoom(true, &self.dds_config.signal_data)
+                            }
+                            KeyCode::Home => self.chart_view.pan(false, &self.dds_config.signal_data),
+                            KeyCode::End => self.chart_view.pan(true, &self.dds_config.signal_data),
+                            //Pane arrangement and collapsed panes
+                            KeyCode::Tab => {
+                                self.layout.arrangement = self.layout.arrangement.next();
+                                self.layout_status =
+                                    format!("Layout: {}", self.layout.arrangement.name());
+                            }
+                            KeyCode::F(1) => self.layout.toggle(Pane::Info),
+                            KeyCode::F(2) => self.layout.toggle(Pane::Fft),
+                            KeyCode::F(3) => self.layout.toggle(Pane::Scope),
+                            KeyCode::F(4) => self.layout.toggle(Pane::Controls),
+                            KeyCode::F(5) => self.layout.toggle(Pane::Piano),
+                            KeyCode::F(6) => self.layout.toggle(Pane::Channels),
+                            KeyCode::F(10) => {
+                                self.layout_status = match Config::save_layout(&self.layout) {
+                                    Ok(path) => format!("Layout saved to {}", path.display()),
+                                    Err(e) => format!("Layout not saved: {}", e),
                                 }
                             }
-                            KeyCode::Char('r') => {
-                                self.dds_config.toggle_rand();
+                            //Colour theme
+                            KeyCode::F(7) => self.themes.cycle(),
+                            //Octave naming of middle C
+                            KeyCode::Char('M') => {
+                                self.middle_c = match self.middle_c {
+                                    midi_utils::MiddleC::C4 => midi_utils::MiddleC::C3,
+                                    midi_utils::MiddleC::C3 => midi_utils::MiddleC::C4,
+                                }
                             }
                             _ => {}
                         },
@@ -194,6 +420,49 @@ impl TuneIn {
                         AppState::Manual => self.state = self.manual_config.key_event(key, &mut self.com_config),
                         // Forward Keyevents to the com subsystem
                         AppState::ComConfig => self.state = self.com_config.key_event(key),
+                        // Forward Keyevents to the tuning subsystem
+                        AppState::Tuning => self.state = self.tuning_config.key_event(key),
+                        // Forward Keyevents to the chord subsystem
+                        AppState::Chord => self.state = self.chord_config.key_event(key),
+                        // Forward Keyevents to the cc knob subsystem
+                        AppState::KnobConfig => self.state = self.cc_panel.key_event(key),
+                        // Forward Keyevents to the program browser
+                        AppState::Programs => {
+                            self.state = self.programs.key_event(key, &mut self.com_config)
+                        }
+                        // Forward Keyevents to the sequencer
+                        AppState::Sequencer => {
+                            self.state = self.sequencer.key_event(key, &mut self.transport)
+                        }
+                        // Forward Keyevents to the sysex librarian
+                        AppState::SysEx => self.state = self.sysex.key_event(key),
+                        // Forward Keyevents to the stress test
+                        AppState::Stress => {
+                            self.state = self.stress.key_event(key, &mut self.com_config)
+                        }
+                        // Forward Keyevents to the latency tool
+                        AppState::Latency => {
+                            self.state = self.latency.key_event(key, &mut self.com_config)
+                        }
+                        // Forward Keyevents to the fuzzer
+                        AppState::Fuzz => {
+                            self.state = self.fuzzer.key_event(key, &mut self.com_config)
+                        }
+                        // Forward Keyevents to the sweeps
+                        AppState::Sweep => {
+                            self.state = self.sweep.key_event(key, &mut self.com_config)
+                        }
+                        // Forward Keyevents to the wav analysis
+                        AppState::Analysis => self.state = self.analyzer.key_event(key),
+                        // Forward Keyevents to the dds model
+                        AppState::Model => {
+                            self.state = self.model.key_event(
+                                key,
+                                &mut self.com_config,
+                                &self.sequencer,
+                                &self.transport,
+                            )
+                        }
                     }
                 }
             }
@@ -204,37 +473,218 @@ impl TuneIn {
         }
     }
 
-    fn on_tick(&mut self) {}
+    // Toggle the tones of a midi key in the current tuning, a whole chord in chord mode
+    fn toggle_note(&mut self, note: i32) {
+        let freqs: Vec<f64> = self
+            .chord_config
+            .chord_keys(note)
+            .into_iter()
+            .filter(|key| (0..=127).contains(key))
+            .filter_map(|key| self.tuning_config.tuning.frequency(key))
+            .collect();
+        let velocity = self.velocity.next_velocity();
+        self.dds_config
+            .toggle_key(&mut self.com_config, note, &freqs, velocity);
+    }
+
+    // Stop everything that plays and silence the synth
+    fn panic(&mut self, note_offs: bool) {
+        // Nothing may send a note right after the panic
+        self.sweep.stop(&mut self.com_config);
+        self.fuzzer.stop();
+        self.arp.stop();
+        self.transport.stop();
+        self.dds_config.clear();
+        // The panic resets the controllers of the synth, show the wheels and knobs reset too
+        self.wheels.reset();
+        self.cc_panel.reset();
+        let result = all_notes_off::send_panic(&mut self.com_config, note_offs);
+        self.com_config.report_send(result);
+    }
+
+    fn on_tick(&mut self) {
+        // Route received messages before sending, so the sends of this tick don't delay the read
+        let (read_at, received) = self.com_config.read_bytes();
+        for message in self.midi_in.feed(&received) {
+            match message {
+                MidiMessage::SysEx(data) => {
+                    self.latency.receive(&data, read_at);
+                    self.fuzzer.receive(&data);
+                    self.sysex.capture(&data);
+                    if self.stress.listening() {
+                        self.stress.receive(&data);
+                    }
+                }
+                MidiMessage::Channel(data) => {
+                    self.latency.receive(&data, read_at);
+                    self.piano.receive(&data);
+                }
+                _ => {}
+            }
+        }
+        self.arp.on_tick(&self.dds_config, &mut self.com_config);
+        self.sequencer.on_tick(&self.transport, &mut self.com_config);
+        self.clock.on_tick(&self.transport, &mut self.com_config);
+        self.wheels.on_tick(&mut self.com_config, &self.dds_config);
+        self.sysex.on_tick(&mut self.com_config);
+        self.stress.on_tick(&mut self.com_config);
+        self.latency.on_tick(&mut self.com_config);
+        self.fuzzer.on_tick(&mut self.com_config);
+        self.sweep.on_tick(&mut self.com_config);
+        self.model.on_tick();
+        self.analyzer.on_tick();
+    }
+    // Shortcuts of the current state
+    fn keymaps(&self) -> Vec<&'static Keymap> {
+        match self.state {
+            AppState::Running => vec![&keymap::RUNNING],
+            AppState::Manual => self.manual_config.keymaps(),
+            AppState::ComConfig => self.com_config.keymaps(),
+            AppState::Tuning => self.tuning_config.keymaps(),
+            AppState::Chord => self.chord_config.keymaps(),
+            AppState::KnobConfig => self.cc_panel.keymaps(),
+            AppState::Programs => self.programs.keymaps(),
+            AppState::Sequencer => self.sequencer.keymaps(),
+            AppState::SysEx => self.sysex.keymaps(),
+            AppState::Stress => self.stress.keymaps(),
+            AppState::Latency => self.latency.keymaps(),
+            AppState::Fuzz => self.fuzzer.keymaps(),
+            AppState::Sweep => self.sweep.keymaps(),
+            AppState::Analysis => self.analyzer.keymaps(),
+            AppState::Model => self.model.keymaps(),
+        }
+    }
+    // Whether a text field takes the keys
+    fn typing(&self) -> bool {
+        self.keymaps()
+            .iter()
+            .any(|map| std::ptr::eq(*map, &keymap::INPUT_EDITING))
+    }
     // Draw the current frame
     fn draw(&mut self, frame: &mut Frame) {
         // Get the Layout
-        let (base_layer, general_layout, fft_layout, channel_layout) =
-            layout_utils::generate_main_layout(frame);
-        let serial_table = self.com_config.get_table();
+        let layout = layout_utils::generate_main_layout(frame, &self.layout);
+        let base_layer = layout.base_layer.clone();
+        let theme = self.themes.current();
+        if layout.too_small {
+            render_utils::render_too_small(frame, base_layer[0], theme);
+        }
         // Render the main features
-        render_utils::render_general(
-            frame,
-            general_layout,
-            serial_table,
-            self.current_attenu,
-            self.current_octave,
-        );
-        render_utils::render_dds(frame, fft_layout, &self.dds_config.signal_data);
-        render_utils::render_channels(frame, channel_layout, &self.dds_config.signal_data);
+        if let Some(general_layout) = layout.general.clone() {
+            let serial_table = self.com_config.get_table(theme);
+            render_utils::render_general(
+                frame,
+                general_layout,
+                serial_table,
+                &self.velocity,
+                self.current_octave,
+                &[
+                    self.arp.status(),
+                    self.chord_config.status(),
+                    self.clock.status(&self.transport),
+                    self.layout_status.clone(),
+                    format!("Theme: {}", self.themes.name()),
+                ],
+                theme,
+            );
+        }
+        if let Some(area) = layout.status {
+            let mut line = self.com_config.get_status_line(theme);
+            line.push_span(format!(
+                " | Octave {} | Velocity {}",
+                self.current_octave,
+                self.velocity.value()
+            ));
+            frame.render_widget(line, area);
+        }
+        if let Some(area) = layout.fft {
+            render_utils::render_dds(
+                frame,
+                area,
+                &self.dds_config.signal_data,
+                &self.analyzer,
+                &self.chart_view,
+                self.middle_c,
+                theme,
+            );
+        }
+        if let Some(area) = layout.scope {
+            self.scope
+                .render_scope(frame, area, &self.dds_config.signal_data, theme);
+        }
+        if let Some(area) = layout.wheels {
+            self.wheels.render_wheels(frame, area, theme);
+        }
+        if let Some(area) = layout.knobs {
+            self.cc_panel.render_knobs(frame, area, theme);
+        }
+        if let Some(area) = layout.piano {
+            self.piano.render_piano(
+                frame,
+                area,
+                &self.dds_config.signal_data,
+                60 + 12 * self.current_octave,
+                self.middle_c,
+                theme,
+            );
+        }
+        if layout.channels.is_some() {
+            // The note of a running sweep gets a free pane, the held tones keep theirs
+            let mut signals = self.dds_config.signal_data.clone();
+            let mut waveforms = self.scope.waveform_names();
+            let slot = self.dds_config.free_slot().unwrap_or(dds_data::VOICES);
+            let highlight = self.sweep.current(slot).map(|(signal, label)| {
+                signals.push(signal);
+                if let Some(name) = waveforms.get_mut(slot) {
+                    *name = "";
+                }
+                (slot, label)
+            });
+            render_utils::render_channels(
+                frame,
+                &layout,
+                &signals,
+                &waveforms,
+                highlight,
+                self.middle_c,
+                theme,
+            );
+        }
 
-        // Show the com popup and shortcuts
+        // Show the popup of the current state
         match self.state {
-            AppState::Manual => {
-                self.manual_config.show_manual_popup(frame);
-                self.manual_config.render_shortcuts(frame, base_layer);
-            },
-            AppState::ComConfig => {
-                self.com_config.show_com_popup(frame);
-                self.com_config.render_shortcuts(frame, base_layer);
-            },
-            _ => {
-                render_utils::render_shortcuts(frame, base_layer);
+            AppState::Manual => self.manual_config.show_manual_popup(frame, theme),
+            AppState::ComConfig => self.com_config.show_com_popup(frame, theme),
+            AppState::Tuning => self.tuning_config.show_tuning_popup(frame, theme),
+            AppState::Chord => self.chord_config.show_chord_popup(frame, theme),
+            AppState::Programs => self.programs.show_program_popup(frame, theme),
+            AppState::KnobConfig => self.cc_panel.show_knob_popup(frame, theme),
+            AppState::Sequencer => {
+                self.sequencer
+                    .show_sequencer_popup(frame, &self.transport, self.middle_c, theme)
+            }
+            AppState::SysEx => self.sysex.show_sysex_popup(frame, theme),
+            AppState::Stress => self.stress.show_stress_popup(frame, &self.com_config, theme),
+            AppState::Latency => self.latency.show_latency_popup(frame, &self.com_config, theme),
+            AppState::Fuzz => self.fuzzer.show_fuzz_popup(frame, theme),
+            AppState::Sweep => self.sweep.show_sweep_popup(frame, self.middle_c, theme),
+            AppState::Analysis => self
+                .analyzer
+                .show_analysis_popup(frame, &self.dds_config.signal_data, self.middle_c, theme),
+            AppState::Model => self.model.show_model_popup(frame, &self.com_config, theme),
+            AppState::Running => {}
+        }
+        // Help over everything and the shortcuts
+        if self.help.open {
+            self.help.show_help_popup(frame, &self.keymaps(), theme);
+            keymap::render_shortcuts(frame, base_layer, &[&keymap::HELP], theme);
+        } else {
+            // ? opens the help unless it is typed into a field
+            let mut keymaps = self.keymaps();
+            if !self.typing() {
+                keymaps.insert(0, &keymap::GLOBAL);
             }
+            keymap::render_shortcuts(frame, base_layer, &keymaps, theme);
         }
     }
 }
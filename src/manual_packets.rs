@@ -1,18 +1,19 @@
 use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::{
-    layout::{Constraint, Direction, Layout, Position, Rect}, style::{Modifier, Style, Stylize}, text::{Line, Span, Text}, widgets::{Block, BorderType, Clear, Paragraph}, Frame
+    layout::{Constraint, Direction, Layout, Position}, style::{Modifier, Style}, text::{Line, Span, Text}, widgets::{Block, BorderType, Clear, Paragraph}, Frame
 };
-use std::rc::Rc;
 
 use crate::AppState;
 use crate::input::Input;
+use crate::keymap::{self, Keymap};
 use crate::popup_utils::popup_area;
 use crate::ComConfig;
+use crate::theme::Theme;
 
 enum PacketConfigState {
-    CommandInput,
-    NoteInput,
-    VelocityInput,
+    Command,
+    Note,
+    Velocity,
 }
 // Main com config struct
 pub struct ManualPackets {
@@ -26,7 +27,7 @@ impl ManualPackets {
     //Constructor with default values
     pub fn new() -> Self {
         Self {
-            config_state: PacketConfigState::CommandInput,
+            config_state: PacketConfigState::Command,
             command_input: Input::new(),
             note_input: Input::new(),
             velocity_input: Input::new(),
@@ -91,17 +92,17 @@ impl ManualPackets {
             },
             _ => {
                 match self.config_state {
-                    PacketConfigState::CommandInput => {
+                    PacketConfigState::Command => {
                         if self.command_input.key_event(key) {
                             app_state = AppState::Running;
                         }
                     }
-                    PacketConfigState::NoteInput => {
+                    PacketConfigState::Note => {
                         if self.note_input.key_event(key) {
                             app_state = AppState::Running;
                         }
                     }
-                    PacketConfigState::VelocityInput => {
+                    PacketConfigState::Velocity => {
                         if self.velocity_input.key_event(key) {
                             app_state = AppState::Running;
                         }
@@ -109,18 +110,18 @@ impl ManualPackets {
                 };
             }
         }
-        return app_state;
+        app_state
     }
     // Toggle state
     fn toggle_state(&mut self) {
         match self.config_state {
-            PacketConfigState::CommandInput => self.config_state = PacketConfigState::NoteInput,
-            PacketConfigState::NoteInput => self.config_state = PacketConfigState::VelocityInput,
-            PacketConfigState::VelocityInput => self.config_state = PacketConfigState::CommandInput,
+            PacketConfigState::Command => self.config_state = PacketConfigState::Note,
+            PacketConfigState::Note => self.config_state = PacketConfigState::Velocity,
+            PacketConfigState::Velocity => self.config_state = PacketConfigState::Command,
         }
     }
     //Render a popup form Com settings
-    pub fn show_manual_popup(&mut self, frame: &mut Frame) {
+    pub fn show_manual_popup(&mut self, frame: &mut Frame, theme: &Theme) {
         // Area of the popup
         let area = popup_area(frame.area(), 60, 40);
         // Clear area
@@ -158,27 +159,27 @@ impl ManualPackets {
         );
 
         frame.render_widget(
-            self.command_input.get_input(String::from("Command"))
+            self.command_input.get_input(String::from("Command"), theme)
             .style(Style::default()),
             vertical_layout[1],
         );
         frame.render_widget(
             self.note_input
-                .get_input(String::from("Note"))
+                .get_input(String::from("Note"), theme)
                 .style(Style::default()),
             vertical_layout[2],
         );
         frame.render_widget(
             self.velocity_input
-                .get_input(String::from("Velocity"))
+                .get_input(String::from("Velocity"), theme)
                 .style(Style::default()),
             vertical_layout[3],
         );
         // Highlighting
         match self.config_state {
-            PacketConfigState::CommandInput => {
+            PacketConfigState::Command => {
                 frame.render_widget(
-            self.command_input.get_input(String::from("Command")),
+            self.command_input.get_input(String::from("Command"), theme),
                     vertical_layout[1],
                 );
                 frame.set_cursor_position(Position::new(
@@ -189,10 +190,10 @@ impl ManualPackets {
                     vertical_layout[1].y + vertical_layout[2].height - 2,
                 ));
             }
-            PacketConfigState::NoteInput => {
+            PacketConfigState::Note => {
                 frame.render_widget(
             self.note_input
-                        .get_input(String::from("Note")),
+                        .get_input(String::from("Note"), theme),
                     vertical_layout[2],
                 );
                 frame.set_cursor_position(Position::new(
@@ -203,10 +204,10 @@ impl ManualPackets {
                     vertical_layout[2].y + vertical_layout[2].height - 2,
                 ));
             }
-            PacketConfigState::VelocityInput => {
+            PacketConfigState::Velocity => {
                 frame.render_widget(
             self.velocity_input
-                        .get_input(String::from("Velocity")),
+                        .get_input(String::from("Velocity"), theme),
                     vertical_layout[3],
                 );
                 frame.set_cursor_position(Position::new(
@@ -219,13 +220,8 @@ impl ManualPackets {
             }
         }
     }
-    // Render shortcuts
-    pub fn render_shortcuts(&self, frame: &mut Frame, layout: Rc<[Rect]>) {
-        let shortcuts = "Quit Manual: q | \
-                                        Switch input field: tab | \
-                                        Send: Enter | \
-                                        Clear inputs: c";
-        let paragraph = Paragraph::new(shortcuts).style(Style::new().blue());
-        frame.render_widget(paragraph, layout[1]);
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        vec![&keymap::MANUAL]
     }
 }
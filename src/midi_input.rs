@@ -0,0 +1,154 @@
+// A complete message received from the device
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MidiMessage {
+    Channel(Vec<u8>), //Channel voice message with its status byte, running status is expanded
+    SysEx(Vec<u8>),   //System exclusive message including F0 and F7
+    Common(Vec<u8>),  //System common message
+    RealTime(u8),     //Single byte real time message
+}
+// Splits the received byte stream into messages
+pub struct MidiParser {
+    status: Option<u8>,     //Running status
+    data: Vec<u8>,          //Data bytes of the current message
+    sysex: Option<Vec<u8>>, //System exclusive message being received
+}
+
+impl MidiParser {
+    // Create new struct
+    pub fn new() -> Self {
+        Self {
+            status: None,
+            data: Vec::new(),
+            sysex: None,
+        }
+    }
+    // Feed received bytes and return the messages they completed
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut messages = Vec::new();
+        for &byte in bytes {
+            match byte {
+                // Real time messages may appear anywhere, even inside other messages
+                0xF8..=0xFF => messages.push(MidiMessage::RealTime(byte)),
+                0xF0 => {
+                    self.status = None;
+                    self.sysex = Some(vec![byte]);
+                }
+                0xF7 => {
+                    if let Some(mut sysex) = self.sysex.take() {
+                        sysex.push(byte);
+                        messages.push(MidiMessage::SysEx(sysex));
+                    }
+                }
+                // Any other status byte ends an unterminated system exclusive message
+                0x80..=0xF6 => {
+                    self.sysex = None;
+                    self.data.clear();
+                    self.status = Some(byte);
+                    match byte {
+                        0xF6 => {
+                            messages.push(MidiMessage::Common(vec![byte]));
+                            self.status = None;
+                        }
+                        // Undefined system common messages, their data bytes are dropped
+                        0xF4 | 0xF5 => self.status = None,
+                        _ => {}
+                    }
+                }
+                _ => {
+                    if let Some(sysex) = self.sysex.as_mut() {
+                        sysex.push(byte);
+                        continue;
+                    }
+                    // Stray data bytes without a status are dropped
+                    let Some(status) = self.status else {
+                        continue;
+                    };
+                    self.data.push(byte);
+                    if self.data.len() < data_length(status) {
+                        continue;
+                    }
+                    let mut message = vec![status];
+                    message.append(&mut self.data);
+                    if status >= 0xF0 {
+                        // System common messages don't have running status
+                        self.status = None;
+                        messages.push(MidiMessage::Common(message));
+                    } else {
+                        messages.push(MidiMessage::Channel(message));
+                    }
+                }
+            }
+        }
+        messages
+    }
+}
+
+// Number of data bytes following a status byte
+pub fn data_length(status: u8) -> usize {
+    match status {
+        0xC0..=0xDF | 0xF1 | 0xF3 => 1,
+        0x80..=0xEF | 0xF2 => 2,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_running_status() {
+        let mut parser = MidiParser::new();
+        let messages = parser.feed(&[0x90, 0x3C, 0x64, 0x40, 0x00, 0xC1, 0x05, 0x06]);
+        assert_eq!(messages, vec![
+            MidiMessage::Channel(vec![0x90, 0x3C, 0x64]),
+            MidiMessage::Channel(vec![0x90, 0x40, 0x00]),
+            MidiMessage::Channel(vec![0xC1, 0x05]),
+            MidiMessage::Channel(vec![0xC1, 0x06]),
+        ]);
+    }
+
+    #[test]
+    fn real_time_bytes_inside_messages() {
+        let mut parser = MidiParser::new();
+        let messages = parser.feed(&[0x90, 0xF8, 0x3C, 0xFE, 0x64, 0xF0, 0x41, 0xF8, 0x10, 0xF7]);
+        assert_eq!(messages, vec![
+            MidiMessage::RealTime(0xF8),
+            MidiMessage::RealTime(0xFE),
+            MidiMessage::Channel(vec![0x90, 0x3C, 0x64]),
+            MidiMessage::RealTime(0xF8),
+            MidiMessage::SysEx(vec![0xF0, 0x41, 0x10, 0xF7]),
+        ]);
+    }
+
+    #[test]
+    fn messages_split_over_reads() {
+        let mut parser = MidiParser::new();
+        assert!(parser.feed(&[0xE0, 0x00]).is_empty());
+        assert_eq!(parser.feed(&[0x40]), vec![MidiMessage::Channel(vec![0xE0, 0x00, 0x40])]);
+        assert!(parser.feed(&[0xF0, 0x7E]).is_empty());
+        assert_eq!(parser.feed(&[0x7F, 0xF7]), vec![MidiMessage::SysEx(vec![
+            0xF0, 0x7E, 0x7F, 0xF7
+        ])]);
+    }
+
+    #[test]
+    fn system_common_ends_running_status() {
+        let mut parser = MidiParser::new();
+        let messages = parser.feed(&[0x90, 0x3C, 0x64, 0xF3, 0x02, 0x40, 0x00, 0xF6, 0x40]);
+        assert_eq!(messages, vec![
+            MidiMessage::Channel(vec![0x90, 0x3C, 0x64]),
+            MidiMessage::Common(vec![0xF3, 0x02]),
+            MidiMessage::Common(vec![0xF6]),
+        ]);
+        assert!(parser.feed(&[0xF5, 0x01, 0x02]).is_empty());
+    }
+
+    #[test]
+    fn drops_stray_and_unterminated_data() {
+        let mut parser = MidiParser::new();
+        // Data without status and a sysex cut off by a new status byte
+        let messages = parser.feed(&[0x3C, 0x64, 0xF0, 0x41, 0x10, 0x80, 0x3C, 0x00, 0xF7]);
+        assert_eq!(messages, vec![MidiMessage::Channel(vec![0x80, 0x3C, 0x00])]);
+    }
+}
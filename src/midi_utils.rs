@@ -1,4 +1,160 @@
+use serde::Deserialize;
+
+// Octave naming of middle C (60)
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum MiddleC {
+    #[default]
+    C4, //Scientific pitch notation
+    C3, //Used by Yamaha and many DAWs
+}
+
+// Center value of the 14 bit pitch bend
+pub const PITCH_BEND_CENTER: u16 = 0x2000;
+// Pitch bend range in semitones synths start with, used until another range is sent
+pub const PITCH_BEND_RANGE: f64 = 2.;
+// Octaves the home row can play, the keys span midi notes 0 to 120
+pub const LOWEST_OCTAVE: i32 = -5;
+pub const HIGHEST_OCTAVE: i32 = 4;
+// Home row keys and the semitone above C they play
+pub const NOTE_KEYS: [(char, i32); 8] = [
+    ('s', 0),
+    ('d', 2),
+    ('f', 4),
+    ('g', 5),
+    ('h', 7),
+    ('j', 9),
+    ('k', 11),
+    ('l', 12),
+];
+
 // Convert a frequencies
 pub fn freq_to_note_id(freq: f64) -> u8 {
-    return (12.0 * (freq / 440.0).log2() + 69.0).round() as u8;
+    (12.0 * (freq / 440.0).log2() + 69.0).round().clamp(0., 127.) as u8
+}
+// Frequency of an equal tempered midi note
+pub fn note_to_freq(note: u8) -> f64 {
+    440.0 * f64::powf(2., (note as f64 - 69.0) / 12.0)
+}
+// Deviation in cents between a frequency and the nearest midi note
+pub fn cents_offset(freq: f64) -> f64 {
+    1200.0 * (freq / note_to_freq(freq_to_note_id(freq))).log2()
+}
+// Convert a frequency to the nearest midi note and the pitch bend that reaches it exactly
+pub fn freq_to_note_bend(freq: f64, bend_range: f64) -> (u8, u16) {
+    let bend = PITCH_BEND_CENTER as f64 * (1. + cents_offset(freq) / (bend_range * 100.));
+    (freq_to_note_id(freq), bend.round().clamp(0., 16383.) as u16)
+}
+// Semitone a home row key plays
+pub fn key_semitone(key: char) -> i32 {
+    NOTE_KEYS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, semitone)| *semitone)
+        .unwrap_or(0)
+}
+// Name of a midi note with middle C (60) as C4 or C3
+pub fn note_name(note: u8, middle_c: MiddleC) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    let offset = match middle_c {
+        MiddleC::C4 => 1,
+        MiddleC::C3 => 2,
+    };
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - offset)
+}
+// Parse space separated hex bytes like "90 3C 64"
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    text.split_whitespace()
+        .map(|b| {
+            // from_str_radix alone would accept signs like +7
+            let digits = b
+                .strip_prefix("0x")
+                .or(b.strip_prefix("0X"))
+                .unwrap_or(b);
+            match digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                true => u8::from_str_radix(digits, 16).ok(),
+                false => None,
+            }
+            .ok_or(format!("Not a valid hex byte: {}", b))
+        })
+        .collect()
+}
+// Format bytes as space separated hex
+pub fn format_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_bend_on_the_grid() {
+        assert_eq!(freq_to_note_bend(440., PITCH_BEND_RANGE), (69, PITCH_BEND_CENTER));
+        assert_eq!(freq_to_note_bend(note_to_freq(0), PITCH_BEND_RANGE), (0, PITCH_BEND_CENTER));
+        assert_eq!(freq_to_note_bend(note_to_freq(127), PITCH_BEND_RANGE), (127, PITCH_BEND_CENTER));
+    }
+
+    #[test]
+    fn note_bend_between_notes() {
+        // A quarter tone above A4 is half way up a 2 semitone bend range
+        let (note, bend) = freq_to_note_bend(440. * 2f64.powf(0.49 / 12.), PITCH_BEND_RANGE);
+        assert_eq!(note, 69);
+        assert_eq!(bend, 8192 + 2007);
+        let (note, bend) = freq_to_note_bend(440. * 2f64.powf(-0.49 / 12.), 1.);
+        assert_eq!(note, 69);
+        assert_eq!(bend, 8192 - 4014);
+    }
+
+    #[test]
+    fn note_bend_outside_midi_range() {
+        // The note is clamped and the bend saturates
+        let (note, bend) = freq_to_note_bend(1., PITCH_BEND_RANGE);
+        assert_eq!((note, bend), (0, 0));
+        let (note, bend) = freq_to_note_bend(20_000., PITCH_BEND_RANGE);
+        assert_eq!((note, bend), (127, 16383));
+    }
+
+    #[test]
+    fn note_name_follows_middle_c() {
+        assert_eq!(note_name(60, MiddleC::C4), "C4");
+        assert_eq!(note_name(60, MiddleC::C3), "C3");
+        assert_eq!(note_name(69, MiddleC::C4), "A4");
+        assert_eq!(note_name(61, MiddleC::C3), "C#3");
+    }
+
+    #[test]
+    fn note_name_at_the_midi_range_ends() {
+        assert_eq!(note_name(0, MiddleC::C4), "C-1");
+        assert_eq!(note_name(0, MiddleC::C3), "C-2");
+        assert_eq!(note_name(127, MiddleC::C4), "G9");
+        assert_eq!(note_name(127, MiddleC::C3), "G8");
+    }
+
+    #[test]
+    fn parse_hex_accepts_prefixes_and_whitespace() {
+        assert_eq!(parse_hex(" F0 0x41\t0X10 7 f7 "), Ok(vec![0xF0, 0x41, 0x10, 0x07, 0xF7]));
+        assert_eq!(parse_hex(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn parse_hex_rejects_invalid_bytes() {
+        assert!(parse_hex("100").is_err());
+        assert!(parse_hex("0x").is_err());
+        assert!(parse_hex("+7").is_err());
+        assert!(parse_hex("-1").is_err());
+        assert!(parse_hex("G1").is_err());
+        assert!(parse_hex("41,10").is_err());
+    }
+
+    #[test]
+    fn format_hex_round_trips() {
+        let data = vec![0x00, 0x7F, 0xF0];
+        assert_eq!(format_hex(&data), "00 7F F0");
+        assert_eq!(parse_hex(&format_hex(&data)), Ok(data));
+    }
 }
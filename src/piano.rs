@@ -0,0 +1,182 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders},
+};
+
+use crate::dds_data::Signal;
+use crate::midi_utils::{self, MiddleC, NOTE_KEYS};
+use crate::theme::Theme;
+
+// The whole MIDI range, so every note the device reports back is shown
+const LOWEST: u8 = 0;
+const HIGHEST: u8 = 127;
+// Semitones of an octave that are black keys
+const BLACK_KEYS: [u8; 5] = [1, 3, 6, 8, 10];
+
+// Piano keyboard with the held and received notes
+pub struct Piano {
+    received: [u16; 128], //Channels a note is held on by the device, one bit per channel
+}
+
+impl Piano {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self { received: [0; 128] }
+    }
+    // Track the notes of a message received from the device
+    pub fn receive(&mut self, data: &[u8]) {
+        let channel_bit = 1 << (data[0] & 0x0F);
+        match (data[0] & 0xF0, data.get(1).copied(), data.get(2).copied()) {
+            (0x90, Some(note), Some(velocity)) if velocity > 0 => {
+                self.received[note as usize] |= channel_bit
+            }
+            (0x80 | 0x90, Some(note), _) => self.received[note as usize] &= !channel_bit,
+            // All sound off and all notes off
+            (0xB0, Some(120 | 123), _) => {
+                for channels in self.received.iter_mut() {
+                    *channels &= !channel_bit;
+                }
+            }
+            _ => {}
+        }
+    }
+    // Render the keyboard, the labels of the home row keys start at the base note
+    pub fn render_piano(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        channel_data: &[Signal],
+        base_note: i32,
+        middle_c: MiddleC,
+        theme: &Theme,
+    ) {
+        let block = Block::new()
+            .border_type(BorderType::Thick)
+            .borders(Borders::ALL)
+            .title(Line::from(vec![
+                Span::raw("Piano | "),
+                Span::styled("Held", Style::default().fg(theme.bars)),
+                Span::raw(" | "),
+                Span::styled("Received", Style::default().fg(theme.overlay)),
+                Span::raw(" | "),
+                Span::styled("Both", Style::default().fg(theme.mixed)),
+            ]));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        if inner.height < 2 || inner.width == 0 {
+            return;
+        }
+
+        let whites: Vec<u8> = (LOWEST..=HIGHEST).filter(|n| !is_black(*n)).collect();
+        // Three columns per white key if there is space, otherwise only the octaves around the base note
+        let width = if inner.width as usize >= whites.len() * 3 { 3 } else { 2 };
+        let visible = (inner.width as usize / width).min(whites.len());
+        let base_white = whites.iter().position(|n| *n as i32 >= base_note).unwrap_or(0);
+        let first = base_white
+            .saturating_sub(visible.saturating_sub(8) / 2)
+            .min(whites.len() - visible);
+
+        let held = |note: u8| channel_data.iter().any(|s| s.note == note);
+        // Without colours the white keys are reversed and sounding keys shaded,
+        // darker for held notes, lighter for received ones and solid for both
+        let key = |note: u8| {
+            let (color, shade) = match (held(note), self.received[note as usize] != 0) {
+                (true, true) => (theme.mixed, Some('█')),
+                (true, false) => (theme.bars, Some('▓')),
+                (false, true) => (theme.overlay, Some('░')),
+                (false, false) if is_black(note) => (theme.key_black, None),
+                (false, false) => (theme.key_white, None),
+            };
+            match (theme.monochrome, shade) {
+                (true, Some(shade)) => (shade, Style::default()),
+                (true, None) if is_black(note) => (' ', Style::default()),
+                (true, None) => (' ', theme.fill(color)),
+                (false, _) => (' ', Style::default().bg(color).fg(theme.dim)),
+            }
+        };
+        let label = |note: u8| {
+            NOTE_KEYS
+                .iter()
+                .find(|(_, semitone)| base_note + semitone == note as i32)
+                .map(|(key, _)| *key)
+        };
+
+        let buf = frame.buffer_mut();
+        let bottom = inner.y + inner.height - 1;
+        let keys = &whites[first..first + visible];
+        // White keys over the full height, the bottom row shows the key label or the octave
+        for (i, note) in keys.iter().enumerate() {
+            let x = inner.x + (i * width) as u16;
+            let (symbol, style) = key(*note);
+            for y in inner.y..=bottom {
+                for dx in 0..width as u16 {
+                    buf[(x + dx, y)].set_char(symbol).set_style(style);
+                }
+            }
+            if let Some(label) = label(*note) {
+                buf[(x, bottom)].set_char(label).set_fg(theme.error);
+            } else if note % 12 == 0 {
+                let octave = midi_utils::note_name(*note, middle_c).replacen('C', "", 1);
+                buf.set_string(x, bottom, octave, style);
+            }
+        }
+        // Black keys on the upper rows between their white neighbours, one column narrower
+        for (i, note) in keys.iter().enumerate().take(visible - 1) {
+            let black = note + 1;
+            if !is_black(black) {
+                continue;
+            }
+            let x = inner.x + ((i + 1) * width) as u16 - 1;
+            let (symbol, style) = key(black);
+            for y in inner.y..bottom {
+                for dx in 0..width as u16 - 1 {
+                    buf[(x + dx, y)].set_char(symbol).set_style(style);
+                }
+            }
+        }
+    }
+}
+
+// Whether a note is a black key
+fn is_black(note: u8) -> bool {
+    BLACK_KEYS.contains(&(note % 12))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_on_with_velocity_zero_releases() {
+        let mut piano = Piano::new();
+        piano.receive(&[0x90, 60, 100]);
+        piano.receive(&[0x91, 60, 100]);
+        piano.receive(&[0x9F, 127, 1]);
+        assert_eq!(piano.received[60], 0b11);
+        assert_eq!(piano.received[127], 1 << 15);
+        piano.receive(&[0x90, 60, 0]);
+        assert_eq!(piano.received[60], 0b10);
+        piano.receive(&[0x81, 60, 64]);
+        assert_eq!(piano.received[60], 0);
+    }
+
+    #[test]
+    fn channel_mode_messages_only_clear_their_channel() {
+        let mut piano = Piano::new();
+        for status in [0x90, 0x91, 0x92] {
+            piano.receive(&[status, 60, 100]);
+            piano.receive(&[status, 72, 100]);
+        }
+        // All notes off on channel 1 and all sound off on channel 2
+        piano.receive(&[0xB0, 123, 0]);
+        piano.receive(&[0xB1, 120, 0]);
+        assert_eq!(piano.received[60], 0b100);
+        assert_eq!(piano.received[72], 0b100);
+        // Other controllers keep the notes
+        piano.receive(&[0xB2, 121, 0]);
+        assert_eq!(piano.received[60], 0b100);
+    }
+}
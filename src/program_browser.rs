@@ -0,0 +1,231 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::Text,
+    widgets::{Block, BorderType, Clear, HighlightSpacing, List, ListState, Paragraph},
+};
+
+use crate::AppState;
+use crate::config::DeviceBank;
+use crate::keymap::{self, Keymap};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// General MIDI level 1 program names
+const GM_PROGRAMS: [&str; 128] = [
+    "Acoustic Grand Piano", "Bright Acoustic Piano", "Electric Grand Piano", "Honky-tonk Piano",
+    "Electric Piano 1", "Electric Piano 2", "Harpsichord", "Clavi",
+    "Celesta", "Glockenspiel", "Music Box", "Vibraphone",
+    "Marimba", "Xylophone", "Tubular Bells", "Dulcimer",
+    "Drawbar Organ", "Percussive Organ", "Rock Organ", "Church Organ",
+    "Reed Organ", "Accordion", "Harmonica", "Tango Accordion",
+    "Acoustic Guitar (nylon)", "Acoustic Guitar (steel)", "Electric Guitar (jazz)", "Electric Guitar (clean)",
+    "Electric Guitar (muted)", "Overdriven Guitar", "Distortion Guitar", "Guitar Harmonics",
+    "Acoustic Bass", "Electric Bass (finger)", "Electric Bass (pick)", "Fretless Bass",
+    "Slap Bass 1", "Slap Bass 2", "Synth Bass 1", "Synth Bass 2",
+    "Violin", "Viola", "Cello", "Contrabass",
+    "Tremolo Strings", "Pizzicato Strings", "Orchestral Harp", "Timpani",
+    "String Ensemble 1", "String Ensemble 2", "SynthStrings 1", "SynthStrings 2",
+    "Choir Aahs", "Voice Oohs", "Synth Voice", "Orchestra Hit",
+    "Trumpet", "Trombone", "Tuba", "Muted Trumpet",
+    "French Horn", "Brass Section", "SynthBrass 1", "SynthBrass 2",
+    "Soprano Sax", "Alto Sax", "Tenor Sax", "Baritone Sax",
+    "Oboe", "English Horn", "Bassoon", "Clarinet",
+    "Piccolo", "Flute", "Recorder", "Pan Flute",
+    "Blown Bottle", "Shakuhachi", "Whistle", "Ocarina",
+    "Lead 1 (square)", "Lead 2 (sawtooth)", "Lead 3 (calliope)", "Lead 4 (chiff)",
+    "Lead 5 (charang)", "Lead 6 (voice)", "Lead 7 (fifths)", "Lead 8 (bass + lead)",
+    "Pad 1 (new age)", "Pad 2 (warm)", "Pad 3 (polysynth)", "Pad 4 (choir)",
+    "Pad 5 (bowed)", "Pad 6 (metallic)", "Pad 7 (halo)", "Pad 8 (sweep)",
+    "FX 1 (rain)", "FX 2 (soundtrack)", "FX 3 (crystal)", "FX 4 (atmosphere)",
+    "FX 5 (brightness)", "FX 6 (goblins)", "FX 7 (echoes)", "FX 8 (sci-fi)",
+    "Sitar", "Banjo", "Shamisen", "Koto",
+    "Kalimba", "Bag pipe", "Fiddle", "Shanai",
+    "Tinkle Bell", "Agogo", "Steel Drums", "Woodblock",
+    "Taiko Drum", "Melodic Tom", "Synth Drum", "Reverse Cymbal",
+    "Guitar Fret Noise", "Breath Noise", "Seashore", "Bird Tweet",
+    "Telephone Ring", "Helicopter", "Applause", "Gunshot",
+];
+// General MIDI drum kits (GS numbering), played on channel 10
+const GM_DRUM_KITS: [(u8, &str); 9] = [
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "TR-808 Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+// Channel of the drum kits
+const DRUM_CHANNEL: u8 = 9;
+
+// A list of programs that share a bank
+struct ProgramList {
+    name: String,
+    bank_msb: u8,
+    bank_lsb: u8,
+    drums: bool,                    //Always sent on the drum channel
+    programs: Vec<(u8, String)>,    //Program number and name
+}
+// Main program browser struct
+pub struct ProgramBrowser {
+    lists: Vec<ProgramList>,
+    list_index: usize,   //Selected list
+    list_state: ListState,
+    channel: u8,         //Channel for programs that aren't drum kits
+    last_sent: Option<String>,
+}
+
+impl ProgramBrowser {
+    //Constructor with the General MIDI lists followed by the device lists from the config
+    pub fn new(devices: &[DeviceBank]) -> Self {
+        let mut lists = vec![
+            ProgramList {
+                name: String::from("General MIDI"),
+                bank_msb: 0,
+                bank_lsb: 0,
+                drums: false,
+                programs: GM_PROGRAMS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, n)| (i as u8, n.to_string()))
+                    .collect(),
+            },
+            ProgramList {
+                name: String::from("GM Drum Kits"),
+                bank_msb: 0,
+                bank_lsb: 0,
+                drums: true,
+                programs: GM_DRUM_KITS
+                    .iter()
+                    .map(|(i, n)| (*i, n.to_string()))
+                    .collect(),
+            },
+        ];
+        lists.extend(devices.iter().map(|d| ProgramList {
+            name: d.name.clone(),
+            bank_msb: d.bank_msb.min(127),
+            bank_lsb: d.bank_lsb.min(127),
+            drums: false,
+            programs: d
+                .programs
+                .iter()
+                .take(128)
+                .enumerate()
+                .map(|(i, n)| (i as u8, n.clone()))
+                .collect(),
+        }));
+        Self {
+            lists,
+            list_index: 0,
+            list_state: ListState::default().with_selected(Some(0)),
+            channel: 0,
+            last_sent: None,
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, com_config: &mut ComConfig) -> AppState {
+        let mut app_state: AppState = AppState::Programs;
+        match key.code {
+            // Quit
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Move down
+            KeyCode::Char('j') | KeyCode::Down => self.list_state.select_next(),
+            // Move up
+            KeyCode::Char('k') | KeyCode::Up => self.list_state.select_previous(),
+            // Switch list
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.list_index = (self.list_index + self.lists.len() - 1) % self.lists.len();
+                self.list_state.select(Some(0));
+            }
+            KeyCode::Char('l') | KeyCode::Right => {
+                self.list_index = (self.list_index + 1) % self.lists.len();
+                self.list_state.select(Some(0));
+            }
+            // Channel
+            KeyCode::Char('-') => self.channel = self.channel.saturating_sub(1),
+            KeyCode::Char('+') => self.channel = (self.channel + 1).min(15),
+            // Send the selected program
+            KeyCode::Enter => self.send_program(com_config),
+            _ => {}
+        }
+        app_state
+    }
+    // Send bank select and program change for the selected program
+    fn send_program(&mut self, com_config: &mut ComConfig) {
+        let list = &self.lists[self.list_index];
+        let Some((program, name)) = self
+            .list_state
+            .selected()
+            .and_then(|i| list.programs.get(i))
+        else {
+            return;
+        };
+        let channel = if list.drums { DRUM_CHANNEL } else { self.channel };
+        com_config.send_midi(0xB0 | channel, 0, list.bank_msb);
+        com_config.send_midi(0xB0 | channel, 32, list.bank_lsb);
+        com_config.send_bytes(&[0xC0 | channel, *program]);
+        self.last_sent = Some(format!(
+            "Sent {} {} (Bank {}/{}) on channel {}",
+            program + 1,
+            name,
+            list.bank_msb,
+            list.bank_lsb,
+            channel + 1
+        ));
+    }
+    //Render a popup with the programs of the selected list
+    pub fn show_program_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        let list = &self.lists[self.list_index];
+        let channel = if list.drums { DRUM_CHANNEL } else { self.channel };
+        let programs = List::new(
+            list.programs
+                .iter()
+                .map(|(p, n)| Text::from(format!("{:>3} {}", p + 1, n)))
+                .collect::<Vec<Text>>(),
+        )
+        .block(
+            Block::bordered()
+                .title(format!(
+                    "< {} > Bank {}/{} | Channel {}",
+                    list.name,
+                    list.bank_msb,
+                    list.bank_lsb,
+                    channel + 1
+                ))
+                .border_type(BorderType::Thick),
+        )
+        .style(Style::default())
+        .highlight_style(Style::new().fg(theme.selected).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">> ")
+        .highlight_spacing(HighlightSpacing::WhenSelected)
+        .repeat_highlight_symbol(false);
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 60);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(3)])
+            .split(area);
+        frame.render_stateful_widget(programs, vertical_layout[0], &mut self.list_state);
+        frame.render_widget(
+            Paragraph::new(self.last_sent.clone().unwrap_or_default()).block(
+                Block::bordered()
+                    .title("Last sent")
+                    .border_type(BorderType::Thick),
+            ),
+            vertical_layout[1],
+        );
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        vec![&keymap::PROGRAMS]
+    }
+}
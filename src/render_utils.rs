@@ -1,9 +1,18 @@
 use std::rc::Rc;
 
+use crate::{
+    chart_view::ChartView,
+    dds_data::Signal,
+    layout_utils::{MainLayout, MIN_HEIGHT, MIN_WIDTH},
+    midi_utils::{self, MiddleC},
+    spectrum::SpectrumAnalyzer,
+    theme::Theme,
+    velocity::Velocity,
+};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::Style,
     symbols,
     text::Line,
     widgets::{
@@ -16,8 +25,10 @@ pub fn render_general(
     frame: &mut Frame,
     layout: Rc<[Rect]>,
     serial: Table,
-    current_strength: f64,
+    velocity: &Velocity,
     current_octave: i32,
+    status: &[String],
+    theme: &Theme,
 ) {
     // Create the block surrounding signal info
     frame.render_widget(
@@ -36,38 +47,83 @@ pub fn render_general(
         layout[0],
     );
 
+    // Gauges followed by one line per status
+    let mut constraints = vec![
+        Constraint::Length(3),
+        Constraint::Length(1),
+        Constraint::Length(3),
+    ];
+    constraints.extend(status.iter().map(|_| Constraint::Length(1)));
     let signal_info_layout = Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .horizontal_margin(2)
-        .constraints(vec![Constraint::Length(3), Constraint::Length(3)])
+        .constraints(constraints)
         .split(layout[0]);
 
+    // Level set by the user and the velocity it is sent with
     LineGauge::default()
         .block(
             Block::new()
                 .borders(Borders::NONE)
-                .title(Line::from("Strength (0 <-> 255)").centered()),
+                .title(Line::from("Velocity (0 <-> 127)").centered()),
         )
-        .filled_style(Style::default().fg(Color::Blue).bg(Color::Blue))
-        .unfilled_style(Style::default().fg(Color::Red).bg(Color::Red))
-        .label(format!("{:0>3}", current_strength))
+        .filled_style(theme.filled_style())
+        .unfilled_style(theme.unfilled_style())
+        .label(format!("{:>3} -> {:>3}", velocity.level, velocity.value()))
         .line_set(symbols::line::NORMAL)
-        .ratio(current_strength / 255.)
+        .ratio(velocity.level as f64 / 127.)
         .render(signal_info_layout[0], frame.buffer_mut());
 
+    let random_str = if velocity.random {
+        format!(
+            "{}-{} {}",
+            velocity.random_min,
+            velocity.random_max,
+            velocity.distribution_name()
+        )
+    } else {
+        String::from("off")
+    };
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Curve: {} | Random: {}",
+            velocity.curve_name(),
+            random_str
+        ))
+        .centered(),
+        signal_info_layout[1],
+    );
+
     LineGauge::default()
         .block(
             Block::new()
                 .borders(Borders::NONE)
-                .title(Line::from("Octave (-6 <-> 4)").centered()),
+                .title(
+                    Line::from(format!(
+                        "Octave ({} <-> {})",
+                        midi_utils::LOWEST_OCTAVE,
+                        midi_utils::HIGHEST_OCTAVE
+                    ))
+                    .centered(),
+                ),
         )
-        .filled_style(Style::default().fg(Color::Blue).bg(Color::Blue))
-        .unfilled_style(Style::default().fg(Color::Red).bg(Color::Red))
+        .filled_style(theme.filled_style())
+        .unfilled_style(theme.unfilled_style())
         .label(format!("{:>3}", current_octave))
         .line_set(symbols::line::NORMAL)
-        .ratio((current_octave + 6) as f64 / 10.)
-        .render(signal_info_layout[1], frame.buffer_mut());
+        .ratio(
+            (current_octave - midi_utils::LOWEST_OCTAVE) as f64
+                / (midi_utils::HIGHEST_OCTAVE - midi_utils::LOWEST_OCTAVE) as f64,
+        )
+        .render(signal_info_layout[2], frame.buffer_mut());
+
+    for (i, line) in status.iter().enumerate() {
+        frame.render_widget(
+            Paragraph::new(line.as_str()).centered(),
+            signal_info_layout[i + 3],
+        );
+    }
 
     frame.render_widget(
         Block::new()
@@ -81,17 +137,65 @@ pub fn render_general(
 
     frame.render_widget(serial, layout[1]);
 }
-// Render the dds visualisation
-pub fn render_dds(frame: &mut Frame, layout: Rc<[Rect]>, channel_data: &Vec<(f64, f64)>) {
-    // Create the dataset for the fft graph
-    let dds_dataset = Dataset::default()
-        .marker(symbols::Marker::Braille)
-        .style(Style::new().fg(Color::Blue))
-        .graph_type(GraphType::Bar)
-        .data(channel_data);
+// Render the dds visualisation, the measured spectrum of the analyzer and its peaks are drawn on top
+pub fn render_dds(
+    frame: &mut Frame,
+    area: Rect,
+    channel_data: &[Signal],
+    analyzer: &SpectrumAnalyzer,
+    view: &ChartView,
+    middle_c: MiddleC,
+    theme: &Theme,
+) {
+    let points: Vec<(f64, f64)> = channel_data
+        .iter()
+        .map(|s| (view.x(s.freq), view.velocity_y(s.velocity)))
+        .collect();
+    let to_axis = |(freq, db): &(f64, f64)| (view.x(*freq), view.db_y(*db));
+    // The log axis has no place for 0 Hz
+    let spectrum_points: Vec<(f64, f64)> =
+        analyzer.display.iter().filter(|(freq, _)| *freq > 0.).map(to_axis).collect();
+    let peak_points: Vec<(f64, f64)> = analyzer.peak_points().iter().map(to_axis).collect();
+    // Dotted gridlines
+    let gridlines: Vec<(f64, f64)> = view
+        .gridlines(channel_data)
+        .iter()
+        .flat_map(|x| (0..=10).map(move |i| (*x, i as f64 / 10.)))
+        .collect();
+    // Create the datasets for the fft graph, the gridlines are drawn first
+    let mut datasets = vec![
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .style(Style::new().fg(theme.dim))
+            .graph_type(GraphType::Scatter)
+            .data(&gridlines),
+    ];
+    datasets.push(
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .style(Style::new().fg(theme.overlay))
+            .graph_type(GraphType::Line)
+            .data(&spectrum_points),
+    );
+    datasets.push(
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .style(Style::new().fg(theme.bars))
+            .graph_type(GraphType::Bar)
+            .data(&points),
+    );
+    datasets.push(
+        Dataset::default()
+            .marker(symbols::Marker::Dot)
+            .style(Style::new().fg(theme.error))
+            .graph_type(GraphType::Scatter)
+            .data(&peak_points),
+    );
+    let (x_bounds, x_labels) = view.x_axis(channel_data, middle_c);
+    let (y_title, y_labels) = view.y_axis();
 
     // Create fft widget and the block surrounding it
-    let chart = Chart::new(vec![dds_dataset.clone()])
+    let chart = Chart::new(datasets)
         .block(
             Block::new()
                 .borders(Borders::ALL)
@@ -101,58 +205,118 @@ pub fn render_dds(frame: &mut Frame, layout: Rc<[Rect]>, channel_data: &Vec<(f64
         )
         .x_axis(
             Axis::default()
-                .title("Frequency")
-                .style(Style::default().fg(Color::White))
-                .bounds([0., 6000.])
-                .labels(["0", "3000", "6000"]),
+                .title(if view.log_axis { "Note" } else { "Hz" })
+                .style(Style::default().fg(theme.text))
+                .bounds(x_bounds)
+                .labels(x_labels),
         )
         .y_axis(
             Axis::default()
-                .title("Strength")
-                .style(Style::default().fg(Color::White))
-                .bounds([0., 255.])
-                .labels(["0", "255"]),
+                .title(y_title)
+                .style(Style::default().fg(theme.text))
+                .bounds([0., 1.])
+                .labels(y_labels),
         );
 
-    frame.render_widget(chart, layout[0]);
+    frame.render_widget(chart, area);
+}
+// Render the hint shown instead of the main view in a small terminal
+pub fn render_too_small(frame: &mut Frame, area: Rect, theme: &Theme) {
+    let message = format!(
+        "Terminal too small: {}x{}, at least {}x{} needed",
+        frame.area().width,
+        frame.area().height,
+        MIN_WIDTH,
+        MIN_HEIGHT
+    );
+    frame.render_widget(
+        Paragraph::new(message)
+            .centered()
+            .style(Style::new().fg(theme.error))
+            .block(Block::new().padding(Padding::top(area.height / 2))),
+        area,
+    );
 }
-// Render the current state of the different states
-pub fn render_channels(frame: &mut Frame, layout: Rc<[Rect]>, channel_data: &Vec<(f64, f64)>) {
+// Render the current state of the different states, a highlighted channel gets its own title
+pub fn render_channels(
+    frame: &mut Frame,
+    main_layout: &MainLayout,
+    channel_data: &[Signal],
+    waveforms: &[&str],
+    highlight: Option<(usize, String)>,
+    middle_c: MiddleC,
+    theme: &Theme,
+) {
+    let Some(layout) = &main_layout.channels else {
+        return;
+    };
+    let hidden = main_layout.hidden_channels;
     for i in 0..layout.len() {
-        // Set default values used if a channel is unassigned
-        let mut signal_freq = 0.;
-        let mut signal_strength = 0.;
+        // Empty slots show dashes
+        let row = |name: &'static str, value: String| Row::new(vec![name.to_string(), value]);
+        let mut rows = vec![
+            row("Note", "-".to_string()),
+            row("Freq", "-".to_string()),
+            row("Cents", "-".to_string()),
+            row("Velocity", "-".to_string()),
+            row("Channel", "-".to_string()),
+            row("Held", "-".to_string()),
+        ];
 
         // Check if a channel has valid data in it
-        if i < channel_data.len() {
-            signal_freq = channel_data[i].0;
-            signal_strength = channel_data[i].1;
+        if let Some(signal) = channel_data.iter().find(|s| s.slot == i) {
+            // Deviation of the requested frequency from the equal tempered note it was sent as
+            let cents = 1200. * (signal.freq / midi_utils::note_to_freq(signal.note)).log2();
+            rows = vec![
+                row(
+                    "Note",
+                    format!("{} ({})", midi_utils::note_name(signal.note, middle_c), signal.note),
+                ),
+                row("Freq", format!("{:.1} Hz", signal.freq)),
+                row("Cents", format!("{:+.1} ct", cents)),
+                row("Velocity", format!("{}", signal.velocity)),
+                row("Channel", format!("{}", signal.channel + 1)),
+                row(
+                    "Held",
+                    format!("{:.1} s", signal.started.elapsed().as_secs_f64()),
+                ),
+            ];
         }
-
-        // Convert numerical values to string
-        let freq_str = &format!("{:.1} Hz", signal_freq);
-        let strength_str = &format!("{:.1}", signal_strength);
-
-        // Create data rows
-        let rows = [
-            Row::new(vec!["Freq", freq_str]),
-            Row::new(vec!["Strength", strength_str]),
-        ];
+        let wave = match channel_data.iter().any(|s| s.slot == i) {
+            true => waveforms.get(i).copied().unwrap_or(""),
+            false => "",
+        };
+        rows.push(row("Wave", wave.to_string()));
 
         // Define how wide cells of table are
-        let widths = [Constraint::Percentage(30), Constraint::Percentage(70)];
+        let widths = [Constraint::Length(8), Constraint::Fill(1)];
 
+        let (title, border_color) = match &highlight {
+            Some((index, label)) if *index == i => (label.clone(), theme.highlight),
+            _ => ("Channel ".to_string() + &(i + 1).to_string(), theme.text),
+        };
+        // The last pane tells how many panes were cut off and how many of them play
+        let hint = if hidden > 0 && i == layout.len() - 1 {
+            let playing = channel_data.iter().filter(|s| s.slot >= layout.len()).count();
+            match playing {
+                0 => format!("+{} more channels", hidden),
+                _ => format!("+{} more channels, {} playing", hidden, playing),
+            }
+        } else {
+            String::new()
+        };
         // Create table and the block surrounding it
         let table = Table::new(rows, widths)
             .column_spacing(1)
-            .style(Style::new().white())
+            .style(Style::new().fg(theme.text))
             .block(
                 Block::new()
                     .border_type(BorderType::Thick)
                     .borders(Borders::ALL)
-                    .border_style(Style::default().fg(Color::White))
+                    .border_style(Style::default().fg(border_color))
                     .style(Style::default())
-                    .title("Channel ".to_string() + &(i + 1).to_string())
+                    .title(title)
+                    .title_bottom(Line::from(hint).right_aligned())
                     .padding(Padding {
                         left: 1,
                         right: 1,
@@ -164,17 +328,3 @@ pub fn render_channels(frame: &mut Frame, layout: Rc<[Rect]>, channel_data: &Vec
         frame.render_widget(table.clone(), layout[i]);
     }
 }
-// Render shortcuts
-pub fn render_shortcuts(frame: &mut Frame, layout: Rc<[Rect]>) {
-    let shortcuts = "Quit: q | \
-                    Com Config: p | \
-                    Play tone: Home row | \
-                    --Strength: v | \
-                    ++Strength: V | \
-                    Toggle random Strength: r | \
-                    --Octave: n | \
-                    ++Octave: N | \
-                    Clear notes: c";
-    let paragraph = Paragraph::new(shortcuts).style(Style::new().blue());
-    frame.render_widget(paragraph, layout[1]);
-}
@@ -0,0 +1,176 @@
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Style,
+    symbols,
+    text::Span,
+    widgets::{Axis, Block, BorderType, Borders, Chart, Dataset, GraphType, Padding},
+};
+
+use crate::dds_data::{Signal, VOICES};
+use crate::dds_model::Waveform;
+use crate::theme::Theme;
+
+// Selectable time windows in ms
+const WINDOWS: [f64; 8] = [0.5, 1., 2., 5., 10., 20., 50., 100.];
+// Points calculated over the window
+const POINTS: usize = 1000;
+// Oscilloscope of the summed voices
+pub struct Scope {
+    window: usize,                  //Index of the time window
+    waveforms: [Waveform; VOICES], //Waveform of every DDS slot
+}
+
+impl Scope {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            window: 4,
+            waveforms: [Waveform::Sine; VOICES],
+        }
+    }
+    // Show a shorter or longer time window
+    pub fn zoom(&mut self, zoom_in: bool) {
+        self.window = if zoom_in {
+            self.window.saturating_sub(1)
+        } else {
+            (self.window + 1).min(WINDOWS.len() - 1)
+        };
+    }
+    // Change the waveform of a DDS slot
+    pub fn cycle_waveform(&mut self, slot: usize) {
+        if slot < VOICES {
+            self.waveforms[slot] = self.waveforms[slot].next();
+        }
+    }
+    // Names of the waveforms of every DDS slot
+    pub fn waveform_names(&self) -> Vec<&'static str> {
+        self.waveforms.iter().map(|w| w.name()).collect()
+    }
+    // Sum of the voices over the window, a voice at full velocity has an amplitude of 1
+    fn summed(&self, channel_data: &[Signal]) -> Vec<(f64, f64)> {
+        let window = WINDOWS[self.window];
+        (0..POINTS)
+            .map(|i| {
+                let time = window * i as f64 / (POINTS - 1) as f64;
+                let value = channel_data
+                    .iter()
+                    .map(|signal| {
+                        let phase = (signal.freq * time / 1000.).fract();
+                        let waveform = self.waveforms.get(signal.slot).unwrap_or(&Waveform::Sine);
+                        waveform.sample(phase) * signal.velocity as f64 / 127.
+                    })
+                    .sum();
+                (time, value)
+            })
+            .collect()
+    }
+    // Render the summed waveform with the full scale of the dac marked
+    pub fn render_scope(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        channel_data: &[Signal],
+        theme: &Theme,
+    ) {
+        let window = WINDOWS[self.window];
+        let points = self.summed(channel_data);
+        let peak = points.iter().fold(0., |peak: f64, (_, v)| peak.max(v.abs()));
+        let range = peak.max(1.).ceil();
+        let full_scale = [(0., 1.), (window, 1.)];
+        let negative_full_scale = [(0., -1.), (window, -1.)];
+
+        let clipping = peak > 1.;
+        let title = if clipping {
+            Span::styled(
+                format!("Scope | Peak {:.2} clipping", peak),
+                Style::default().fg(theme.error),
+            )
+        } else {
+            Span::raw(format!("Scope | Peak {:.2}", peak))
+        };
+        let limit_style = Style::new().fg(if clipping { theme.error } else { theme.dim });
+        let chart = Chart::new(vec![
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .style(limit_style)
+                .graph_type(GraphType::Line)
+                .data(&full_scale),
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .style(limit_style)
+                .graph_type(GraphType::Line)
+                .data(&negative_full_scale),
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .style(Style::new().fg(theme.highlight))
+                .graph_type(GraphType::Line)
+                .data(&points),
+        ])
+        .block(
+            Block::new()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Thick)
+                .title(title)
+                .padding(Padding::new(1, 2, 1, 1)),
+        )
+        .x_axis(
+            Axis::default()
+                .title("ms")
+                .style(Style::default().fg(theme.text))
+                .bounds([0., window])
+                .labels(["0".to_string(), format!("{}", window)]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(theme.text))
+                .bounds([-range, range])
+                .labels([format!("-{}", range), "0".to_string(), format!("{}", range)]),
+        );
+
+        frame.render_widget(chart, area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn signal(slot: usize, freq: f64) -> Signal {
+        Signal {
+            key: 0,
+            freq,
+            velocity: 127,
+            note: 69,
+            channel: 0,
+            slot,
+            started: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn waveforms_follow_the_slot() {
+        let mut scope = Scope::new();
+        scope.cycle_waveform(3);
+        // A square wave is at full scale right after the start of the period
+        let points = scope.summed(&[signal(3, 100.)]);
+        assert!((points[1].1 - 1.).abs() < 1e-9);
+        let points = scope.summed(&[signal(2, 100.)]);
+        assert!(points[1].1 < 0.1);
+    }
+
+    #[test]
+    fn sums_every_signal() {
+        let mut scope = Scope::new();
+        for slot in 0..VOICES {
+            scope.cycle_waveform(slot);
+        }
+        // Twelve square waves, two without a slot of their own play a sine
+        let signals: Vec<Signal> = (0..12).map(|slot| signal(slot, 100.)).collect();
+        let points = scope.summed(&signals);
+        let sine = (2. * std::f64::consts::PI * 100. * points[1].0 / 1000.).sin();
+        let expected = VOICES as f64 + 2. * sine;
+        assert!((points[1].1 - expected).abs() < 1e-9);
+    }
+}
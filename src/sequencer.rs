@@ -0,0 +1,498 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table},
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::midi_utils::{self, MiddleC};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::transport::Transport;
+use crate::theme::Theme;
+
+// Number of steps in a pattern
+const STEPS: usize = 16;
+// Length of a step in beats (sixteenth notes)
+const STEP_BEATS: f64 = 0.25;
+// Most tracks a pattern can have
+const MAX_TRACKS: usize = 8;
+
+// A single step of a track
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Step {
+    pub active: bool, //Step plays a note
+    pub velocity: u8, //Velocity of the note
+    pub gate: u8,     //Note length in percent of a step
+}
+// A row of steps playing the same note
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Track {
+    pub note: u8,          //Midi note of the track
+    pub steps: Vec<Step>,  //Steps of the track
+}
+// Pattern as saved to a file
+#[derive(Serialize, Deserialize)]
+pub struct Pattern {
+    pub bpm: f64,           //Tempo the pattern was saved with
+    pub swing: u8,          //Delay of every second step, 50 is straight
+    pub tracks: Vec<Track>, //Tracks of the pattern
+}
+// File actions waiting for a path
+enum FileAction {
+    Save,
+    Load,
+}
+// Main sequencer struct
+pub struct Sequencer {
+    pattern: Pattern,
+    cursor_track: usize,              //Selected track
+    cursor_step: usize,               //Selected step
+    next_step: u64,                   //Next step to play counted from the transport start
+    current_step: Option<usize>,      //Step that played last
+    starts: u64,                      //Transport start the step cursor belongs to
+    pending_offs: Vec<(f64, u8)>,     //Position in beats and note of notes to turn off
+    file_action: Option<FileAction>,  //Pending save or load
+    input: Input,                     //Input for the file path
+}
+
+impl Step {
+    fn new() -> Self {
+        Self {
+            active: false,
+            velocity: 100,
+            gate: 50,
+        }
+    }
+}
+
+impl Track {
+    fn new(note: u8) -> Self {
+        Self {
+            note,
+            steps: vec![Step::new(); STEPS],
+        }
+    }
+}
+
+impl Sequencer {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            pattern: Pattern {
+                bpm: 120.,
+                swing: 50,
+                tracks: [60, 64, 67, 72].into_iter().map(Track::new).collect(),
+            },
+            cursor_track: 0,
+            cursor_step: 0,
+            next_step: 0,
+            current_step: None,
+            starts: 0,
+            pending_offs: Vec::new(),
+            file_action: None,
+            input: Input::new(),
+        }
+    }
+    // Play the steps that are due on the transport
+    pub fn on_tick(&mut self, transport: &Transport, com_config: &mut ComConfig) {
+        if !transport.playing {
+            self.release_all(com_config);
+            self.current_step = None;
+            return;
+        }
+        let position = transport.position();
+        // Start over from step 0 after the transport was started from the beginning
+        if self.starts != transport.starts {
+            self.starts = transport.starts;
+            self.release_all(com_config);
+            self.next_step = 0;
+        }
+        // Resync after the transport jumped
+        let expected = (position / STEP_BEATS).floor() as u64;
+        if self.next_step > expected + 1 || self.next_step + 4 < expected {
+            self.release_all(com_config);
+            self.next_step = expected;
+        }
+        // Turn off notes whose gate has passed
+        let mut pending = Vec::new();
+        for (beat, note) in self.pending_offs.drain(..) {
+            if beat <= position {
+                com_config.send_midi(0x80, note, 0x00);
+            } else {
+                pending.push((beat, note));
+            }
+        }
+        self.pending_offs = pending;
+        // Play every step that started since the last tick
+        while self.step_time(self.next_step) <= position {
+            let start = self.step_time(self.next_step);
+            let index = self.next_step as usize % STEPS;
+            for track in &self.pattern.tracks {
+                let step = &track.steps[index];
+                if step.active {
+                    com_config.send_midi(0x90, track.note, step.velocity);
+                    self.pending_offs
+                        .push((start + STEP_BEATS * step.gate as f64 / 100., track.note));
+                }
+            }
+            self.current_step = Some(index);
+            self.next_step += 1;
+        }
+    }
+    // Position of a step in beats, every second step is delayed by the swing
+    fn step_time(&self, step: u64) -> f64 {
+        let pair = (step / 2) as f64 * STEP_BEATS * 2.;
+        if step % 2 == 1 {
+            pair + STEP_BEATS * 2. * self.pattern.swing as f64 / 100.
+        } else {
+            pair
+        }
+    }
+    // Note messages of the pattern played a number of times, with their time in seconds
+    pub fn events(&self, bpm: f64, loops: u32) -> Vec<(f64, Vec<u8>)> {
+        let beat = 60. / bpm;
+        let mut events = Vec::new();
+        for step in 0..STEPS as u64 * loops as u64 {
+            let start = self.step_time(step);
+            let index = step as usize % STEPS;
+            for track in &self.pattern.tracks {
+                let s = &track.steps[index];
+                if s.active {
+                    let end = start + STEP_BEATS * s.gate as f64 / 100.;
+                    events.push((start * beat, vec![0x90, track.note, s.velocity]));
+                    events.push((end * beat, vec![0x80, track.note, 0x00]));
+                }
+            }
+        }
+        // Stable, a note off stays in front of a note on at the same time
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+        events
+    }
+    // Turn off every note that is still sounding
+    fn release_all(&mut self, com_config: &mut ComConfig) {
+        for (_, note) in self.pending_offs.drain(..) {
+            com_config.send_midi(0x80, note, 0x00);
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, transport: &mut Transport) -> AppState {
+        let mut app_state: AppState = AppState::Sequencer;
+        // Path input for saving and loading
+        if let Some(action) = &self.file_action {
+            match key.code {
+                KeyCode::Enter => {
+                    let path = self.input.submit_message();
+                    let result = match action {
+                        FileAction::Save => self.save(path.trim(), transport),
+                        FileAction::Load => self.load(path.trim(), transport),
+                    };
+                    match result {
+                        Ok(()) => self.file_action = None,
+                        Err(e) => self.input.display_error(e),
+                    }
+                }
+                _ => {
+                    if self.input.key_event(key) {
+                        self.file_action = None;
+                    }
+                }
+            }
+            return app_state;
+        }
+        let tracks = self.pattern.tracks.len();
+        match key.code {
+            // Quit, the pattern keeps playing
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Move the cursor
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.cursor_step = (self.cursor_step + STEPS - 1) % STEPS;
+            }
+            KeyCode::Char('l') | KeyCode::Right => {
+                self.cursor_step = (self.cursor_step + 1) % STEPS;
+            }
+            KeyCode::Char('k') | KeyCode::Up if tracks > 0 => {
+                self.cursor_track = (self.cursor_track + tracks - 1) % tracks;
+            }
+            KeyCode::Char('j') | KeyCode::Down if tracks > 0 => {
+                self.cursor_track = (self.cursor_track + 1) % tracks;
+            }
+            // Toggle the selected step
+            KeyCode::Enter => {
+                if let Some(step) = self.selected_step() {
+                    step.active = !step.active;
+                }
+            }
+            // Play or stop the transport
+            KeyCode::Char(' ') => {
+                if transport.playing {
+                    transport.stop();
+                } else {
+                    transport.start();
+                }
+            }
+            // Step velocity
+            KeyCode::Char('v') => {
+                if let Some(step) = self.selected_step() {
+                    step.velocity = step.velocity.saturating_sub(5).max(1);
+                }
+            }
+            KeyCode::Char('V') => {
+                if let Some(step) = self.selected_step() {
+                    step.velocity = (step.velocity + 5).min(127);
+                }
+            }
+            // Step gate
+            KeyCode::Char('g') => {
+                if let Some(step) = self.selected_step() {
+                    step.gate = (step.gate - 10).max(10);
+                }
+            }
+            KeyCode::Char('G') => {
+                if let Some(step) = self.selected_step() {
+                    step.gate = (step.gate + 10).min(100);
+                }
+            }
+            // Track note
+            KeyCode::Char('n') => {
+                if let Some(track) = self.pattern.tracks.get_mut(self.cursor_track) {
+                    track.note = track.note.saturating_sub(1);
+                }
+            }
+            KeyCode::Char('N') => {
+                if let Some(track) = self.pattern.tracks.get_mut(self.cursor_track) {
+                    track.note = (track.note + 1).min(127);
+                }
+            }
+            // Tempo and swing
+            KeyCode::Char('-') => transport.set_bpm(transport.bpm - 1.),
+            KeyCode::Char('+') => transport.set_bpm(transport.bpm + 1.),
+            KeyCode::Char('[') => self.pattern.swing = (self.pattern.swing - 1).max(50),
+            KeyCode::Char(']') => self.pattern.swing = (self.pattern.swing + 1).min(75),
+            // Add or delete a track
+            KeyCode::Char('a') if tracks < MAX_TRACKS => {
+                let note = self.pattern.tracks.last().map_or(60, |t| (t.note + 1).min(127));
+                self.pattern.tracks.push(Track::new(note));
+            }
+            KeyCode::Char('d') if tracks > 0 => {
+                self.pattern.tracks.remove(self.cursor_track);
+                self.cursor_track = self.cursor_track.min(tracks.saturating_sub(2));
+            }
+            // Save or load a pattern
+            KeyCode::Char('s') => self.file_action = Some(FileAction::Save),
+            KeyCode::Char('o') => self.file_action = Some(FileAction::Load),
+            _ => {}
+        }
+        app_state
+    }
+    // Step under the cursor
+    fn selected_step(&mut self) -> Option<&mut Step> {
+        self.pattern
+            .tracks
+            .get_mut(self.cursor_track)
+            .map(|t| &mut t.steps[self.cursor_step])
+    }
+    // Write the pattern to a toml file
+    fn save(&mut self, path: &str, transport: &Transport) -> Result<(), String> {
+        self.pattern.bpm = transport.bpm;
+        let content = toml::to_string(&self.pattern).map_err(|e| e.to_string())?;
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+    // Read a pattern from a toml file
+    fn load(&mut self, path: &str, transport: &mut Transport) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut pattern: Pattern = toml::from_str(&content).map_err(|e| e.to_string())?;
+        if !pattern.bpm.is_finite() {
+            return Err(format!("Not a valid tempo: {}", pattern.bpm));
+        }
+        // Bring hand edited files back into range
+        pattern.tracks.truncate(MAX_TRACKS);
+        pattern.swing = pattern.swing.clamp(50, 75);
+        for track in &mut pattern.tracks {
+            track.note = track.note.min(127);
+            track.steps.resize(STEPS, Step::new());
+            for step in &mut track.steps {
+                step.velocity = step.velocity.clamp(1, 127);
+                step.gate = step.gate.clamp(10, 100);
+            }
+        }
+        transport.set_bpm(pattern.bpm);
+        self.pattern = pattern;
+        self.cursor_track = 0;
+        Ok(())
+    }
+    //Render the sequencer screen
+    pub fn show_sequencer_popup(
+        &mut self,
+        frame: &mut Frame,
+        transport: &Transport,
+        middle_c: MiddleC,
+        theme: &Theme,
+    ) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 90, 80);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        // Header with the step numbers
+        let header = Row::new(
+            std::iter::once(Cell::from("Note"))
+                .chain((1..=STEPS).map(|i| Cell::from(format!("{:>2}", i)))),
+        )
+        .style(Style::default().add_modifier(Modifier::BOLD));
+        // One row per track, the cursor is reversed and the playing step is yellow
+        let rows = self.pattern.tracks.iter().enumerate().map(|(t, track)| {
+            let cells = track.steps.iter().enumerate().map(|(s, step)| {
+                let mut style = if self.current_step == Some(s) {
+                    Style::default().fg(theme.highlight)
+                } else {
+                    Style::default()
+                };
+                if t == self.cursor_track && s == self.cursor_step {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Cell::from(if step.active { " ■" } else { " ·" }).style(style)
+            });
+            Row::new(
+                std::iter::once(Cell::from(midi_utils::note_name(track.note, middle_c))).chain(cells),
+            )
+        });
+        let widths = std::iter::once(Constraint::Length(5))
+            .chain(std::iter::repeat_n(Constraint::Length(2), STEPS));
+        let table = Table::new(rows, widths)
+            .header(header)
+            .column_spacing(1)
+            .block(
+                Block::new()
+                    .border_type(BorderType::Thick)
+                    .borders(Borders::ALL)
+                    .title("Sequencer"),
+            );
+        frame.render_widget(table, vertical_layout[0]);
+
+        // Details of the selected step and the transport
+        let step_str = match self.pattern.tracks.get(self.cursor_track) {
+            Some(track) => {
+                let step = &track.steps[self.cursor_step];
+                format!(
+                    "Step {} | Velocity {} | Gate {}%",
+                    self.cursor_step + 1,
+                    step.velocity,
+                    step.gate
+                )
+            }
+            None => String::from("No tracks"),
+        };
+        let transport_str = format!(
+            "BPM {:.0} | Swing {}% | {}",
+            transport.bpm,
+            self.pattern.swing,
+            if transport.playing { "Playing" } else { "Stopped" }
+        );
+        frame.render_widget(
+            Paragraph::new(step_str + " || " + &transport_str).block(
+                Block::bordered()
+                    .title("Info")
+                    .border_type(BorderType::Thick),
+            ),
+            vertical_layout[1],
+        );
+
+        // Path input for saving and loading
+        let title = match self.file_action {
+            Some(FileAction::Save) => "Save pattern (.toml)",
+            Some(FileAction::Load) => "Load pattern (.toml)",
+            None => "Pattern file",
+        };
+        frame.render_widget(
+            self.input.get_input(String::from(title), theme),
+            vertical_layout[2],
+        );
+        if self.file_action.is_some() {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[2].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[2].y + vertical_layout[2].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.file_action.is_some() {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::SEQUENCER]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Note ons sent by one tick
+    fn tick(sequencer: &mut Sequencer, transport: &Transport) -> Vec<Vec<u8>> {
+        ComConfig::new()
+            .record(|com| sequencer.on_tick(transport, com))
+            .into_iter()
+            .filter(|data| data[0] == 0x90)
+            .collect()
+    }
+
+    #[test]
+    fn restart_plays_step_zero() {
+        let mut sequencer = Sequencer::new();
+        sequencer.pattern.tracks[0].steps[0].active = true;
+        let mut transport = Transport::new();
+        transport.start();
+        assert_eq!(tick(&mut sequencer, &transport), vec![vec![0x90, 60, 100]]);
+        assert_eq!(sequencer.current_step, Some(0));
+        // Restart right after step 0 played, with and without stopping
+        transport.start();
+        assert_eq!(tick(&mut sequencer, &transport), vec![vec![0x90, 60, 100]]);
+        transport.stop();
+        tick(&mut sequencer, &transport);
+        transport.start();
+        assert_eq!(tick(&mut sequencer, &transport), vec![vec![0x90, 60, 100]]);
+    }
+
+    #[test]
+    fn swing_delays_odd_steps() {
+        let mut sequencer = Sequencer::new();
+        assert_eq!(sequencer.step_time(1), STEP_BEATS);
+        sequencer.pattern.swing = 75;
+        assert_eq!(sequencer.step_time(1), STEP_BEATS * 1.5);
+        assert_eq!(sequencer.step_time(2), STEP_BEATS * 2.);
+    }
+
+    #[test]
+    fn load_rejects_invalid_tempo() {
+        let mut sequencer = Sequencer::new();
+        let mut transport = Transport::new();
+        let path = std::env::temp_dir().join(format!("tunein-pattern-{}.toml", std::process::id()));
+        let path = path.to_str().unwrap();
+        sequencer.save(path, &transport).unwrap();
+        let content = fs::read_to_string(path).unwrap().replace("bpm = 120.0", "bpm = nan");
+        fs::write(path, content).unwrap();
+        assert_eq!(sequencer.load(path, &mut transport), Err(String::from("Not a valid tempo: NaN")));
+        fs::remove_file(path).unwrap();
+        assert_eq!(transport.bpm, 120.);
+    }
+}
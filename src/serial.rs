@@ -1,17 +1,22 @@
 use crossterm::event::{KeyCode, KeyEvent};
 use ratatui::{
     Frame,
-    layout::{Constraint, Direction, Layout, Position, Rect},
-    style::{Color, Modifier, Style, Stylize},
-    text::Text,
-    widgets::{Block, BorderType, Borders, Clear, List, ListState, Paragraph, Row, Table},
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Borders, Clear, List, ListState, Row, Table},
 };
 use serialport::{self, SerialPort};
-use std::{rc::Rc, time::Duration};
+use std::time::{Duration, Instant};
 
 use crate::AppState;
 use crate::input::Input;
+use crate::keymap::{self, Keymap};
 use crate::popup_utils::popup_area;
+use crate::theme::Theme;
+
+// Messages a recording keeps, a stress run would otherwise fill the memory
+const MAX_RECORDED: usize = 1 << 20;
 
 // Different states
 #[derive(Debug, PartialEq, Eq)]
@@ -28,6 +33,9 @@ pub struct ComConfig {
     active_com_port: Option<Box<dyn SerialPort>>, //Connected com port
     baud: u32,                                    //Desired baud
     input: Input,                                 //Input for text input
+    recording_start: Option<Instant>,             //Start of the recording of sent bytes
+    recorded: Vec<(f64, Vec<u8>)>,                //Recorded bytes with their time in seconds
+    send_error: Option<String>,                   //Last failed write, cleared by the next successful one
 }
 
 impl ComConfig {
@@ -41,6 +49,9 @@ impl ComConfig {
             active_com_port: None,
             baud: 0,
             input: Input::new(),
+            recording_start: None,
+            recorded: Vec::new(),
+            send_error: None,
         }
     }
 
@@ -50,13 +61,82 @@ impl ComConfig {
     pub fn scan_serialports(&mut self) {
         self.com_ports = serialport::available_ports().expect("Error reading Com ports");
     }
+    // Open a port, replaces the connected one
+    pub fn connect(&mut self, port_name: &str, baud: u32) -> Result<(), String> {
+        let port = serialport::new(port_name, baud)
+            .timeout(Duration::from_millis(10))
+            .open()
+            .map_err(|e| format!("Failed to open {}: {}", port_name, e))?;
+        self.baud = baud;
+        self.active_com_port = Some(port);
+        self.send_error = None;
+        Ok(())
+    }
     // Send a midi message
     pub fn send_midi(&mut self, status: u8, note: u8, vel: u8) {
-        self.active_com_port
-            .as_mut()
-            .unwrap()
-            .write(&[status, note, vel])
-            .expect("Send failed");
+        self.send_bytes(&[status, note, vel]);
+    }
+    // Send raw bytes, nothing is sent while no port is connected and errors are shown in the serial table
+    pub fn send_bytes(&mut self, data: &[u8]) {
+        let result = self.try_send_bytes(data);
+        self.report_send(result);
+    }
+    // Show the outcome of a fallible send in the serial table
+    pub fn report_send(&mut self, result: std::io::Result<()>) {
+        match result {
+            Ok(()) => self.send_error = None,
+            Err(e) => self.send_error = Some(format!("Send failed: {}", e)),
+        }
+    }
+    // Send raw bytes and return write errors like timeouts instead of panicking
+    pub fn try_send_bytes(&mut self, data: &[u8]) -> std::io::Result<()> {
+        // Record everything until the recording is full, even without a connected port
+        if let Some(start) = self.recording_start.filter(|_| !self.recording_full()) {
+            self.recorded
+                .push((start.elapsed().as_secs_f64(), data.to_vec()));
+        }
+        match self.active_com_port.as_mut() {
+            Some(port) => port.write_all(data),
+            None => Ok(()),
+        }
+    }
+    // Start recording all sent bytes
+    pub fn start_recording(&mut self) {
+        self.recording_start = Some(Instant::now());
+        self.recorded.clear();
+    }
+    // Stop recording and return the sent bytes with their time in seconds
+    pub fn stop_recording(&mut self) -> Vec<(f64, Vec<u8>)> {
+        self.recording_start = None;
+        std::mem::take(&mut self.recorded)
+    }
+    // Whether sent bytes are recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording_start.is_some()
+    }
+    // Whether the recording reached its limit and drops further messages
+    pub fn recording_full(&self) -> bool {
+        self.recorded.len() >= MAX_RECORDED
+    }
+    // Whether a port is connected
+    pub fn is_connected(&self) -> bool {
+        self.active_com_port.is_some()
+    }
+    // Read all bytes received so far without blocking, with the time they were read
+    pub fn read_bytes(&mut self) -> (Instant, Vec<u8>) {
+        let Some(port) = self.active_com_port.as_mut() else {
+            return (Instant::now(), Vec::new());
+        };
+        let available = port.bytes_to_read().unwrap_or(0) as usize;
+        if available == 0 {
+            return (Instant::now(), Vec::new());
+        }
+        let mut data = vec![0; available];
+        match port.read(&mut data) {
+            Ok(n) => data.truncate(n),
+            Err(_) => data.clear(),
+        }
+        (Instant::now(), data)
     }
     // Event handling
     pub fn key_event(&mut self, key: KeyEvent) -> AppState {
@@ -68,13 +148,12 @@ impl ComConfig {
                 // Toggle stat
                 KeyCode::Tab => self.toggle_state(),
                 // Select entry
-                KeyCode::Enter => match self.list_state.selected() {
-                    Some(i) => {
+                KeyCode::Enter => {
+                    if let Some(i) = self.list_state.selected() {
                         self.port_index = i;
                         self.config_state = ConfigState::BaudSelection;
                     }
-                    None => {}
-                },
+                }
                 // Move down
                 KeyCode::Char('j') | KeyCode::Down => self.select_next(),
                 // Move up
@@ -90,20 +169,16 @@ impl ComConfig {
                 KeyCode::Enter => match self.input.submit_message().parse() {
                     // Check if input is valid
                     Ok(b) => {
-                        self.baud = b;
                         // Connect to the port
-                        self.active_com_port = Some(
-                            serialport::new(
-                                self.com_ports[self.port_index].port_name.clone(),
-                                self.baud,
-                            )
-                            .timeout(Duration::from_millis(10))
-                            .open()
-                            .expect("Failed to open port"),
-                        );
-                        // Change state
-                        self.config_state = ConfigState::BaudSelection;
-                        app_state = AppState::Running;
+                        let port_name = self.com_ports[self.port_index].port_name.clone();
+                        match self.connect(&port_name, b) {
+                            Ok(()) => {
+                                // Change state
+                                self.config_state = ConfigState::BaudSelection;
+                                app_state = AppState::Running;
+                            }
+                            Err(e) => self.input.display_error(e),
+                        }
                     }
                     Err(_) => self.input.display_error(String::from("Not a valid value")),
                 },
@@ -114,7 +189,7 @@ impl ComConfig {
                 }
             },
         };
-        return app_state;
+        app_state
     }
     // Select next entry
     fn select_next(&mut self) {
@@ -131,21 +206,43 @@ impl ComConfig {
             ConfigState::BaudSelection => self.config_state = ConfigState::PortSelection,
         }
     }
+    // One line with the connection for the collapsed info column
+    pub fn get_status_line(&self, theme: &Theme) -> Line<'_> {
+        let connection = match &self.active_com_port {
+            Some(p) => Span::styled(
+                format!(
+                    "{} @ {}",
+                    p.name().unwrap_or_default(),
+                    p.baud_rate().unwrap_or(self.baud)
+                ),
+                Style::new().fg(theme.ok),
+            ),
+            None => Span::styled("Not connected", Style::new().fg(theme.error)),
+        };
+        let mut spans = vec![Span::raw("Serial: "), connection];
+        if let Some(e) = &self.send_error {
+            spans.push(Span::styled(format!(" | {}", e), Style::new().fg(theme.error)));
+        }
+        Line::from(spans)
+    }
     // Get table for rendering
-    pub fn get_table(&self) -> Table<'_> {
+    pub fn get_table(&self, theme: &Theme) -> Table<'_> {
         // Create data rows
-        let rows = if let Some(p) = &self.active_com_port {
+        let mut rows = if let Some(p) = &self.active_com_port {
             if let Ok(r) = p.baud_rate() {
-                [
-                    Row::new(vec![String::from("Name"), p.name().unwrap().clone()]).green(),
-                    Row::new(vec!["Baud".to_string(), r.clone().to_string()]).green(),
+                vec![
+                    Row::new(vec![String::from("Name"), p.name().unwrap().clone()]).fg(theme.ok),
+                    Row::new(vec!["Baud".to_string(), r.clone().to_string()]).fg(theme.ok),
                 ]
             } else {
-                get_rows_nc()
+                get_rows_nc(theme)
             }
         } else {
-            get_rows_nc()
+            get_rows_nc(theme)
         };
+        if let Some(e) = &self.send_error {
+            rows.push(Row::new(vec!["Error".to_string(), e.clone()]).fg(theme.error));
+        }
 
         // Define how wide cells of table are
         let widths = [Constraint::Percentage(30), Constraint::Fill(1)];
@@ -163,7 +260,7 @@ impl ComConfig {
     }
 
     //Render a popup form Com settings
-    pub fn show_com_popup(&mut self, frame: &mut Frame) {
+    pub fn show_com_popup(&mut self, frame: &mut Frame, theme: &Theme) {
         let list = List::new(
             self.com_ports
                 .iter()
@@ -176,7 +273,7 @@ impl ComConfig {
                 .border_type(BorderType::Thick),
         )
         .style(Style::default())
-        .highlight_style(Style::new().fg(Color::Green).add_modifier(Modifier::BOLD))
+        .highlight_style(Style::new().fg(theme.selected).add_modifier(Modifier::BOLD))
         .highlight_symbol(">> ")
         .highlight_spacing(ratatui::widgets::HighlightSpacing::WhenSelected)
         .repeat_highlight_symbol(false);
@@ -193,13 +290,13 @@ impl ComConfig {
         match self.config_state {
             ConfigState::PortSelection => {
                 frame.render_stateful_widget(
-                    &list.yellow(),
+                    &list.fg(theme.highlight),
                     vertical_layout[0],
                     &mut self.list_state,
                 );
                 frame.render_widget(
                     self.input
-                        .get_input(String::from("Baud"))
+                        .get_input(String::from("Baud"), theme)
                         .style(Style::default()),
                     vertical_layout[1],
                 );
@@ -207,7 +304,7 @@ impl ComConfig {
             ConfigState::BaudSelection => {
                 frame.render_stateful_widget(&list, vertical_layout[0], &mut self.list_state);
                 frame.render_widget(
-                    self.input.get_input(String::from("Baud")),
+                    self.input.get_input(String::from("Baud"), theme),
                     vertical_layout[1],
                 );
                 // Turn cursor on
@@ -221,26 +318,29 @@ impl ComConfig {
             }
         }
     }
-    // Render shortcuts
-    pub fn render_shortcuts(&self, frame: &mut Frame, layout: Rc<[Rect]>) {
-        let shortcuts = if self.config_state == ConfigState::PortSelection {
-            "Quit Config: q | \
-                    Switch to Baud entry: tab | \
-                    Submit: Enter | \
-                    Next Entry: Down/j | \
-                    Prev Entry: Up/k | \
-                    Rescan serialports: r"
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.config_state == ConfigState::PortSelection {
+            vec![&keymap::COM_CONFIG]
         } else {
-            self.input.get_shortcuts()
-        };
-        let paragraph = Paragraph::new(shortcuts).style(Style::new().blue());
-        frame.render_widget(paragraph, layout[1]);
+            vec![&keymap::FIELDS, self.input.keymap()]
+        }
     }
 }
 
-fn get_rows_nc() -> [Row<'static>; 2] {
-    [
-        Row::new(vec![String::from("Name"), "Not connected".to_string()]).red(),
-        Row::new(vec!["Baud".to_string(), "Not connected".to_string()]).red(),
+fn get_rows_nc(theme: &Theme) -> Vec<Row<'static>> {
+    vec![
+        Row::new(vec![String::from("Name"), "Not connected".to_string()]).fg(theme.error),
+        Row::new(vec!["Baud".to_string(), "Not connected".to_string()]).fg(theme.error),
     ]
 }
+
+#[cfg(test)]
+impl ComConfig {
+    // Bytes sent while running f, for the tests of the senders
+    pub fn record(&mut self, f: impl FnOnce(&mut Self)) -> Vec<Vec<u8>> {
+        self.start_recording();
+        f(self);
+        self.stop_recording().into_iter().map(|(_, data)| data).collect()
+    }
+}
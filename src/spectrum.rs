@@ -0,0 +1,573 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use hound::{SampleFormat, WavReader};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, BorderType, Clear, Paragraph, Row, Table},
+};
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::{
+    f64::consts::PI,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::AppState;
+use crate::dds_data::Signal;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::midi_utils::{self, MiddleC};
+use crate::popup_utils::popup_area;
+use crate::theme::Theme;
+
+// Selectable FFT sizes
+const FFT_SIZES: [usize; 6] = [2048, 4096, 8192, 16384, 32768, 65536];
+// Lowest level shown and considered for peaks in dBFS
+pub const FLOOR_DB: f64 = -100.;
+// Peaks have to be within this range of the strongest peak in dB
+const PEAK_RANGE: f64 = 60.;
+// Lowest frequency considered for peaks
+const MIN_PEAK_FREQ: f64 = 20.;
+// Most peaks listed
+const MAX_PEAKS: usize = 10;
+// Most points of the chart overlay
+const DISPLAY_POINTS: usize = 2000;
+// Step of the analysis position in seconds
+const POSITION_STEP: f64 = 0.1;
+
+// Window applied before the FFT
+#[derive(Clone, Copy)]
+enum WindowFunction {
+    BlackmanHarris, //Low side lobes, wide main lobe
+    Hann,           //General purpose
+    Rectangular,    //No window, only for periodic signals
+}
+// A detected peak
+#[derive(Debug, PartialEq)]
+struct Peak {
+    freq: f64, //Interpolated frequency
+    db: f64,   //Level in dBFS
+}
+// Chart overlay and peaks of an analysis
+type Spectrum = (Vec<(f64, f64)>, Vec<Peak>);
+// Samples and settings of an analysis, moved to the worker thread
+struct Analysis {
+    samples: Arc<Vec<f32>>,
+    sample_rate: u32,
+    size: usize,            //FFT size
+    window: WindowFunction,
+    averaged: bool,         //Average over the whole file instead of a single window
+    position: f64,          //Start of the single window in seconds
+}
+// Main spectrum analyzer struct
+pub struct SpectrumAnalyzer {
+    samples: Arc<Vec<f32>>,         //Mono samples of the loaded file, shared with the analysis
+    sample_rate: u32,
+    file: Option<String>,           //Name of the loaded file
+    fft_size_index: usize,          //Selected FFT size
+    window: WindowFunction,
+    averaged: bool,                 //Average over the whole file instead of a single window
+    position: f64,                  //Start of the single window in seconds
+    pub display: Vec<(f64, f64)>,   //Frequency and level in dBFS of the chart overlay
+    peaks: Vec<Peak>,
+    loading: bool,                  //Path input is open
+    input: Input,                   //Input for the wav path
+    job: Option<JoinHandle<Spectrum>>, //Running analysis
+    outdated: bool,                 //Settings changed since the running analysis started
+}
+
+impl SpectrumAnalyzer {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            samples: Arc::new(Vec::new()),
+            sample_rate: 0,
+            file: None,
+            fft_size_index: 3,
+            window: WindowFunction::BlackmanHarris,
+            averaged: true,
+            position: 0.,
+            display: Vec::new(),
+            peaks: Vec::new(),
+            loading: true,
+            input: Input::new(),
+            job: None,
+            outdated: false,
+        }
+    }
+    // Frequencies of the detected peaks for the chart
+    pub fn peak_points(&self) -> Vec<(f64, f64)> {
+        self.peaks.iter().map(|p| (p.freq, p.db)).collect()
+    }
+    // Load a wav file and analyze it
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let (samples, sample_rate) = load_wav(path)?;
+        if samples.is_empty() {
+            return Err(String::from("File has no samples"));
+        }
+        self.samples = Arc::new(samples);
+        self.sample_rate = sample_rate;
+        self.file = Some(path.to_string());
+        self.position = 0.;
+        self.analyze();
+        Ok(())
+    }
+    // Remove the loaded file and the overlay
+    fn clear(&mut self) {
+        self.samples = Arc::new(Vec::new());
+        self.outdated = false;
+        self.file = None;
+        self.display.clear();
+        self.peaks.clear();
+    }
+    // Length of the loaded file in seconds
+    fn length(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate.max(1) as f64
+    }
+    // Analyze again with the current settings, a running analysis is redone once it finished
+    fn analyze(&mut self) {
+        if self.samples.is_empty() {
+            return;
+        }
+        self.outdated = true;
+        self.start_analysis();
+    }
+    // Compute the spectrum on a worker thread, large FFTs over long files would block the ui
+    fn start_analysis(&mut self) {
+        if self.job.is_some() || !self.outdated {
+            return;
+        }
+        self.outdated = false;
+        let settings = Analysis {
+            samples: self.samples.clone(),
+            sample_rate: self.sample_rate,
+            size: FFT_SIZES[self.fft_size_index],
+            window: self.window,
+            averaged: self.averaged,
+            position: self.position,
+        };
+        self.job = Some(thread::spawn(move || settings.run()));
+    }
+    // Take the result of a finished analysis and start the next one if the settings changed
+    pub fn on_tick(&mut self) {
+        if !self.job.as_ref().is_some_and(|job| job.is_finished()) {
+            return;
+        }
+        if let Some(Ok((display, peaks))) = self.job.take().map(|job| job.join()) {
+            // A file cleared while analyzing gets no overlay
+            if !self.samples.is_empty() {
+                self.display = display;
+                self.peaks = peaks;
+            }
+        }
+        self.start_analysis();
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent) -> AppState {
+        let mut app_state: AppState = AppState::Analysis;
+        // Path input for loading
+        if self.loading {
+            match key.code {
+                KeyCode::Enter => {
+                    let path = self.input.submit_message();
+                    match self.load(path.trim()) {
+                        Ok(()) => self.loading = false,
+                        Err(e) => self.input.display_error(e),
+                    }
+                }
+                _ => {
+                    if self.input.key_event(key) {
+                        self.loading = false;
+                    }
+                }
+            }
+            return app_state;
+        }
+        match key.code {
+            // Quit, the overlay stays on the chart
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Load a file
+            KeyCode::Char('o') => self.loading = true,
+            // Remove the file
+            KeyCode::Char('c') => self.clear(),
+            // FFT size
+            KeyCode::Char('f') => {
+                self.fft_size_index = self.fft_size_index.saturating_sub(1);
+                self.analyze();
+            }
+            KeyCode::Char('F') => {
+                self.fft_size_index = (self.fft_size_index + 1).min(FFT_SIZES.len() - 1);
+                self.analyze();
+            }
+            // Window function
+            KeyCode::Char('w') => {
+                self.window = match self.window {
+                    WindowFunction::BlackmanHarris => WindowFunction::Hann,
+                    WindowFunction::Hann => WindowFunction::Rectangular,
+                    WindowFunction::Rectangular => WindowFunction::BlackmanHarris,
+                };
+                self.analyze();
+            }
+            // Average or single window
+            KeyCode::Char('a') => {
+                self.averaged = !self.averaged;
+                self.analyze();
+            }
+            // Position of the single window
+            KeyCode::Char('h') | KeyCode::Left if !self.averaged => {
+                self.position = (self.position - POSITION_STEP).max(0.);
+                self.analyze();
+            }
+            KeyCode::Char('l') | KeyCode::Right if !self.averaged => {
+                self.position = (self.position + POSITION_STEP).min(self.length());
+                self.analyze();
+            }
+            _ => {}
+        }
+        app_state
+    }
+    //Render the analysis popup with the peaks compared to the expected notes
+    pub fn show_analysis_popup(
+        &mut self,
+        frame: &mut Frame,
+        expected: &[Signal],
+        middle_c: MiddleC,
+        theme: &Theme,
+    ) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 70, 70);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(6),
+                Constraint::Fill(1),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        let window = match self.window {
+            WindowFunction::BlackmanHarris => "Blackman-Harris",
+            WindowFunction::Hann => "Hann",
+            WindowFunction::Rectangular => "Rectangular",
+        };
+        let size = FFT_SIZES[self.fft_size_index];
+        let range = if self.averaged {
+            String::from("Average of the whole file")
+        } else {
+            format!(
+                "Window at {:.1} s of {:.1} s",
+                self.position,
+                self.length()
+            )
+        };
+        let range = match self.job {
+            Some(_) => range + " | Analyzing",
+            None => range,
+        };
+        let text = Text::from(vec![
+            Line::from(format!(
+                " File: {}",
+                self.file.clone().unwrap_or(String::from("None"))
+            )),
+            Line::from(format!(
+                " Sample rate: {} Hz | Length: {:.2} s",
+                self.sample_rate,
+                self.length()
+            )),
+            Line::from(format!(
+                " FFT size: {} ({:.2} Hz per bin) | Window: {} | {}",
+                size,
+                self.sample_rate as f64 / size as f64,
+                window,
+                range
+            )),
+            Line::from(if expected.is_empty() {
+                " No notes held, errors are against the nearest equal tempered note"
+            } else {
+                " Errors are against the nearest held note"
+            }),
+        ]);
+        frame.render_widget(
+            Paragraph::new(text).block(
+                Block::bordered()
+                    .title("WAV Analysis")
+                    .border_type(BorderType::Thick),
+            ),
+            vertical_layout[0],
+        );
+
+        // Peaks compared to the nearest expected tone
+        let header = Row::new(vec!["Peak", "Level", "Note", "Expected", "Error"])
+            .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows = self.peaks.iter().map(|peak| {
+            let target = expected
+                .iter()
+                .map(|s| s.freq)
+                .min_by(|a, b| (peak.freq / a).ln().abs().total_cmp(&(peak.freq / b).ln().abs()))
+                .unwrap_or(midi_utils::note_to_freq(midi_utils::freq_to_note_id(peak.freq)));
+            let cents = 1200. * (peak.freq / target).log2();
+            Row::new(vec![
+                format!("{:.2} Hz", peak.freq),
+                format!("{:.1} dB", peak.db),
+                midi_utils::note_name(midi_utils::freq_to_note_id(peak.freq), middle_c),
+                format!("{:.2} Hz", target),
+                format!("{:+.1} ct", cents),
+            ])
+        });
+        frame.render_widget(
+            Table::new(rows, [Constraint::Fill(1); 5])
+                .header(header)
+                .block(
+                    Block::bordered()
+                        .title("Peaks")
+                        .border_type(BorderType::Thick),
+                ),
+            vertical_layout[1],
+        );
+
+        // Path input for loading
+        frame.render_widget(
+            self.input.get_input(String::from("Load recording (.wav)"), theme),
+            vertical_layout[2],
+        );
+        if self.loading {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[2].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[2].y + vertical_layout[2].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.loading {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::ANALYSIS]
+        }
+    }
+}
+
+// Read a wav file as mono samples between -1 and 1
+pub fn load_wav(path: &str) -> Result<(Vec<f32>, u32), String> {
+    let reader = WavReader::open(path).map_err(|e| e.to_string())?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader
+            .into_samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+        SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .into_samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string())?
+        }
+    };
+    // Mix all channels down
+    let channels = spec.channels.max(1) as usize;
+    let mono = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, spec.sample_rate))
+}
+
+impl Analysis {
+    // Compute the spectrum, its chart overlay and the peaks
+    fn run(&self) -> Spectrum {
+        let size = self.size;
+        let window = window(self.window, size);
+        let window_sum: f64 = window.iter().sum();
+        let fft = FftPlanner::<f64>::new().plan_fft_forward(size);
+
+        // Start of every analysed window, short files are zero padded
+        let starts: Vec<usize> = if self.averaged {
+            (0..self.samples.len().saturating_sub(size).max(1))
+                .step_by(size / 2)
+                .collect()
+        } else {
+            let start = (self.position * self.sample_rate as f64) as usize;
+            vec![start.min(self.samples.len().saturating_sub(size))]
+        };
+        // Average the power of all windows
+        let mut power = vec![0.; size / 2];
+        let mut buffer = vec![Complex::new(0., 0.); size];
+        for start in &starts {
+            for (i, value) in buffer.iter_mut().enumerate() {
+                let sample = self.samples.get(start + i).copied().unwrap_or(0.) as f64;
+                *value = Complex::new(sample * window[i], 0.);
+            }
+            fft.process(&mut buffer);
+            for (p, value) in power.iter_mut().zip(&buffer) {
+                // Scaled so a full scale sine reads 0 dBFS
+                let amplitude = 2. * value.norm() / window_sum;
+                *p += amplitude * amplitude / starts.len() as f64;
+            }
+        }
+        let db: Vec<f64> = power
+            .iter()
+            .map(|p| (10. * p.max(1e-20).log10()).max(FLOOR_DB))
+            .collect();
+        let bin_width = self.sample_rate as f64 / size as f64;
+
+        // Keep the highest level of neighbouring bins for the overlay
+        let group = db.len().div_ceil(DISPLAY_POINTS);
+        let display = db
+            .chunks(group)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let (offset, level) = chunk
+                    .iter()
+                    .enumerate()
+                    .fold((0, FLOOR_DB), |max, (j, l)| if *l > max.1 { (j, *l) } else { max });
+                ((i * group + offset) as f64 * bin_width, level)
+            })
+            .collect();
+        (display, find_peaks(&db, bin_width))
+    }
+}
+
+// Local maxima close to the strongest one, interpolated between bins
+fn find_peaks(db: &[f64], bin_width: f64) -> Vec<Peak> {
+    let max_db = db.iter().cloned().fold(FLOOR_DB, f64::max);
+    let mut peaks: Vec<Peak> = (1..db.len().saturating_sub(1))
+        .filter(|&k| {
+            db[k] > db[k - 1]
+                && db[k] >= db[k + 1]
+                && db[k] > max_db - PEAK_RANGE
+                && db[k] > FLOOR_DB
+                && k as f64 * bin_width >= MIN_PEAK_FREQ
+        })
+        .map(|k| {
+            let (offset, level) = interpolate_peak(db[k - 1], db[k], db[k + 1]);
+            Peak {
+                freq: (k as f64 + offset) * bin_width,
+                db: level,
+            }
+        })
+        .collect();
+    peaks.sort_by(|a, b| b.db.total_cmp(&a.db));
+    peaks.truncate(MAX_PEAKS);
+    peaks.sort_by(|a, b| a.freq.total_cmp(&b.freq));
+    peaks
+}
+
+// Offset in bins and level of the vertex of the parabola through a bin and its neighbours
+fn interpolate_peak(a: f64, b: f64, c: f64) -> (f64, f64) {
+    let curvature = a - 2. * b + c;
+    let offset = if curvature == 0. { 0. } else { 0.5 * (a - c) / curvature };
+    (offset, b - 0.25 * (a - c) * offset)
+}
+
+// Coefficients of a window function
+fn window(function: WindowFunction, size: usize) -> Vec<f64> {
+    (0..size)
+        .map(|i| {
+            let x = 2. * PI * i as f64 / size as f64;
+            match function {
+                WindowFunction::BlackmanHarris => {
+                    0.35875 - 0.48829 * x.cos() + 0.14128 * (2. * x).cos()
+                        - 0.01168 * (3. * x).cos()
+                }
+                WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+                WindowFunction::Rectangular => 1.,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn close(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() < tolerance
+    }
+
+    #[test]
+    fn interpolation_finds_parabola_vertex() {
+        // Symmetric neighbours keep the bin
+        assert_eq!(interpolate_peak(-10., 0., -10.), (0., 0.));
+        // y = -(x - 0.25)^2 sampled at -1, 0 and 1
+        let (offset, level) = interpolate_peak(-1.5625, -0.0625, -0.5625);
+        assert!(close(offset, 0.25, 1e-12));
+        assert!(close(level, 0., 1e-12));
+        // The vertex never leaves the neighbouring bins
+        let (offset, _) = interpolate_peak(-3., 0., -0.0001);
+        assert!(offset > 0. && offset < 0.5);
+        // A flat top has no curvature
+        assert_eq!(interpolate_peak(-6., -6., -6.), (0., -6.));
+    }
+
+    #[test]
+    fn peaks_are_local_maxima_in_range() {
+        let mut db = vec![FLOOR_DB; 64];
+        db[9..12].copy_from_slice(&[-20., -3., -20.]);
+        db[29..32].copy_from_slice(&[-40., -30., -50.]);
+        // Below the peak range of the strongest peak
+        db[49..52].copy_from_slice(&[-90., -80., -90.]);
+        // Under the lowest peak frequency
+        db[0..3].copy_from_slice(&[-1., 0., -1.]);
+        let peaks = find_peaks(&db, 10.);
+        assert_eq!(peaks.len(), 2);
+        assert!(close(peaks[0].freq, 100., 1e-9));
+        // The louder left neighbour pulls the second peak below its bin
+        assert!(peaks[1].freq > 295. && peaks[1].freq < 300.);
+        assert!(find_peaks(&[], 10.).is_empty());
+        assert!(find_peaks(&[0.], 10.).is_empty());
+    }
+
+    #[test]
+    fn sine_between_bins() {
+        let sample_rate = 48000;
+        let size = FFT_SIZES[0];
+        // Off the bin centre, where the peak without interpolation would be a bin too low
+        let freq = 42.3 * sample_rate as f64 / size as f64;
+        let samples = (0..sample_rate)
+            .map(|i| (2. * PI * freq * i as f64 / sample_rate as f64).sin() as f32)
+            .collect();
+        let analysis = Analysis {
+            samples: Arc::new(samples),
+            sample_rate,
+            size,
+            window: WindowFunction::BlackmanHarris,
+            averaged: true,
+            position: 0.,
+        };
+        let (_, peaks) = analysis.run();
+        let strongest = peaks.iter().max_by(|a, b| a.db.total_cmp(&b.db)).unwrap();
+        assert!(close(strongest.freq, freq, 0.1 * sample_rate as f64 / size as f64));
+        assert!(close(strongest.db, 0., 0.5));
+    }
+
+    #[test]
+    fn analyzes_in_the_background() {
+        let mut analyzer = SpectrumAnalyzer::new();
+        analyzer.samples = Arc::new(vec![0.5; 48000]);
+        analyzer.sample_rate = 48000;
+        analyzer.analyze();
+        // Settings changed while the first analysis runs are analyzed afterwards
+        analyzer.fft_size_index = 0;
+        analyzer.analyze();
+        for _ in 0..500 {
+            analyzer.on_tick();
+            if analyzer.job.is_none() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!analyzer.outdated);
+        assert_eq!(analyzer.display.len(), FFT_SIZES[0] / 2);
+    }
+}
@@ -0,0 +1,372 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph, Sparkline},
+};
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+use crate::keymap::{self, Keymap};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// Selectable rates in messages per second, 0 sends at line speed
+const RATES: [u32; 10] = [0, 10, 50, 100, 250, 500, 1000, 2000, 5000, 10000];
+// Non-commercial SysEx header of the test messages, a 14 bit sequence number follows
+const STRESS_HEADER: [u8; 4] = [0xF0, 0x7D, 0x53, 0x54];
+// Sequence numbers wrap after 14 bits
+const SEQ_MODULO: u32 = 0x4000;
+// Writes taking longer than this count as stalls
+const STALL_THRESHOLD: Duration = Duration::from_millis(1);
+// Most messages sent in a single tick, keeps the UI responsive at line speed
+const MAX_BATCH: u32 = 256;
+// Time to wait for late echoes after the test ended
+const ECHO_GRACE: Duration = Duration::from_millis(500);
+
+// Statistics of a stress run
+struct StressStats {
+    attempted: u32,         //Messages started, failed writes are not repeated
+    sent: u32,              //Messages written
+    write_errors: u32,      //Writes that failed, usually timeouts
+    stalls: u32,            //Writes slower than the stall threshold
+    max_write: Duration,    //Slowest write
+    received: u32,          //Echoed test messages
+    lost: u32,              //Sequence numbers skipped in the echo
+    reordered: u32,         //Echoes older than the expected one
+    next_echo: u32,         //Sequence number expected next in the echo
+    per_second: Vec<u64>,   //Messages sent in each second of the run
+}
+// Main stress test struct
+pub struct StressTest {
+    rate_index: usize,          //Selected rate
+    duration: u64,              //Run time in seconds
+    running: bool,
+    start: Instant,             //Start of the current run
+    end: Option<Instant>,       //End of the last run, echoes are still counted for a moment
+    stats: StressStats,
+}
+
+impl StressStats {
+    fn new() -> Self {
+        Self {
+            attempted: 0,
+            sent: 0,
+            write_errors: 0,
+            stalls: 0,
+            max_write: Duration::ZERO,
+            received: 0,
+            lost: 0,
+            reordered: 0,
+            next_echo: 0,
+            per_second: Vec::new(),
+        }
+    }
+}
+
+impl StressTest {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            rate_index: 6,
+            duration: 10,
+            running: false,
+            start: Instant::now(),
+            end: None,
+            stats: StressStats::new(),
+        }
+    }
+    // Whether echoes of a run are still expected
+    pub fn listening(&self) -> bool {
+        self.running || self.end.is_some_and(|e| e.elapsed() < ECHO_GRACE)
+    }
+    // Start a new run
+    fn start(&mut self, com_config: &ComConfig) {
+        if !com_config.is_connected() {
+            return;
+        }
+        self.stats = StressStats::new();
+        self.start = Instant::now();
+        self.end = None;
+        self.running = true;
+    }
+    // Stop the run, echoes are counted for a moment longer
+    fn stop(&mut self) {
+        if !self.running {
+            return;
+        }
+        self.running = false;
+        self.end = Some(Instant::now());
+    }
+    // Test message with a sequence number
+    fn message(seq: u32) -> Vec<u8> {
+        let mut message = STRESS_HEADER.to_vec();
+        message.extend([(seq >> 7) as u8, (seq & 0x7F) as u8, 0xF7]);
+        message
+    }
+    // Send the messages that are due
+    pub fn on_tick(&mut self, com_config: &mut ComConfig) {
+        if !self.running {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        if elapsed >= Duration::from_secs(self.duration) {
+            self.stop();
+            return;
+        }
+        let rate = RATES[self.rate_index];
+        let due = if rate == 0 {
+            MAX_BATCH
+        } else {
+            let target = (elapsed.as_secs_f64() * rate as f64) as u32;
+            target.saturating_sub(self.stats.attempted).min(MAX_BATCH)
+        };
+        let second = elapsed.as_secs() as usize;
+        if self.stats.per_second.len() <= second {
+            self.stats.per_second.resize(second + 1, 0);
+        }
+        for _ in 0..due {
+            let seq = self.stats.attempted % SEQ_MODULO;
+            self.stats.attempted += 1;
+            let write_start = Instant::now();
+            let result = com_config.try_send_bytes(&Self::message(seq));
+            let write_time = write_start.elapsed();
+            self.stats.max_write = self.stats.max_write.max(write_time);
+            if result.is_err() {
+                // Part of the message may be written already, sending it again would corrupt
+                // the stream, so it counts as an error and the port gets a tick to drain
+                self.stats.write_errors += 1;
+                break;
+            }
+            self.stats.sent += 1;
+            self.stats.per_second[second] += 1;
+            if write_time > STALL_THRESHOLD {
+                self.stats.stalls += 1;
+                // At line speed a stall means the buffers are full
+                if rate == 0 {
+                    break;
+                }
+            }
+        }
+    }
+    // Compare an echoed message against the sent sequence
+    pub fn receive(&mut self, message: &[u8]) {
+        let [0xF0, 0x7D, 0x53, 0x54, msb, lsb, 0xF7] = message else {
+            return;
+        };
+        let seq = (*msb as u32) << 7 | *lsb as u32;
+        self.stats.received += 1;
+        let gap = (seq + SEQ_MODULO - self.stats.next_echo) % SEQ_MODULO;
+        if gap < SEQ_MODULO / 2 {
+            self.stats.lost += gap;
+            self.stats.next_echo = (seq + 1) % SEQ_MODULO;
+        } else {
+            // Older than expected, it was already counted as lost
+            self.stats.reordered += 1;
+            self.stats.lost = self.stats.lost.saturating_sub(1);
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, com_config: &mut ComConfig) -> AppState {
+        let mut app_state: AppState = AppState::Stress;
+        match key.code {
+            // Quit, a running test is stopped
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.stop();
+                app_state = AppState::Running;
+            }
+            // Start or stop
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                if self.running {
+                    self.stop();
+                } else {
+                    self.start(com_config);
+                }
+            }
+            // Rate
+            KeyCode::Char('-') if !self.running => {
+                self.rate_index = self.rate_index.saturating_sub(1)
+            }
+            KeyCode::Char('+') if !self.running => {
+                self.rate_index = (self.rate_index + 1).min(RATES.len() - 1)
+            }
+            // Duration
+            KeyCode::Char('[') if !self.running => self.duration = (self.duration - 1).max(1),
+            KeyCode::Char(']') if !self.running => self.duration = (self.duration + 1).min(600),
+            KeyCode::Char('{') if !self.running => self.duration = (self.duration.saturating_sub(10)).max(1),
+            KeyCode::Char('}') if !self.running => self.duration = (self.duration + 10).min(600),
+            _ => {}
+        }
+        app_state
+    }
+    // Name of the selected rate
+    fn rate_name(&self) -> String {
+        match RATES[self.rate_index] {
+            0 => String::from("Line speed"),
+            r => format!("{} msg/s", r),
+        }
+    }
+    //Render the stress test screen
+    pub fn show_stress_popup(&mut self, frame: &mut Frame, com_config: &ComConfig, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 70, 60);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Fill(1), Constraint::Length(6)])
+            .split(area);
+
+        let stats = &self.stats;
+        let elapsed = match self.end {
+            Some(end) => end.duration_since(self.start),
+            None if self.running => self.start.elapsed(),
+            None => Duration::ZERO,
+        };
+        let achieved = if elapsed.is_zero() {
+            0.
+        } else {
+            stats.sent as f64 / elapsed.as_secs_f64()
+        };
+        let state = if self.running {
+            Span::styled("Running", Style::default().fg(theme.highlight))
+        } else if !com_config.is_connected() {
+            Span::styled("No port connected", Style::default().fg(theme.error))
+        } else {
+            Span::raw("Stopped")
+        };
+        // Echo results are only meaningful if the device echoes at all
+        let echo = if stats.received == 0 {
+            Line::from(" Echo: nothing received")
+        } else {
+            let missing = stats.sent.saturating_sub(stats.received);
+            let style = if stats.lost > 0 || stats.reordered > 0 || missing > 0 {
+                Style::default().fg(theme.error)
+            } else {
+                Style::default().fg(theme.ok)
+            };
+            Line::from(Span::styled(
+                format!(
+                    " Echo: received {} | lost {} | reordered {} | not echoed {}",
+                    stats.received, stats.lost, stats.reordered, missing
+                ),
+                style,
+            ))
+        };
+        let text = Text::from(vec![
+            Line::from(Span::styled(
+                " Throughput stress test",
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![Span::raw(" State: "), state]),
+            Line::from(format!(
+                " Rate: {} | Duration: {} s | Elapsed: {:.1} s",
+                self.rate_name(),
+                self.duration,
+                elapsed.as_secs_f64()
+            )),
+            Line::from(""),
+            Line::from(format!(
+                " Sent: {} messages ({} bytes) | Achieved: {:.0} msg/s",
+                stats.sent,
+                stats.sent * 3,
+                achieved
+            )),
+            Line::from(format!(
+                " Write stalls (>{} ms): {} | Write errors: {} | Slowest write: {:.2} ms",
+                STALL_THRESHOLD.as_millis(),
+                stats.stalls,
+                stats.write_errors,
+                stats.max_write.as_secs_f64() * 1000.
+            )),
+            echo,
+            Line::from(""),
+            Line::from(" • Sends non-commercial SysEx (F0 7D 53 54 seq F7) with a sequence number."),
+            Line::from(" • Devices that echo the messages back get checked for drops and reordering."),
+        ]);
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+        frame.render_widget(
+            Sparkline::default()
+                .block(
+                    Block::bordered()
+                        .title("Messages per second")
+                        .border_type(BorderType::Thick),
+                )
+                .data(&stats.per_second)
+                .style(Style::default().fg(theme.bars)),
+            vertical_layout[1],
+        );
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        vec![&keymap::STRESS]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echo the messages with the given sequence numbers
+    fn echo(test: &mut StressTest, sequence: impl IntoIterator<Item = u32>) {
+        for seq in sequence {
+            test.receive(&StressTest::message(seq));
+        }
+    }
+
+    #[test]
+    fn message_round_trips() {
+        assert_eq!(StressTest::message(0x1FFF), vec![0xF0, 0x7D, 0x53, 0x54, 0x3F, 0x7F, 0xF7]);
+        let mut test = StressTest::new();
+        echo(&mut test, [0x1FFF]);
+        assert_eq!(test.stats.next_echo, 0x2000);
+    }
+
+    #[test]
+    fn in_order_echoes() {
+        let mut test = StressTest::new();
+        echo(&mut test, 0..100);
+        assert_eq!((test.stats.received, test.stats.lost, test.stats.reordered), (100, 0, 0));
+    }
+
+    #[test]
+    fn dropped_echoes_count_as_lost() {
+        let mut test = StressTest::new();
+        echo(&mut test, [0, 1, 4, 5, 9]);
+        assert_eq!((test.stats.received, test.stats.lost, test.stats.reordered), (5, 5, 0));
+    }
+
+    #[test]
+    fn late_echoes_count_as_reordered() {
+        let mut test = StressTest::new();
+        echo(&mut test, [0, 1, 3, 2, 4]);
+        assert_eq!((test.stats.received, test.stats.lost, test.stats.reordered), (5, 0, 1));
+    }
+
+    #[test]
+    fn sequence_wraps_after_14_bits() {
+        let mut test = StressTest::new();
+        echo(&mut test, (0..SEQ_MODULO + 10).map(|seq| seq % SEQ_MODULO));
+        assert_eq!((test.stats.lost, test.stats.reordered), (0, 0));
+        // A drop across the wrap
+        echo(&mut test, (10..SEQ_MODULO - 1).chain([0, 1]));
+        assert_eq!((test.stats.lost, test.stats.reordered), (1, 0));
+        assert_eq!(test.stats.next_echo, 2);
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let mut test = StressTest::new();
+        test.receive(&[0xEF, 0x00, 0x40]);
+        test.receive(&[0xF0, 0x7D, 0x54, 0x4C, 0x00, 0x00, 0xF7]);
+        assert_eq!(test.stats.received, 0);
+    }
+}
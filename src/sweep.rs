@@ -0,0 +1,370 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Gauge, Paragraph},
+};
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+use crate::dds_data::Signal;
+use crate::keymap::{self, Keymap};
+use crate::midi_utils::{self, MiddleC};
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// What a sweep walks through
+#[derive(Clone, Copy)]
+enum SweepMode {
+    Notes,      //Every note of the range at the fixed velocity
+    Velocities, //Every velocity at the fixed note
+    Matrix,     //Every note of the range at every velocity step
+}
+// Main sweep struct
+pub struct Sweep {
+    mode: SweepMode,
+    note_low: u8,              //First note of the range
+    note_high: u8,             //Last note of the range
+    note: u8,                  //Fixed note of the velocity sweep
+    velocity: u8,              //Fixed velocity of the note sweep
+    velocity_step: u8,         //Distance between the velocities of the matrix
+    length: u64,               //Note length in ms
+    gap: u64,                  //Pause after a note in ms
+    channel: u8,               //Midi channel (0-15)
+    steps: Vec<(u8, u8)>,      //Note and velocity of the steps of the running sweep
+    step: usize,               //Index of the current step
+    playing: Option<(u8, u8)>, //Note and velocity currently held
+    next_change: Instant,      //Time of the next note on or off
+}
+
+impl Sweep {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            mode: SweepMode::Notes,
+            note_low: 0,
+            note_high: 127,
+            note: 60,
+            velocity: 100,
+            velocity_step: 16,
+            length: 200,
+            gap: 50,
+            channel: 0,
+            steps: Vec::new(),
+            step: 0,
+            playing: None,
+            next_change: Instant::now(),
+        }
+    }
+    // Whether a sweep is running
+    fn running(&self) -> bool {
+        self.step < self.steps.len()
+    }
+    // Note and velocity of every step of the selected mode
+    fn build_steps(&self) -> Vec<(u8, u8)> {
+        match self.mode {
+            SweepMode::Notes => (self.note_low..=self.note_high)
+                .map(|n| (n, self.velocity))
+                .collect(),
+            SweepMode::Velocities => (1..=127).map(|v| (self.note, v)).collect(),
+            SweepMode::Matrix => {
+                // The loudest velocity is always part of the matrix
+                let mut velocities: Vec<u8> = (1..127).step_by(self.velocity_step as usize).collect();
+                velocities.push(127);
+                (self.note_low..=self.note_high)
+                    .flat_map(|n| velocities.iter().map(move |v| (n, *v)))
+                    .collect()
+            }
+        }
+    }
+    // Start a sweep
+    fn start(&mut self) {
+        self.steps = self.build_steps();
+        self.step = 0;
+        self.playing = None;
+        self.next_change = Instant::now();
+    }
+    // Stop the sweep and release the held note
+    pub fn stop(&mut self, com_config: &mut ComConfig) {
+        if let Some((note, _)) = self.playing.take() {
+            com_config.send_midi(0x80 | self.channel, note, 0x00);
+        }
+        self.steps.clear();
+        self.step = 0;
+    }
+    // Play the steps of the sweep
+    pub fn on_tick(&mut self, com_config: &mut ComConfig) {
+        if !self.running() || Instant::now() < self.next_change {
+            return;
+        }
+        match self.playing.take() {
+            // Note off, the next step starts after the gap
+            Some((note, _)) => {
+                com_config.send_midi(0x80 | self.channel, note, 0x00);
+                self.step += 1;
+                self.next_change = Instant::now() + Duration::from_millis(self.gap);
+            }
+            // Note on of the current step
+            None => {
+                let (note, velocity) = self.steps[self.step];
+                com_config.send_midi(0x90 | self.channel, note, velocity);
+                self.playing = Some((note, velocity));
+                self.next_change = Instant::now() + Duration::from_millis(self.length);
+            }
+        }
+    }
+    // Signal of the held note in a slot of the channel view and a label with the step
+    pub fn current(&self, slot: usize) -> Option<(Signal, String)> {
+        let (note, velocity) = self.playing?;
+        Some((
+            Signal {
+                key: note as i32,
+                freq: midi_utils::note_to_freq(note),
+                velocity,
+                note,
+                channel: self.channel,
+                slot,
+                started: self.next_change - Duration::from_millis(self.length),
+            },
+            self.step_label(),
+        ))
+    }
+    // Label with the current step
+    fn step_label(&self) -> String {
+        format!("Sweep {}/{}", self.step + 1, self.steps.len())
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent, com_config: &mut ComConfig) -> AppState {
+        let mut app_state: AppState = AppState::Sweep;
+        // Settings are locked while a sweep runs
+        if self.running() {
+            match key.code {
+                // Quit, the sweep keeps running
+                KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+                // Stop
+                KeyCode::Enter | KeyCode::Char(' ') => self.stop(com_config),
+                _ => {}
+            }
+            return app_state;
+        }
+        match key.code {
+            // Quit
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Start
+            KeyCode::Enter | KeyCode::Char(' ') => self.start(),
+            // Mode
+            KeyCode::Char('m') => {
+                self.mode = match self.mode {
+                    SweepMode::Notes => SweepMode::Velocities,
+                    SweepMode::Velocities => SweepMode::Matrix,
+                    SweepMode::Matrix => SweepMode::Notes,
+                }
+            }
+            // Note range
+            KeyCode::Char('[') => self.note_low = self.note_low.saturating_sub(1),
+            KeyCode::Char(']') => self.note_low = (self.note_low + 1).min(self.note_high),
+            KeyCode::Char('{') => self.note_high = (self.note_high.saturating_sub(1)).max(self.note_low),
+            KeyCode::Char('}') => self.note_high = (self.note_high + 1).min(127),
+            // Fixed note and velocity
+            KeyCode::Char('n') => self.note = self.note.saturating_sub(1),
+            KeyCode::Char('N') => self.note = (self.note + 1).min(127),
+            KeyCode::Char('v') => self.velocity = (self.velocity - 1).max(1),
+            KeyCode::Char('V') => self.velocity = (self.velocity + 1).min(127),
+            // Matrix velocity step
+            KeyCode::Char('s') => self.velocity_step = (self.velocity_step - 1).max(1),
+            KeyCode::Char('S') => self.velocity_step = (self.velocity_step + 1).min(64),
+            // Timing
+            KeyCode::Char('l') => self.length = (self.length.saturating_sub(10)).max(10),
+            KeyCode::Char('L') => self.length = (self.length + 10).min(5000),
+            KeyCode::Char('g') => self.gap = self.gap.saturating_sub(10),
+            KeyCode::Char('G') => self.gap = (self.gap + 10).min(5000),
+            // Channel
+            KeyCode::Char('-') => self.channel = self.channel.saturating_sub(1),
+            KeyCode::Char('+') => self.channel = (self.channel + 1).min(15),
+            _ => {}
+        }
+        app_state
+    }
+    //Render the sweep settings and progress
+    pub fn show_sweep_popup(&mut self, frame: &mut Frame, middle_c: MiddleC, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 50);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        let block = Block::bordered()
+            .title("Sweep")
+            .border_type(BorderType::Thick);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let mode = match self.mode {
+            SweepMode::Notes => "Notes at a fixed velocity",
+            SweepMode::Velocities => "Velocities at a fixed note",
+            SweepMode::Matrix => "Notes x velocities",
+        };
+        // Highlight the settings the selected mode uses
+        let used = |used: bool| {
+            if used {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(theme.dim)
+            }
+        };
+        let (range, fixed_note, fixed_velocity, step) = match self.mode {
+            SweepMode::Notes => (true, false, true, false),
+            SweepMode::Velocities => (false, true, false, false),
+            SweepMode::Matrix => (true, false, false, true),
+        };
+        let steps = if self.running() {
+            self.steps.len()
+        } else {
+            self.build_steps().len()
+        };
+        let duration = steps as u64 * (self.length + self.gap);
+        let mut lines = vec![
+            Line::from(vec![Span::raw(" Mode: "), Span::styled(mode, used(true))]),
+            Line::from(Span::styled(
+                format!(
+                    " Note range: {} ({}) - {} ({})",
+                    midi_utils::note_name(self.note_low, middle_c),
+                    self.note_low,
+                    midi_utils::note_name(self.note_high, middle_c),
+                    self.note_high
+                ),
+                used(range),
+            )),
+            Line::from(Span::styled(
+                format!(" Fixed note: {} ({})", midi_utils::note_name(self.note, middle_c), self.note),
+                used(fixed_note),
+            )),
+            Line::from(Span::styled(
+                format!(" Fixed velocity: {}", self.velocity),
+                used(fixed_velocity),
+            )),
+            Line::from(Span::styled(
+                format!(" Velocity step: {}", self.velocity_step),
+                used(step),
+            )),
+            Line::from(Span::styled(
+                format!(
+                    " Length: {} ms | Gap: {} ms | Channel: {}",
+                    self.length,
+                    self.gap,
+                    self.channel + 1
+                ),
+                used(true),
+            )),
+            Line::from(format!(
+                " Steps: {} | Duration: {:.1} s",
+                steps,
+                duration as f64 / 1000.
+            )),
+            Line::from(""),
+        ];
+        match self.playing {
+            Some((note, velocity)) => lines.push(Line::from(Span::styled(
+                format!(
+                    " {}: {} ({}) velocity {}",
+                    self.step_label(),
+                    midi_utils::note_name(note, middle_c),
+                    note,
+                    velocity
+                ),
+                Style::default().fg(theme.highlight),
+            ))),
+            None if self.running() => lines.push(Line::from(" Gap")),
+            None => lines.push(Line::from(" Stopped")),
+        }
+        frame.render_widget(Paragraph::new(Text::from(lines)), inner);
+
+        // Progress of the running sweep
+        if self.running() && inner.height > 2 {
+            let gauge_area = Rect::new(inner.x + 1, inner.y + inner.height - 2, inner.width.saturating_sub(2), 1);
+            frame.render_widget(
+                Gauge::default()
+                    .gauge_style(Style::default().fg(theme.bars))
+                    .ratio(self.step as f64 / self.steps.len() as f64),
+                gauge_area,
+            );
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.running() {
+            vec![&keymap::SWEEP_RUNNING]
+        } else {
+            vec![&keymap::SWEEP]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sweep without waiting between the notes
+    fn fast_sweep(mode: SweepMode) -> Sweep {
+        let mut sweep = Sweep::new();
+        sweep.mode = mode;
+        sweep.length = 0;
+        sweep.gap = 0;
+        sweep
+    }
+
+    #[test]
+    fn note_sweep_covers_the_range() {
+        let mut sweep = fast_sweep(SweepMode::Notes);
+        (sweep.note_low, sweep.note_high, sweep.velocity) = (60, 63, 90);
+        assert_eq!(sweep.build_steps(), vec![(60, 90), (61, 90), (62, 90), (63, 90)]);
+        let velocities = fast_sweep(SweepMode::Velocities).build_steps();
+        assert_eq!(velocities.len(), 127);
+        assert_eq!((velocities[0], velocities[126]), ((60, 1), (60, 127)));
+    }
+
+    #[test]
+    fn matrix_always_ends_at_127() {
+        for velocity_step in 1..=127 {
+            let mut sweep = fast_sweep(SweepMode::Matrix);
+            (sweep.note_low, sweep.note_high, sweep.velocity_step) = (60, 61, velocity_step);
+            let steps = sweep.build_steps();
+            let velocities: Vec<u8> = steps.iter().filter(|(n, _)| *n == 60).map(|(_, v)| *v).collect();
+            assert_eq!(velocities.first(), Some(&1));
+            assert_eq!(velocities.last(), Some(&127));
+            assert!(velocities.windows(2).all(|w| w[0] < w[1]), "step {}", velocity_step);
+            assert_eq!(steps.len(), 2 * velocities.len());
+        }
+    }
+
+    #[test]
+    fn on_tick_alternates_note_on_and_off() {
+        let mut sweep = fast_sweep(SweepMode::Notes);
+        (sweep.note_low, sweep.note_high, sweep.channel) = (60, 61, 2);
+        sweep.start();
+        let sent = ComConfig::new().record(|com| {
+            for _ in 0..6 {
+                sweep.on_tick(com);
+            }
+        });
+        assert_eq!(
+            sent,
+            vec![vec![0x92, 60, 100], vec![0x82, 60, 0], vec![0x92, 61, 100], vec![0x82, 61, 0]]
+        );
+        assert!(!sweep.running());
+    }
+
+    #[test]
+    fn stop_releases_the_held_note() {
+        let mut sweep = fast_sweep(SweepMode::Notes);
+        sweep.start();
+        let sent = ComConfig::new().record(|com| {
+            sweep.on_tick(com);
+            sweep.stop(com);
+            sweep.on_tick(com);
+        });
+        assert_eq!(sent, vec![vec![0x90, 0, 100], vec![0x80, 0, 0]]);
+        assert!(!sweep.running());
+    }
+}
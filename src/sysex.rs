@@ -0,0 +1,533 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Text},
+    widgets::{Block, BorderType, Clear, HighlightSpacing, List, ListState, Paragraph},
+};
+use std::{
+    collections::VecDeque,
+    fs,
+    time::{Duration, Instant},
+};
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::midi_utils;
+use crate::popup_utils::popup_area;
+use crate::serial::ComConfig;
+use crate::theme::Theme;
+
+// Bytes per line of the hex view
+const HEX_WIDTH: usize = 16;
+// Highest delay between two messages in ms
+const MAX_DELAY: u64 = 2000;
+// Names of common one byte manufacturer ids
+const MANUFACTURERS: [(u8, &str); 16] = [
+    (0x01, "Sequential"),
+    (0x04, "Moog"),
+    (0x06, "Lexicon"),
+    (0x07, "Kurzweil"),
+    (0x0F, "Ensoniq"),
+    (0x10, "Oberheim"),
+    (0x18, "E-mu"),
+    (0x40, "Kawai"),
+    (0x41, "Roland"),
+    (0x42, "Korg"),
+    (0x43, "Yamaha"),
+    (0x44, "Casio"),
+    (0x47, "Akai"),
+    (0x7D, "Non-commercial"),
+    (0x7E, "Universal Non-Real Time"),
+    (0x7F, "Universal Real Time"),
+];
+// Names of common three byte manufacturer ids (0x00 followed by two bytes)
+const EXTENDED_MANUFACTURERS: [(u8, u8, &str); 9] = [
+    (0x00, 0x01, "Time/Warner Interactive"),
+    (0x00, 0x0E, "Alesis"),
+    (0x20, 0x29, "Novation"),
+    (0x20, 0x32, "Behringer"),
+    (0x20, 0x33, "Access"),
+    (0x20, 0x3C, "Elektron"),
+    (0x20, 0x6B, "Arturia"),
+    (0x21, 0x09, "Native Instruments"),
+    (0x21, 0x1D, "Expert Sleepers"),
+];
+
+// Where a message came from
+enum Origin {
+    File,
+    Built,
+    Captured,
+}
+// Actions waiting for text input
+enum InputAction {
+    Load,
+    Save,
+    Build,
+}
+// A stored system exclusive message
+struct SysExMessage {
+    origin: Origin,
+    data: Vec<u8>, //Full message including F0 and F7
+}
+// Main sysex librarian struct
+pub struct SysExLibrarian {
+    messages: Vec<SysExMessage>,
+    list_state: ListState,
+    scroll: u16,                        //First line of the hex view
+    delay: u64,                         //Delay between two sent messages in ms
+    capture: bool,                      //Store incoming messages
+    queue: VecDeque<Vec<u8>>,           //Messages waiting to be sent
+    next_send: Instant,                 //Earliest time for the next queued message
+    input_action: Option<InputAction>,  //Pending load, save or build
+    input: Input,                       //Input for paths and message bytes
+    last_status: Option<String>,
+    send_failed: bool,                  //Last status is a send error
+}
+
+impl SysExLibrarian {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            messages: Vec::new(),
+            list_state: ListState::default(),
+            scroll: 0,
+            delay: 100,
+            capture: false,
+            queue: VecDeque::new(),
+            next_send: Instant::now(),
+            input_action: None,
+            input: Input::new(),
+            last_status: None,
+            send_failed: false,
+        }
+    }
+    // Store an incoming message while capturing
+    pub fn capture(&mut self, data: &[u8]) {
+        if !self.capture {
+            return;
+        }
+        self.messages.push(SysExMessage {
+            origin: Origin::Captured,
+            data: data.to_vec(),
+        });
+        self.last_status = Some(format!("Captured {} bytes", data.len()));
+        if self.list_state.selected().is_none() {
+            self.list_state.select(Some(0));
+        }
+    }
+    // Send the next queued message once the delay passed
+    pub fn on_tick(&mut self, com_config: &mut ComConfig) {
+        if self.queue.is_empty() || Instant::now() < self.next_send {
+            return;
+        }
+        if let Some(data) = self.queue.pop_front() {
+            // Drop the rest of the queue, later messages often depend on the failed one
+            if let Err(e) = com_config.try_send_bytes(&data) {
+                self.last_status = Some(format!(
+                    "Send failed: {}, {} messages dropped",
+                    e,
+                    self.queue.len()
+                ));
+                self.send_failed = true;
+                self.queue.clear();
+                return;
+            }
+            self.send_failed = false;
+            self.next_send = Instant::now() + Duration::from_millis(self.delay);
+            if self.queue.is_empty() {
+                self.last_status = Some(String::from("All messages sent"));
+            }
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent) -> AppState {
+        let mut app_state: AppState = AppState::SysEx;
+        // Text input for paths and message bytes
+        if let Some(action) = &self.input_action {
+            match key.code {
+                KeyCode::Enter => {
+                    let text = self.input.submit_message();
+                    let result = match action {
+                        InputAction::Load => self.load(text.trim()),
+                        InputAction::Save => self.save(text.trim()),
+                        InputAction::Build => self.build(&text),
+                    };
+                    match result {
+                        Ok(()) => self.input_action = None,
+                        Err(e) => self.input.display_error(e),
+                    }
+                }
+                _ => {
+                    if self.input.key_event(key) {
+                        self.input_action = None;
+                    }
+                }
+            }
+            return app_state;
+        }
+        match key.code {
+            // Quit, queued messages keep sending
+            KeyCode::Char('q') | KeyCode::Esc => app_state = AppState::Running,
+            // Move down
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.list_state.select_next();
+                self.scroll = 0;
+            }
+            // Move up
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.list_state.select_previous();
+                self.scroll = 0;
+            }
+            // Scroll the hex view
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(8),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(8),
+            // Queue the selected message
+            KeyCode::Enter => {
+                if let Some(message) = self.selected() {
+                    let data = message.data.clone();
+                    self.queue.push_back(data);
+                    self.last_status = Some(String::from("Sending selected message"));
+                }
+            }
+            // Queue all messages
+            KeyCode::Char('a') => {
+                self.queue
+                    .extend(self.messages.iter().map(|m| m.data.clone()));
+                self.last_status = Some(format!("Sending {} messages", self.queue.len()));
+            }
+            // Cancel sending
+            KeyCode::Char('s') => {
+                self.queue.clear();
+                self.last_status = Some(String::from("Sending stopped"));
+            }
+            // Delay between messages
+            KeyCode::Char('-') => self.delay = self.delay.saturating_sub(10),
+            KeyCode::Char('+') => self.delay = (self.delay + 10).min(MAX_DELAY),
+            // Toggle capture
+            KeyCode::Char('c') => self.capture = !self.capture,
+            // Delete the selected message
+            KeyCode::Char('d') => {
+                if let Some(i) = self.list_state.selected().filter(|i| *i < self.messages.len()) {
+                    self.messages.remove(i);
+                    if self.messages.is_empty() {
+                        self.list_state.select(None);
+                    }
+                }
+            }
+            // Delete all messages
+            KeyCode::Char('x') => {
+                self.messages.clear();
+                self.list_state.select(None);
+            }
+            // Text input actions
+            KeyCode::Char('o') => self.input_action = Some(InputAction::Load),
+            KeyCode::Char('w') => self.input_action = Some(InputAction::Save),
+            KeyCode::Char('b') => self.input_action = Some(InputAction::Build),
+            _ => {}
+        }
+        app_state
+    }
+    // Selected message
+    fn selected(&self) -> Option<&SysExMessage> {
+        self.list_state.selected().and_then(|i| self.messages.get(i))
+    }
+    // Add all messages of a .syx file
+    fn load(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read(path).map_err(|e| e.to_string())?;
+        let messages = split_messages(&content);
+        if messages.is_empty() {
+            return Err(String::from("No sysex messages in file"));
+        }
+        self.last_status = Some(format!("Loaded {} messages from {}", messages.len(), path));
+        self.messages
+            .extend(messages.into_iter().map(|data| SysExMessage {
+                origin: Origin::File,
+                data,
+            }));
+        if self.list_state.selected().is_none() {
+            self.list_state.select(Some(0));
+        }
+        Ok(())
+    }
+    // Write all messages into a .syx file
+    fn save(&mut self, path: &str) -> Result<(), String> {
+        if self.messages.is_empty() {
+            return Err(String::from("No messages to save"));
+        }
+        let content: Vec<u8> = self.messages.iter().flat_map(|m| m.data.clone()).collect();
+        fs::write(path, content).map_err(|e| e.to_string())?;
+        self.last_status = Some(format!("Saved {} messages to {}", self.messages.len(), path));
+        Ok(())
+    }
+    // Build a message from hex bytes, the bytes after a | are covered by a Roland checksum
+    fn build(&mut self, text: &str) -> Result<(), String> {
+        let (body, checked) = match text.split_once('|') {
+            Some((body, checked)) => (body, Some(checked)),
+            None => (text, None),
+        };
+        let mut data = vec![0xF0];
+        data.extend(parse_data(body)?);
+        if let Some(checked) = checked {
+            let checked = parse_data(checked)?;
+            let checksum = roland_checksum(&checked);
+            data.extend(checked);
+            data.push(checksum);
+        }
+        data.push(0xF7);
+        if data.len() < 3 {
+            return Err(String::from("Message is empty"));
+        }
+        self.messages.push(SysExMessage {
+            origin: Origin::Built,
+            data,
+        });
+        self.list_state.select(Some(self.messages.len() - 1));
+        self.scroll = 0;
+        Ok(())
+    }
+    //Render the librarian screen
+    pub fn show_sysex_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 90, 80);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ])
+            .split(area);
+        let horizontal_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(vertical_layout[0]);
+
+        // Message list
+        let messages = List::new(
+            self.messages
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    let origin = match m.origin {
+                        Origin::File => "File",
+                        Origin::Built => "Built",
+                        Origin::Captured => "Capture",
+                    };
+                    Text::from(format!(
+                        "{:>3} {:<7} {:>6} B {}",
+                        i + 1,
+                        origin,
+                        m.data.len(),
+                        manufacturer_name(&m.data)
+                    ))
+                })
+                .collect::<Vec<Text>>(),
+        )
+        .block(
+            Block::bordered()
+                .title("SysEx Messages")
+                .border_type(BorderType::Thick),
+        )
+        .highlight_style(Style::new().fg(theme.selected).add_modifier(Modifier::BOLD))
+        .highlight_symbol(">> ")
+        .highlight_spacing(HighlightSpacing::WhenSelected);
+        frame.render_stateful_widget(messages, horizontal_layout[0], &mut self.list_state);
+
+        // Hex view of the selected message
+        let (title, lines) = match self.selected() {
+            Some(message) => (
+                format!("Hex | {}", manufacturer_name(&message.data)),
+                message
+                    .data
+                    .chunks(HEX_WIDTH)
+                    .enumerate()
+                    .map(|(i, chunk)| {
+                        Line::from(format!("{:04X}: {}", i * HEX_WIDTH, midi_utils::format_hex(chunk)))
+                    })
+                    .collect(),
+            ),
+            None => (String::from("Hex"), Vec::new()),
+        };
+        frame.render_widget(
+            Paragraph::new(lines)
+                .scroll((self.scroll, 0))
+                .block(Block::bordered().title(title).border_type(BorderType::Thick)),
+            horizontal_layout[1],
+        );
+
+        // Status line
+        let status = format!(
+            "Delay {} ms | Capture {} | Queued {} | {}",
+            self.delay,
+            if self.capture { "On" } else { "Off" },
+            self.queue.len(),
+            self.last_status.clone().unwrap_or_default()
+        );
+        let status_style = if self.send_failed {
+            Style::new().fg(theme.error)
+        } else {
+            Style::new()
+        };
+        frame.render_widget(
+            Paragraph::new(status).style(status_style).block(
+                Block::bordered()
+                    .title("Status")
+                    .border_type(BorderType::Thick),
+            ),
+            vertical_layout[1],
+        );
+
+        // Input for paths and message bytes
+        let title = match self.input_action {
+            Some(InputAction::Load) => "Load file (.syx)",
+            Some(InputAction::Save) => "Save all messages (.syx)",
+            Some(InputAction::Build) => "Hex bytes without F0/F7, bytes after | get a Roland checksum",
+            None => "Input",
+        };
+        frame.render_widget(
+            self.input.get_input(String::from(title), theme),
+            vertical_layout[2],
+        );
+        if self.input_action.is_some() {
+            frame.set_cursor_position(Position::new(
+                // Draw the cursor at the current position in the input field.
+                // This position is can be controlled via the left and right arrow key
+                vertical_layout[2].x + self.input.get_index() + 1,
+                // Move one line down, from the border to the input line
+                vertical_layout[2].y + vertical_layout[2].height - 2,
+            ));
+        }
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        if self.input_action.is_some() {
+            vec![self.input.keymap()]
+        } else {
+            vec![&keymap::SYSEX]
+        }
+    }
+}
+
+// Split raw bytes into sysex messages, bytes outside of F0..F7 are ignored.
+// Real-time bytes may appear inside a message and are dropped, any other
+// status byte ends a message without F7 and the message is dropped
+fn split_messages(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    for &byte in content {
+        match byte {
+            0xF0 => current = Some(vec![byte]),
+            0xF7 => {
+                if let Some(mut message) = current.take() {
+                    message.push(byte);
+                    messages.push(message);
+                }
+            }
+            0xF8..=0xFF => {}
+            0x80..=0xF6 => current = None,
+            _ => {
+                if let Some(message) = current.as_mut() {
+                    message.push(byte);
+                }
+            }
+        }
+    }
+    messages
+}
+
+// Parse hex bytes, all of them have to be data bytes
+fn parse_data(text: &str) -> Result<Vec<u8>, String> {
+    let data = midi_utils::parse_hex(text)?;
+    match data.iter().find(|b| **b >= 0x80) {
+        Some(b) => Err(format!("Not a valid data byte: {:02X}", b)),
+        None => Ok(data),
+    }
+}
+
+// Roland checksum, the sum of address, data and checksum is a multiple of 128
+pub fn roland_checksum(data: &[u8]) -> u8 {
+    let sum: u32 = data.iter().map(|b| *b as u32).sum();
+    ((128 - sum % 128) % 128) as u8
+}
+
+// Decode the manufacturer id following F0
+pub fn manufacturer_name(data: &[u8]) -> String {
+    match data.get(1..4) {
+        Some([0x00, a, b]) => EXTENDED_MANUFACTURERS
+            .iter()
+            .find(|(x, y, _)| x == a && y == b)
+            .map(|(_, _, n)| n.to_string())
+            .unwrap_or(format!("ID 00 {:02X} {:02X}", a, b)),
+        _ => match data.get(1) {
+            Some(id) => MANUFACTURERS
+                .iter()
+                .find(|(x, _)| x == id)
+                .map(|(_, n)| n.to_string())
+                .unwrap_or(format!("ID {:02X}", id)),
+            None => String::from("Unknown"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roland_checksum_completes_multiple_of_128() {
+        // GS reset: address 40 00 7F, data 00
+        assert_eq!(roland_checksum(&[0x40, 0x00, 0x7F, 0x00]), 0x41);
+        assert_eq!(roland_checksum(&[]), 0);
+        assert_eq!(roland_checksum(&[0x40, 0x40]), 0);
+        // 300 * 7F = 38100 = 297 * 128 + 84
+        assert_eq!(roland_checksum(&[0x7F; 300]), 44);
+    }
+
+    #[test]
+    fn build_appends_checksum() {
+        let mut librarian = SysExLibrarian::new();
+        assert!(librarian.build("41 10 42 12 | 40 00 7F 00").is_ok());
+        assert_eq!(
+            librarian.messages[0].data,
+            vec![0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7]
+        );
+    }
+
+    #[test]
+    fn build_rejects_status_bytes_and_empty_messages() {
+        let mut librarian = SysExLibrarian::new();
+        assert!(librarian.build("41 F7 10").is_err());
+        assert!(librarian.build("41 | 80").is_err());
+        assert!(librarian.build("").is_err());
+        assert!(librarian.messages.is_empty());
+    }
+
+    #[test]
+    fn split_messages_of_a_file() {
+        let content = [0xF0, 0x41, 0x10, 0xF7, 0x00, 0xF0, 0x43, 0x10, 0xF7];
+        assert_eq!(split_messages(&content), vec![
+            vec![0xF0, 0x41, 0x10, 0xF7],
+            vec![0xF0, 0x43, 0x10, 0xF7],
+        ]);
+    }
+
+    #[test]
+    fn split_messages_drops_unterminated_messages() {
+        // Cut off by a new message, by a status byte and by the end of the file
+        let content = [0xF0, 0x41, 0xF0, 0x43, 0xF7, 0xF0, 0x41, 0x90, 0x3C, 0xF7, 0xF0, 0x7E];
+        assert_eq!(split_messages(&content), vec![vec![0xF0, 0x43, 0xF7]]);
+    }
+
+    #[test]
+    fn split_messages_skips_real_time_bytes() {
+        let content = [0xF0, 0xF8, 0x41, 0xFE, 0x10, 0xFA, 0xF7];
+        assert_eq!(split_messages(&content), vec![vec![0xF0, 0x41, 0x10, 0xF7]]);
+    }
+}
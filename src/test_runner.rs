@@ -0,0 +1,361 @@
+use serde::Deserialize;
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::midi_utils;
+use crate::serial::ComConfig;
+
+// Exit codes of the test command
+const EXIT_PASSED: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+const USAGE: &str = "usage: tunein test <suite.toml> [--port <port>] [--baud <baud>] [--junit <report.xml>]";
+
+// A test suite loaded from TOML, misspelled keys are errors
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Suite {
+    #[serde(default = "default_name")]
+    name: String,             //Name used in the report
+    port: Option<String>,     //Serial port, can be overridden with --port
+    baud: Option<u32>,        //Baud rate, can be overridden with --baud
+    #[serde(default = "default_timeout")]
+    timeout_ms: u64,          //Default time to wait for a response
+    steps: Vec<TestStep>,
+}
+// A single step of a suite
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TestStep {
+    name: String,
+    #[serde(default)]
+    send: String,                //Hex bytes to send
+    expect: Option<String>,      //Hex bytes the response has to contain
+    expect_text: Option<String>, //Text the response has to contain
+    timeout_ms: Option<u64>,     //Overrides the suite timeout
+    #[serde(default)]
+    delay_ms: u64,               //Wait before the step is sent
+}
+// Options given on the command line
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    suite_path: String,
+    port: Option<String>,  //Overrides the port of the suite
+    baud: Option<u32>,     //Overrides the baud of the suite
+    junit: Option<String>, //Report path, next to the suite if none
+}
+// Outcome of a step
+struct StepResult {
+    name: String,
+    duration: Duration,
+    failure: Option<String>, //Reason the step failed
+}
+
+fn default_name() -> String {
+    String::from("TuneIn")
+}
+fn default_timeout() -> u64 {
+    500
+}
+
+// Run `tunein test <suite.toml> [--port <port>] [--baud <baud>] [--junit <report.xml>]`
+pub fn run_cli(args: &[String]) -> i32 {
+    match run(args) {
+        Ok(true) => EXIT_PASSED,
+        Ok(false) => EXIT_FAILED,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+// Parse the arguments after `test`, unknown flags and missing values are usage errors
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut suite_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--port" => parsed.port = Some(value()?),
+            "--baud" => {
+                let baud = value()?;
+                parsed.baud = Some(baud.parse().map_err(|_| format!("Not a valid baud: {}", baud))?);
+            }
+            "--junit" => parsed.junit = Some(value()?),
+            flag if flag.starts_with('-') => return Err(format!("Unknown option {}\n{}", flag, USAGE)),
+            path if suite_path.is_none() => suite_path = Some(path.to_string()),
+            extra => return Err(format!("Unexpected argument {}\n{}", extra, USAGE)),
+        }
+    }
+    parsed.suite_path = suite_path.ok_or(USAGE)?;
+    Ok(parsed)
+}
+
+// Run a suite, returns whether all steps passed
+fn run(args: &[String]) -> Result<bool, String> {
+    let Args {
+        suite_path,
+        port,
+        baud,
+        junit,
+    } = parse_args(args)?;
+    let content = fs::read_to_string(&suite_path).map_err(|e| format!("{}: {}", suite_path, e))?;
+    let suite = load_suite(&content).map_err(|e| format!("{}: {}", suite_path, e))?;
+
+    let port = port.or(suite.port.clone()).ok_or("No port in suite, use --port")?;
+    let baud = baud.or(suite.baud).ok_or("No baud in suite, use --baud")?;
+    let mut com_config = ComConfig::new();
+    com_config.connect(&port, baud)?;
+
+    println!("Running {} ({} steps) on {}", suite.name, suite.steps.len(), port);
+    let results: Vec<StepResult> = suite
+        .steps
+        .iter()
+        .map(|step| run_step(&mut com_config, step, suite.timeout_ms))
+        .collect();
+
+    let failed = results.iter().filter(|r| r.failure.is_some()).count();
+    println!("{} passed, {} failed", results.len() - failed, failed);
+
+    // The report goes next to the suite unless a path is given
+    let junit = junit.unwrap_or_else(|| {
+        Path::new(&suite_path)
+            .with_extension("xml")
+            .to_string_lossy()
+            .to_string()
+    });
+    fs::write(&junit, junit_xml(&suite.name, &results)).map_err(|e| format!("{}: {}", junit, e))?;
+    println!("JUnit report written to {}", junit);
+    Ok(failed == 0)
+}
+
+// Parse a suite and check all steps before anything is sent
+fn load_suite(content: &str) -> Result<Suite, String> {
+    let suite: Suite = toml::from_str(content).map_err(|e| e.to_string())?;
+    for step in &suite.steps {
+        midi_utils::parse_hex(&step.send).map_err(|e| format!("{}: {}", step.name, e))?;
+        if let Some(expect) = &step.expect {
+            midi_utils::parse_hex(expect).map_err(|e| format!("{}: {}", step.name, e))?;
+        }
+        // A step without an expectation would pass without checking anything
+        if step.expect.is_none() && step.expect_text.is_none() {
+            return Err(format!("{}: needs expect or expect_text", step.name));
+        }
+    }
+    Ok(suite)
+}
+
+// Send a step and wait for its expected response
+fn run_step(com_config: &mut ComConfig, step: &TestStep, default_timeout: u64) -> StepResult {
+    thread::sleep(Duration::from_millis(step.delay_ms));
+    // Drop everything received before the step
+    com_config.read_bytes();
+    let send = midi_utils::parse_hex(&step.send).unwrap_or_default();
+    let expect = step
+        .expect
+        .as_deref()
+        .map(|e| midi_utils::parse_hex(e).unwrap_or_default());
+    let timeout = Duration::from_millis(step.timeout_ms.unwrap_or(default_timeout));
+
+    let start = Instant::now();
+    // A write that times out fails the step instead of the whole run
+    if let Err(e) = com_config.try_send_bytes(&send) {
+        let failure = format!("send failed: {}", e);
+        println!("FAIL  {}: {}", step.name, failure);
+        return StepResult {
+            name: step.name.clone(),
+            duration: start.elapsed(),
+            failure: Some(failure),
+        };
+    }
+    let mut received = Vec::new();
+    let matches = |received: &[u8]| {
+        expect.as_deref().is_none_or(|e| contains(received, e))
+            && step
+                .expect_text
+                .as_deref()
+                .is_none_or(|t| String::from_utf8_lossy(received).contains(t))
+    };
+    while !matches(&received) && start.elapsed() < timeout {
+        received.extend(com_config.read_bytes().1);
+        thread::sleep(Duration::from_millis(1));
+    }
+    let duration = start.elapsed();
+
+    let failure = if matches(&received) {
+        None
+    } else {
+        let expected = match (&step.expect, &step.expect_text) {
+            (Some(bytes), Some(text)) => format!("{} and \"{}\"", bytes, text),
+            (Some(bytes), None) => bytes.clone(),
+            (None, Some(text)) => format!("\"{}\"", text),
+            (None, None) => String::new(),
+        };
+        Some(format!(
+            "expected {} within {} ms, received [{}]",
+            expected,
+            timeout.as_millis(),
+            midi_utils::format_hex(&received)
+        ))
+    };
+    match &failure {
+        Some(reason) => println!("FAIL  {} ({} ms): {}", step.name, duration.as_millis(), reason),
+        None => println!("PASS  {} ({} ms)", step.name, duration.as_millis()),
+    }
+    StepResult {
+        name: step.name.clone(),
+        duration,
+        failure,
+    }
+}
+
+// Whether a byte sequence appears in the received bytes
+fn contains(received: &[u8], expected: &[u8]) -> bool {
+    expected.is_empty() || received.windows(expected.len()).any(|w| w == expected)
+}
+
+// Build a JUnit XML report
+fn junit_xml(suite_name: &str, results: &[StepResult]) -> String {
+    let failed = results.iter().filter(|r| r.failure.is_some()).count();
+    let total: f64 = results.iter().map(|r| r.duration.as_secs_f64()).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml += &format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" time=\"{:.3}\">\n",
+        escape_xml(suite_name),
+        results.len(),
+        failed,
+        total
+    );
+    for result in results {
+        xml += &format!(
+            "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+            escape_xml(&result.name),
+            escape_xml(suite_name),
+            result.duration.as_secs_f64()
+        );
+        match &result.failure {
+            Some(reason) => {
+                xml += &format!(
+                    ">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                    escape_xml(reason)
+                )
+            }
+            None => xml += "/>\n",
+        }
+    }
+    xml += "</testsuite>\n";
+    xml
+}
+
+// Escape the characters XML reserves
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_suite_and_options() {
+        let parsed = parse_args(&args(&["--port", "/dev/ttyUSB0", "suite.toml", "--baud", "31250"]));
+        assert_eq!(
+            parsed,
+            Ok(Args {
+                suite_path: String::from("suite.toml"),
+                port: Some(String::from("/dev/ttyUSB0")),
+                baud: Some(31250),
+                junit: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_flags_and_missing_values() {
+        assert!(parse_args(&args(&["suite.toml", "--prot", "/dev/ttyUSB0"])).is_err());
+        assert!(parse_args(&args(&["suite.toml", "--port"])).is_err());
+        assert!(parse_args(&args(&["suite.toml", "--junit"])).is_err());
+        assert!(parse_args(&args(&["suite.toml", "--baud", "fast"])).is_err());
+        assert!(parse_args(&args(&["suite.toml", "other.toml"])).is_err());
+        assert!(parse_args(&args(&[])).is_err());
+    }
+
+    #[test]
+    fn usage_errors_exit_with_2() {
+        assert_eq!(run_cli(&args(&["suite.toml", "--port"])), EXIT_ERROR);
+    }
+
+    #[test]
+    fn loads_a_suite() {
+        let suite = load_suite(
+            "name = \"Synth\"\ntimeout_ms = 100\n\
+             [[steps]]\nname = \"Identity\"\nsend = \"F0 7E 7F 06 01 F7\"\nexpect = \"F0 7E\"\n\
+             [[steps]]\nname = \"Ack\"\nsend = \"90 3C 64\"\nexpect_text = \"OK\"\ndelay_ms = 10\n",
+        )
+        .unwrap();
+        assert_eq!(suite.name, "Synth");
+        assert_eq!(suite.timeout_ms, 100);
+        assert_eq!(suite.steps.len(), 2);
+        assert_eq!(suite.steps[1].delay_ms, 10);
+    }
+
+    #[test]
+    fn rejects_misspelled_fields() {
+        let step = load_suite("[[steps]]\nname = \"Identity\"\nsend = \"F0 7E 7F 06 01 F7\"\nexpcet = \"F0 7E\"\n");
+        assert!(step.err().is_some_and(|e| e.contains("expcet")));
+        let suite = load_suite("timeout = 100\n[[steps]]\nname = \"Ack\"\nexpect_text = \"OK\"\n");
+        assert!(suite.err().is_some_and(|e| e.contains("timeout")));
+    }
+
+    #[test]
+    fn rejects_steps_without_expectation() {
+        let suite = load_suite("[[steps]]\nname = \"Note on\"\nsend = \"90 3C 64\"\n");
+        assert_eq!(suite.err().as_deref(), Some("Note on: needs expect or expect_text"));
+        assert!(load_suite("[[steps]]\nname = \"Bad\"\nsend = \"90 3C\"\nexpect = \"GG\"\n").is_err());
+    }
+
+    #[test]
+    fn finds_expected_bytes_anywhere() {
+        assert!(contains(&[0xF8, 0x90, 0x3C, 0x64], &[0x3C, 0x64]));
+        assert!(contains(&[0x90], &[]));
+        assert!(!contains(&[0x90, 0x3C], &[0x3C, 0x64]));
+    }
+
+    #[test]
+    fn report_counts_failures_and_escapes_names() {
+        let results = [
+            StepResult {
+                name: String::from("Note <on>"),
+                duration: Duration::from_millis(5),
+                failure: None,
+            },
+            StepResult {
+                name: String::from("Identity"),
+                duration: Duration::from_millis(500),
+                failure: Some(String::from("expected \"F0\"")),
+            },
+        ];
+        let xml = junit_xml("Synth & Co", &results);
+        assert!(xml.contains("name=\"Synth &amp; Co\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("Note &lt;on&gt;"));
+        assert!(xml.contains("<failure message=\"expected &quot;F0&quot;\"/>"));
+    }
+}
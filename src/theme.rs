@@ -0,0 +1,300 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+use std::{env, str::FromStr};
+
+// Colours of the interface by what they show
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub accent: Color,    //Shortcut lines
+    pub filled: Color,    //Filled part of gauges
+    pub unfilled: Color,  //Empty part of gauges
+    pub highlight: Color, //Editing, running and changed values
+    pub selected: Color,  //Selected list entry
+    pub ok: Color,        //Connected and passing
+    pub error: Color,     //Errors, failures and limits
+    pub dim: Color,       //Unused settings and gridlines
+    pub text: Color,      //Axes and tables
+    pub bars: Color,      //Held tones and bar charts
+    pub overlay: Color,   //Measured spectrum and received notes
+    pub mixed: Color,     //Notes that are held and received
+    pub key_white: Color, //White piano keys
+    pub key_black: Color, //Black piano keys
+    pub monochrome: bool, //Fills use reversed video instead of colours
+}
+// A theme defined in the config, unset colours are taken from the base theme
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct UserTheme {
+    pub name: String,
+    pub base: Option<String>,
+    pub accent: Option<String>,
+    pub filled: Option<String>,
+    pub unfilled: Option<String>,
+    pub highlight: Option<String>,
+    pub selected: Option<String>,
+    pub ok: Option<String>,
+    pub error: Option<String>,
+    pub dim: Option<String>,
+    pub text: Option<String>,
+    pub bars: Option<String>,
+    pub overlay: Option<String>,
+    pub mixed: Option<String>,
+    pub key_white: Option<String>,
+    pub key_black: Option<String>,
+}
+// Built-in and user themes with the selected one
+pub struct Themes {
+    themes: Vec<(String, Theme)>,
+    index: usize,
+}
+
+pub const DARK: Theme = Theme {
+    accent: Color::Blue,
+    filled: Color::Blue,
+    unfilled: Color::Red,
+    highlight: Color::Yellow,
+    selected: Color::Green,
+    ok: Color::Green,
+    error: Color::Red,
+    dim: Color::DarkGray,
+    text: Color::White,
+    bars: Color::Blue,
+    overlay: Color::Green,
+    mixed: Color::Magenta,
+    key_white: Color::White,
+    key_black: Color::Black,
+    monochrome: false,
+};
+// For terminals with a light background
+pub const LIGHT: Theme = Theme {
+    accent: Color::Blue,
+    filled: Color::Blue,
+    unfilled: Color::Gray,
+    highlight: Color::Magenta,
+    selected: Color::Green,
+    ok: Color::Green,
+    error: Color::Red,
+    dim: Color::Gray,
+    text: Color::Black,
+    bars: Color::Blue,
+    overlay: Color::Green,
+    mixed: Color::Cyan,
+    key_white: Color::White,
+    key_black: Color::Black,
+    monochrome: false,
+};
+// Bright colours that survive washed out projectors
+pub const HIGH_CONTRAST: Theme = Theme {
+    accent: Color::LightCyan,
+    filled: Color::White,
+    unfilled: Color::Black,
+    highlight: Color::LightYellow,
+    selected: Color::LightGreen,
+    ok: Color::LightGreen,
+    error: Color::LightRed,
+    dim: Color::Gray,
+    text: Color::White,
+    bars: Color::LightCyan,
+    overlay: Color::LightGreen,
+    mixed: Color::LightMagenta,
+    key_white: Color::White,
+    key_black: Color::Black,
+    monochrome: false,
+};
+// No colours at all, used for NO_COLOR
+pub const MONOCHROME: Theme = Theme {
+    accent: Color::Reset,
+    filled: Color::Reset,
+    unfilled: Color::Reset,
+    highlight: Color::Reset,
+    selected: Color::Reset,
+    ok: Color::Reset,
+    error: Color::Reset,
+    dim: Color::Reset,
+    text: Color::Reset,
+    bars: Color::Reset,
+    overlay: Color::Reset,
+    mixed: Color::Reset,
+    key_white: Color::Reset,
+    key_black: Color::Reset,
+    monochrome: true,
+};
+
+impl Theme {
+    // Style of a filled area, reversed video without colours
+    pub fn fill(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::new().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::new().fg(color).bg(color)
+        }
+    }
+    // Filled part of a gauge
+    pub fn filled_style(&self) -> Style {
+        self.fill(self.filled)
+    }
+    // Empty part of a gauge
+    pub fn unfilled_style(&self) -> Style {
+        self.empty(self.unfilled)
+    }
+    // Style of an empty area next to a filled one
+    pub fn empty(&self, color: Color) -> Style {
+        if self.monochrome {
+            Style::new()
+        } else {
+            Style::new().fg(color).bg(color)
+        }
+    }
+}
+
+impl Themes {
+    // Built-in themes followed by the user themes, NO_COLOR overrides the selected one
+    pub fn load(selected: &str, user_themes: &[UserTheme]) -> Result<Self, String> {
+        let no_color = env::var("NO_COLOR").is_ok_and(|v| !v.is_empty());
+        Self::select(selected, user_themes, no_color)
+    }
+    // Built-in themes followed by the user themes, monochrome is forced without colours
+    fn select(selected: &str, user_themes: &[UserTheme], no_color: bool) -> Result<Self, String> {
+        let mut themes = vec![
+            (String::from("dark"), DARK),
+            (String::from("light"), LIGHT),
+            (String::from("high-contrast"), HIGH_CONTRAST),
+            (String::from("monochrome"), MONOCHROME),
+        ];
+        for user_theme in user_themes {
+            let base = user_theme.base.as_deref().unwrap_or("dark");
+            let Some((_, base)) = themes.iter().find(|(name, _)| name == base) else {
+                return Err(format!("Theme {}: unknown base theme {}", user_theme.name, base));
+            };
+            let theme = user_theme.apply(*base)?;
+            themes.push((user_theme.name.clone(), theme));
+        }
+        let selected = match selected {
+            _ if no_color => "monochrome",
+            "" => "dark",
+            selected => selected,
+        };
+        let Some(index) = themes.iter().position(|(name, _)| name == selected) else {
+            return Err(format!("Unknown theme {}", selected));
+        };
+        Ok(Self { themes, index })
+    }
+    // Theme every widget is drawn with
+    pub fn current(&self) -> &Theme {
+        &self.themes[self.index].1
+    }
+    // Select the following theme
+    pub fn cycle(&mut self) {
+        self.index = (self.index + 1) % self.themes.len();
+    }
+    pub fn name(&self) -> &str {
+        &self.themes[self.index].0
+    }
+}
+
+impl UserTheme {
+    // The base theme with the colours set by the user
+    fn apply(&self, base: Theme) -> Result<Theme, String> {
+        let color = |value: &Option<String>, default: Color| match value {
+            Some(value) => Color::from_str(value)
+                .map_err(|_| format!("Theme {}: unknown colour {}", self.name, value)),
+            None => Ok(default),
+        };
+        Ok(Theme {
+            accent: color(&self.accent, base.accent)?,
+            filled: color(&self.filled, base.filled)?,
+            unfilled: color(&self.unfilled, base.unfilled)?,
+            highlight: color(&self.highlight, base.highlight)?,
+            selected: color(&self.selected, base.selected)?,
+            ok: color(&self.ok, base.ok)?,
+            error: color(&self.error, base.error)?,
+            dim: color(&self.dim, base.dim)?,
+            text: color(&self.text, base.text)?,
+            bars: color(&self.bars, base.bars)?,
+            overlay: color(&self.overlay, base.overlay)?,
+            mixed: color(&self.mixed, base.mixed)?,
+            key_white: color(&self.key_white, base.key_white)?,
+            key_black: color(&self.key_black, base.key_black)?,
+            monochrome: base.monochrome,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_theme(name: &str, base: Option<&str>) -> UserTheme {
+        UserTheme {
+            name: name.to_string(),
+            base: base.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn load_selects_builtin_themes() {
+        let themes = Themes::select("", &[], false).unwrap();
+        assert_eq!(themes.name(), "dark");
+        assert_eq!(themes.current().text, DARK.text);
+        let themes = Themes::select("light", &[], false).unwrap();
+        assert_eq!(themes.name(), "light");
+        assert_eq!(themes.current().text, LIGHT.text);
+    }
+
+    #[test]
+    fn load_rejects_unknown_theme() {
+        assert!(Themes::select("solarized", &[], false).is_err());
+    }
+
+    #[test]
+    fn no_color_forces_monochrome() {
+        let themes = Themes::select("light", &[], true).unwrap();
+        assert_eq!(themes.name(), "monochrome");
+        assert!(themes.current().monochrome);
+    }
+
+    #[test]
+    fn user_theme_overrides_its_base() {
+        let mut custom = user_theme("stage", Some("light"));
+        custom.error = Some(String::from("#ff8000"));
+        custom.bars = Some(String::from("lightcyan"));
+        let themes = Themes::select("stage", &[custom], false).unwrap();
+        let theme = themes.current();
+        assert_eq!(theme.error, Color::Rgb(0xff, 0x80, 0x00));
+        assert_eq!(theme.bars, Color::LightCyan);
+        // Unset colours come from the base theme
+        assert_eq!(theme.text, LIGHT.text);
+        assert_eq!(theme.highlight, LIGHT.highlight);
+    }
+
+    #[test]
+    fn user_theme_defaults_to_dark_and_can_build_on_another() {
+        let first = user_theme("first", None);
+        let mut second = user_theme("second", Some("first"));
+        second.text = Some(String::from("red"));
+        let themes = Themes::select("second", &[first, second], false).unwrap();
+        assert_eq!(themes.current().highlight, DARK.highlight);
+        assert_eq!(themes.current().text, Color::Red);
+    }
+
+    #[test]
+    fn user_theme_errors_name_the_theme() {
+        let err = Themes::select("", &[user_theme("stage", Some("sepia"))], false).err();
+        assert_eq!(err.as_deref(), Some("Theme stage: unknown base theme sepia"));
+        let mut custom = user_theme("stage", None);
+        custom.dim = Some(String::from("not a colour"));
+        let err = Themes::select("", &[custom], false).err();
+        assert_eq!(err.as_deref(), Some("Theme stage: unknown colour not a colour"));
+    }
+
+    #[test]
+    fn cycle_wraps_through_all_themes() {
+        let mut themes = Themes::select("monochrome", &[user_theme("stage", None)], false).unwrap();
+        themes.cycle();
+        assert_eq!(themes.name(), "stage");
+        themes.cycle();
+        assert_eq!(themes.name(), "dark");
+        assert!(!themes.current().monochrome);
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Instant;
+
+// Tempo at startup and for invalid tempos
+const DEFAULT_BPM: f64 = 120.;
+
+// Shared musical time for everything that plays in sync
+pub struct Transport {
+    pub bpm: f64,       //Tempo in beats per minute
+    pub playing: bool,  //Transport is running
+    start: Instant,     //Time the position was last anchored
+    anchor_beats: f64,  //Position in beats at the anchor time
+    pub starts: u64,    //Number of starts from the beginning, players restart when it changes
+}
+
+impl Transport {
+    // Create new struct with default settings
+    pub fn new() -> Self {
+        Self {
+            bpm: DEFAULT_BPM,
+            playing: false,
+            start: Instant::now(),
+            anchor_beats: 0.,
+            starts: 0,
+        }
+    }
+    // Current position in beats (quarter notes)
+    pub fn position(&self) -> f64 {
+        if self.playing {
+            self.anchor_beats + self.start.elapsed().as_secs_f64() * self.bpm / 60.
+        } else {
+            self.anchor_beats
+        }
+    }
+    // Start playing from the beginning
+    pub fn start(&mut self) {
+        self.anchor_beats = 0.;
+        self.start = Instant::now();
+        self.playing = true;
+        self.starts += 1;
+    }
+    // Stop and keep the position
+    pub fn stop(&mut self) {
+        self.anchor_beats = self.position();
+        self.playing = false;
+    }
+    // Continue playing from the current position
+    pub fn resume(&mut self) {
+        self.start = Instant::now();
+        self.playing = true;
+    }
+    // Change the tempo without jumping in position
+    pub fn set_bpm(&mut self, bpm: f64) {
+        self.anchor_beats = self.position();
+        self.start = Instant::now();
+        self.bpm = if bpm.is_finite() { bpm.clamp(20., 300.) } else { DEFAULT_BPM };
+    }
+    // Bar number and beat within the bar (1-4)
+    pub fn bar_beat(&self) -> (u64, u64) {
+        let beat = self.position().floor() as u64;
+        (beat / 4 + 1, beat % 4 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_bpm_keeps_the_tempo_in_range() {
+        let mut transport = Transport::new();
+        transport.set_bpm(400.);
+        assert_eq!(transport.bpm, 300.);
+        transport.set_bpm(f64::NAN);
+        assert_eq!(transport.bpm, DEFAULT_BPM);
+        transport.set_bpm(f64::NEG_INFINITY);
+        assert_eq!(transport.bpm, DEFAULT_BPM);
+    }
+}
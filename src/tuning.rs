@@ -0,0 +1,450 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Clear, Paragraph},
+};
+use std::fs;
+
+use crate::AppState;
+use crate::input::Input;
+use crate::keymap::{self, Keymap};
+use crate::popup_utils::popup_area;
+use crate::theme::Theme;
+
+// Scale loaded from a Scala .scl file
+pub struct Tuning {
+    pub name: String,          //Description line of the scale
+    degrees: Vec<f64>,         //Pitch of every degree in cents, the last one is the period
+    mapping: KeyboardMapping, //Mapping from midi keys to scale degrees
+}
+// Keyboard mapping loaded from a Scala .kbm file
+struct KeyboardMapping {
+    first_note: i32,             //Lowest mapped midi key
+    last_note: i32,              //Highest mapped midi key
+    middle_note: i32,            //Midi key playing scale degree 0
+    reference_note: i32,         //Midi key the reference frequency is given for
+    reference_freq: f64,         //Frequency of the reference key
+    octave_degree: i32,          //Scale degree one mapping repetition spans, 0 for the scale size
+    keys: Vec<Option<i32>>,      //Degree of every key in a repetition, empty for a linear mapping
+}
+
+impl Tuning {
+    // Create the default 12 tone equal temperament
+    pub fn new() -> Self {
+        Self {
+            name: String::from("12-TET"),
+            degrees: (1..=12).map(|i| i as f64 * 100.).collect(),
+            mapping: KeyboardMapping::new(),
+        }
+    }
+    // Parse a Scala scale file and an optional keyboard mapping file
+    pub fn load(scale_path: &str, mapping_path: &str) -> Result<Self, String> {
+        let scale = fs::read_to_string(scale_path).map_err(|e| e.to_string())?;
+        let (name, degrees) = parse_scl(&scale)?;
+        let mapping = if mapping_path.is_empty() {
+            KeyboardMapping::new()
+        } else {
+            let mapping = fs::read_to_string(mapping_path).map_err(|e| e.to_string())?;
+            parse_kbm(&mapping)?
+        };
+        let tuning = Self {
+            name,
+            degrees,
+            mapping,
+        };
+        // The reference key is needed to place every other key
+        if tuning.pitch(tuning.mapping.reference_note).is_none() {
+            return Err(String::from("Reference note is unmapped"));
+        }
+        Ok(tuning)
+    }
+    // Frequency of a midi key, none if the key is unmapped
+    pub fn frequency(&self, note: i32) -> Option<f64> {
+        let cents = self.pitch(note)? - self.pitch(self.mapping.reference_note)?;
+        Some(self.mapping.reference_freq * f64::powf(2., cents / 1200.))
+    }
+    // Pitch of a midi key in cents above scale degree 0, none if the key is unmapped
+    fn pitch(&self, note: i32) -> Option<f64> {
+        let mapping = &self.mapping;
+        if note < mapping.first_note || note > mapping.last_note {
+            return None;
+        }
+        let offset = note - mapping.middle_note;
+        // Linear mapping, every key plays the next degree
+        if mapping.keys.is_empty() {
+            return Some(self.cents(offset));
+        }
+        let size = mapping.keys.len() as i32;
+        let degree = mapping.keys[offset.rem_euclid(size) as usize]?;
+        // Every repetition of the mapping is raised by the pitch of the formal octave,
+        // an octave degree of 0 uses the period of the scale
+        let octave_degree = match mapping.octave_degree {
+            0 => self.degrees.len() as i32,
+            octave_degree => octave_degree,
+        };
+        Some(offset.div_euclid(size) as f64 * self.cents(octave_degree) + self.cents(degree))
+    }
+    // Pitch of a scale degree in cents, degrees outside the scale repeat by the period
+    fn cents(&self, degree: i32) -> f64 {
+        let count = self.degrees.len() as i32;
+        let period = self.degrees[self.degrees.len() - 1];
+        let step = degree.rem_euclid(count) as usize;
+        let base = if step == 0 { 0. } else { self.degrees[step - 1] };
+        degree.div_euclid(count) as f64 * period + base
+    }
+}
+
+impl KeyboardMapping {
+    // Linear mapping with A4 at 440 Hz
+    fn new() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_freq: 440.,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+}
+
+// Lines of a Scala file without comments
+fn scala_lines(content: &str) -> impl Iterator<Item = &str> {
+    content.lines().filter(|line| !line.starts_with('!'))
+}
+
+// Parse the contents of a .scl file into the description and the degrees in cents
+fn parse_scl(content: &str) -> Result<(String, Vec<f64>), String> {
+    let mut lines = scala_lines(content);
+    let name = lines.next().ok_or("Empty scale file")?.trim().to_string();
+    let count: usize = first_token(lines.next())
+        .parse()
+        .map_err(|_| "Invalid note count")?;
+    let degrees = lines
+        .take(count)
+        .map(|line| parse_pitch(first_token(Some(line))))
+        .collect::<Result<Vec<f64>, String>>()?;
+    if degrees.is_empty() || degrees.len() < count {
+        return Err(String::from("Scale has missing notes"));
+    }
+    Ok((name, degrees))
+}
+
+// Parse a single pitch, values with a dot are cents and all others are ratios
+fn parse_pitch(value: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid pitch '{}'", value);
+    if value.contains('.') {
+        return value.parse().map_err(|_| invalid());
+    }
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    let numerator: f64 = numerator.parse().map_err(|_| invalid())?;
+    let denominator: f64 = denominator.parse().map_err(|_| invalid())?;
+    if numerator <= 0. || denominator <= 0. {
+        return Err(invalid());
+    }
+    Ok(1200. * (numerator / denominator).log2())
+}
+
+// Parse the contents of a .kbm file
+fn parse_kbm(content: &str) -> Result<KeyboardMapping, String> {
+    let mut lines = scala_lines(content);
+    let mut next_int = |field: &str| -> Result<i32, String> {
+        first_token(lines.next())
+            .parse()
+            .map_err(|_| format!("Invalid {}", field))
+    };
+    let size = next_int("map size")?;
+    let first_note = next_int("first note")?;
+    let last_note = next_int("last note")?;
+    let middle_note = next_int("middle note")?;
+    let reference_note = next_int("reference note")?;
+    let reference_freq: f64 = first_token(lines.next())
+        .parse()
+        .map_err(|_| "Invalid reference frequency")?;
+    let octave_degree: i32 = first_token(lines.next())
+        .parse()
+        .map_err(|_| "Invalid octave degree")?;
+    // Unmapped keys are marked with an x
+    let keys = lines
+        .take(size.max(0) as usize)
+        .map(|line| match first_token(Some(line)) {
+            "x" => Ok(None),
+            degree => degree
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid key mapping '{}'", degree)),
+        })
+        .collect::<Result<Vec<Option<i32>>, String>>()?;
+    if keys.len() < size.max(0) as usize {
+        return Err(String::from("Mapping has missing keys"));
+    }
+    Ok(KeyboardMapping {
+        first_note,
+        last_note,
+        middle_note,
+        reference_note,
+        reference_freq,
+        octave_degree,
+        keys,
+    })
+}
+
+// First whitespace separated value of a line, anything after it is a comment
+fn first_token(line: Option<&str>) -> &str {
+    line.and_then(|l| l.split_whitespace().next()).unwrap_or("")
+}
+
+// Different input fields
+enum TuningConfigState {
+    Scale,
+    Mapping,
+}
+// Main tuning config struct
+pub struct TuningConfig {
+    config_state: TuningConfigState,
+    pub tuning: Tuning,
+    scale_input: Input,
+    mapping_input: Input,
+}
+
+impl TuningConfig {
+    //Constructor with default values
+    pub fn new() -> Self {
+        Self {
+            config_state: TuningConfigState::Scale,
+            tuning: Tuning::new(),
+            scale_input: Input::new(),
+            mapping_input: Input::new(),
+        }
+    }
+    // Event handling
+    pub fn key_event(&mut self, key: KeyEvent) -> AppState {
+        let mut app_state: AppState = AppState::Tuning;
+        match key.code {
+            // Toggle state
+            KeyCode::Tab => self.toggle_state(),
+            // Load the files
+            KeyCode::Enter => {
+                let scale_path = self.scale_input.submit_message();
+                let mapping_path = self.mapping_input.submit_message();
+                // An empty scale path goes back to equal temperament
+                if scale_path.is_empty() {
+                    self.tuning = Tuning::new();
+                    app_state = AppState::Running;
+                } else {
+                    match Tuning::load(scale_path.trim(), mapping_path.trim()) {
+                        Ok(t) => {
+                            self.tuning = t;
+                            app_state = AppState::Running;
+                        }
+                        Err(e) => self.scale_input.display_error(e),
+                    }
+                }
+            }
+            _ => {
+                let input = match self.config_state {
+                    TuningConfigState::Scale => &mut self.scale_input,
+                    TuningConfigState::Mapping => &mut self.mapping_input,
+                };
+                if input.key_event(key) {
+                    app_state = AppState::Running;
+                }
+            }
+        }
+        app_state
+    }
+    // Toggle state
+    fn toggle_state(&mut self) {
+        match self.config_state {
+            TuningConfigState::Scale => self.config_state = TuningConfigState::Mapping,
+            TuningConfigState::Mapping => self.config_state = TuningConfigState::Scale,
+        }
+    }
+    //Render a popup for the tuning files
+    pub fn show_tuning_popup(&mut self, frame: &mut Frame, theme: &Theme) {
+        // Area of the popup
+        let area = popup_area(frame.area(), 60, 40);
+        // Clear area
+        frame.render_widget(Clear, area); //this clears out the background
+        // Layout
+        let vertical_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Fill(1),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ])
+            .split(area);
+
+        let centered_title = format!(
+            "{:^width$}",
+            "Tuning: ".to_string() + &self.tuning.name,
+            width = vertical_layout[0].width as usize
+        );
+
+        frame.render_widget(
+            Paragraph::new(Text::from(vec![
+                Line::from(Span::styled(
+                    centered_title,
+                    Style::default().add_modifier(Modifier::BOLD),
+                )),
+                Line::from(""),
+                Line::from(" Load a Scala tuning:"),
+                Line::from(""),
+                Line::from(" • Scale: Path to a .scl file, leave empty for 12-TET."),
+                Line::from(" • Mapping: Path to a .kbm file, leave empty for a linear mapping."),
+                Line::from(""),
+                Line::from(" • Note: Detuned notes are sent with a pitch bend on their own channel (2-16)."),
+            ]))
+            .block(Block::bordered().border_type(BorderType::Thick)),
+            vertical_layout[0],
+        );
+
+        frame.render_widget(
+            self.scale_input
+                .get_input(String::from("Scale (.scl)"), theme)
+                .style(Style::default()),
+            vertical_layout[1],
+        );
+        frame.render_widget(
+            self.mapping_input
+                .get_input(String::from("Mapping (.kbm)"), theme)
+                .style(Style::default()),
+            vertical_layout[2],
+        );
+        // Highlighting
+        let (input, title, field) = match self.config_state {
+            TuningConfigState::Scale => (&self.scale_input, "Scale (.scl)", vertical_layout[1]),
+            TuningConfigState::Mapping => {
+                (&self.mapping_input, "Mapping (.kbm)", vertical_layout[2])
+            }
+        };
+        frame.render_widget(input.get_input(String::from(title), theme), field);
+        frame.set_cursor_position(Position::new(
+            // Draw the cursor at the current position in the input field.
+            // This position is can be controlled via the left and right arrow key
+            field.x + input.get_index() + 1,
+            // Move one line down, from the border to the input line
+            field.y + field.height - 2,
+        ));
+    }
+    // Shortcuts of the current mode
+    pub fn keymaps(&self) -> Vec<&'static Keymap> {
+        let input = match self.config_state {
+            TuningConfigState::Scale => &self.scale_input,
+            TuningConfigState::Mapping => &self.mapping_input,
+        };
+        vec![&keymap::FIELDS, input.keymap()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PENTATONIC: &str = "! pentatonic.scl\n!\nJust pentatonic\n 5\n!\n 9/8\n 5/4 major third\n 3/2\n 5/3\n 2\n";
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn parses_scale_with_comments() {
+        let (name, degrees) = parse_scl(PENTATONIC).unwrap();
+        assert_eq!(name, "Just pentatonic");
+        assert_eq!(degrees.len(), 5);
+        assert!(close(degrees[2], 1200. * 1.5f64.log2()));
+        assert!(close(degrees[4], 1200.));
+    }
+
+    #[test]
+    fn parses_cents_and_ratios() {
+        assert!(close(parse_pitch("100.0").unwrap(), 100.));
+        assert!(close(parse_pitch("-5.").unwrap(), -5.));
+        assert!(close(parse_pitch("2").unwrap(), 1200.));
+        assert!(close(parse_pitch("3/2").unwrap(), 1200. * 1.5f64.log2()));
+        assert!(parse_pitch("0/1").is_err());
+        assert!(parse_pitch("3/0").is_err());
+        assert!(parse_pitch("-3/2").is_err());
+        assert!(parse_pitch("abc").is_err());
+    }
+
+    #[test]
+    fn rejects_broken_scales() {
+        assert!(parse_scl("").is_err());
+        assert!(parse_scl("Name\nfive\n2/1\n").is_err());
+        assert!(parse_scl("Name\n3\n9/8\n2/1\n").is_err());
+        assert!(parse_scl("Name\n0\n").is_err());
+    }
+
+    #[test]
+    fn default_tuning_is_equal_temperament() {
+        let tuning = Tuning::new();
+        assert!(close(tuning.frequency(69).unwrap(), 440.));
+        assert!(close(tuning.frequency(81).unwrap(), 880.));
+        assert!(close(tuning.frequency(60).unwrap(), 440. * 2f64.powf(-9. / 12.)));
+        assert_eq!(tuning.frequency(-1), None);
+        assert_eq!(tuning.frequency(128), None);
+    }
+
+    #[test]
+    fn mapping_with_unmapped_keys() {
+        // White keys only, every repetition spans the 7 degrees of a major scale
+        let kbm = "12\n0\n127\n60\n69\n440.0\n7\n0\nx\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n";
+        let mapping = parse_kbm(kbm).unwrap();
+        assert_eq!(mapping.keys.len(), 12);
+        assert_eq!(mapping.keys[1], None);
+        let tuning = Tuning {
+            name: String::from("Major"),
+            degrees: [200., 400., 500., 700., 900., 1100., 1200.].to_vec(),
+            mapping,
+        };
+        assert_eq!(tuning.frequency(61), None);
+        assert_eq!(tuning.pitch(72), Some(1200.));
+        assert!(close(tuning.frequency(81).unwrap(), 880.));
+        assert!(close(tuning.frequency(57).unwrap(), 220.));
+    }
+
+    #[test]
+    fn octave_degree_zero_uses_scale_size() {
+        let kbm = "5\n0\n127\n60\n60\n261.0\n0\n0\n1\n2\n3\n4\n";
+        let (name, degrees) = parse_scl(PENTATONIC).unwrap();
+        let tuning = Tuning {
+            name,
+            degrees,
+            mapping: parse_kbm(kbm).unwrap(),
+        };
+        assert!(close(tuning.pitch(65).unwrap(), 1200.));
+        assert!(close(tuning.frequency(65).unwrap(), 522.));
+        assert!(close(tuning.frequency(55).unwrap(), 130.5));
+    }
+
+    #[test]
+    fn mapping_repeats_at_the_formal_octave() {
+        // Three keys per repetition, repeating at the fifth of the pentatonic scale
+        let kbm = "3\n0\n127\n60\n60\n261.0\n3\n0\n1\n2\n";
+        let (name, degrees) = parse_scl(PENTATONIC).unwrap();
+        let tuning = Tuning {
+            name,
+            degrees,
+            mapping: parse_kbm(kbm).unwrap(),
+        };
+        assert!(close(tuning.frequency(63).unwrap(), 261. * 3. / 2.));
+        // A fifth plus a whole tone, not the major sixth of degree 4
+        assert!(close(tuning.frequency(64).unwrap(), 261. * 3. / 2. * 9. / 8.));
+        assert!(close(tuning.frequency(66).unwrap(), 261. * 9. / 4.));
+        assert!(close(tuning.frequency(58).unwrap(), 261. * 2. / 3. * 9. / 8.));
+    }
+
+    #[test]
+    fn rejects_broken_mappings() {
+        assert!(parse_kbm("").is_err());
+        assert!(parse_kbm("2\n0\n127\n60\n69\n440.0\n12\n0\n").is_err());
+        assert!(parse_kbm("1\n0\n127\n60\n69\n440.0\n12\ny\n").is_err());
+        assert!(parse_kbm("0\n0\n127\n60\n69\nfast\n12\n").is_err());
+    }
+}
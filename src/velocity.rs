@@ -0,0 +1,230 @@
+use rand::Rng;
+
+// Largest value of a midi data byte
+pub const MAX_VELOCITY: u8 = 127;
+// Softest note on, a velocity of 0 is a note off
+pub const MIN_VELOCITY: u8 = 1;
+// Amount the keys change a velocity by
+const VELOCITY_STEP: u8 = 5;
+
+// Shapes mapping the played level to the velocity that is sent
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VelocityCurve {
+    Fixed,       //Always the set level, random mode is ignored
+    Linear,      //Level sent as is
+    Exponential, //Soft levels get softer
+    Logarithmic, //Soft levels get louder
+}
+// Distributions for random velocities
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    Uniform,    //Every value is equally likely
+    Triangular, //Values near the middle of the range are more likely
+    Gaussian,   //Normal distribution around the middle of the range
+}
+// Velocity main struct
+pub struct Velocity {
+    pub level: u8,                  //Level set by the user
+    pub curve: VelocityCurve,       //Curve applied to the level
+    pub random: bool,               //Randomize the level for every note
+    pub random_min: u8,             //Lowest random level
+    pub random_max: u8,             //Highest random level
+    pub distribution: Distribution, //Distribution of the random levels
+}
+
+impl Velocity {
+    // Create new struct with default settings
+    pub fn new() -> Self {
+        Self {
+            level: 100,
+            curve: VelocityCurve::Linear,
+            random: false,
+            random_min: 20,
+            random_max: MAX_VELOCITY,
+            distribution: Distribution::Uniform,
+        }
+    }
+    // Velocity for the next note, randomized if random mode is on
+    pub fn next_velocity(&self) -> u8 {
+        if self.random && self.curve != VelocityCurve::Fixed {
+            self.apply_curve(self.random_level())
+        } else {
+            self.value()
+        }
+    }
+    // Velocity the current level is sent with
+    pub fn value(&self) -> u8 {
+        self.apply_curve(self.level)
+    }
+    // Map a level through the selected curve
+    fn apply_curve(&self, level: u8) -> u8 {
+        let x = level as f64 / MAX_VELOCITY as f64;
+        // Steepness of the exponential and logarithmic curves
+        let k: f64 = 4.;
+        let y = match self.curve {
+            VelocityCurve::Fixed | VelocityCurve::Linear => x,
+            VelocityCurve::Exponential => (f64::exp(k * x) - 1.) / (f64::exp(k) - 1.),
+            VelocityCurve::Logarithmic => (1. + (f64::exp(k) - 1.) * x).ln() / k,
+        };
+        (y * MAX_VELOCITY as f64)
+            .round()
+            .clamp(MIN_VELOCITY as f64, MAX_VELOCITY as f64) as u8
+    }
+    // Draw a random level between min and max
+    fn random_level(&self) -> u8 {
+        let mut rng = rand::rng();
+        let min = self.random_min as f64;
+        let max = self.random_max as f64;
+        let level = match self.distribution {
+            Distribution::Uniform => rng.random_range(min..=max),
+            Distribution::Triangular => {
+                (rng.random_range(min..=max) + rng.random_range(min..=max)) / 2.
+            }
+            Distribution::Gaussian => {
+                // Box-Muller transform, the range covers three standard deviations each side
+                let u1: f64 = rng.random_range(f64::EPSILON..1.);
+                let u2: f64 = rng.random();
+                let normal = (-2. * u1.ln()).sqrt() * (2. * std::f64::consts::PI * u2).cos();
+                (min + max) / 2. + normal * (max - min) / 6.
+            }
+        };
+        level.round().clamp(min, max) as u8
+    }
+    // Lower the level
+    pub fn decrease(&mut self) {
+        self.level = self.level.saturating_sub(VELOCITY_STEP).max(MIN_VELOCITY);
+    }
+    // Raise the level
+    pub fn increase(&mut self) {
+        self.level = (self.level + VELOCITY_STEP).min(MAX_VELOCITY);
+    }
+    // Lower or raise the bottom of the random range, it never passes the top
+    pub fn change_random_min(&mut self, raise: bool) {
+        self.random_min = if raise {
+            (self.random_min + VELOCITY_STEP).min(self.random_max)
+        } else {
+            self.random_min
+                .saturating_sub(VELOCITY_STEP)
+                .max(MIN_VELOCITY)
+        };
+    }
+    // Lower or raise the top of the random range, it never passes the bottom
+    pub fn change_random_max(&mut self, raise: bool) {
+        self.random_max = if raise {
+            (self.random_max + VELOCITY_STEP).min(MAX_VELOCITY)
+        } else {
+            self.random_max
+                .saturating_sub(VELOCITY_STEP)
+                .max(self.random_min)
+        };
+    }
+    pub fn toggle_rand(&mut self) {
+        self.random = !self.random;
+    }
+    // Switch to the next curve
+    pub fn cycle_curve(&mut self) {
+        self.curve = match self.curve {
+            VelocityCurve::Fixed => VelocityCurve::Linear,
+            VelocityCurve::Linear => VelocityCurve::Exponential,
+            VelocityCurve::Exponential => VelocityCurve::Logarithmic,
+            VelocityCurve::Logarithmic => VelocityCurve::Fixed,
+        };
+    }
+    // Switch to the next random distribution
+    pub fn cycle_distribution(&mut self) {
+        self.distribution = match self.distribution {
+            Distribution::Uniform => Distribution::Triangular,
+            Distribution::Triangular => Distribution::Gaussian,
+            Distribution::Gaussian => Distribution::Uniform,
+        };
+    }
+    // Name of the selected curve
+    pub fn curve_name(&self) -> &str {
+        match self.curve {
+            VelocityCurve::Fixed => "Fixed",
+            VelocityCurve::Linear => "Linear",
+            VelocityCurve::Exponential => "Exponential",
+            VelocityCurve::Logarithmic => "Logarithmic",
+        }
+    }
+    // Name of the selected distribution
+    pub fn distribution_name(&self) -> &str {
+        match self.distribution {
+            Distribution::Uniform => "Uniform",
+            Distribution::Triangular => "Triangular",
+            Distribution::Gaussian => "Gaussian",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn velocity(curve: VelocityCurve) -> Velocity {
+        Velocity {
+            curve,
+            ..Velocity::new()
+        }
+    }
+
+    #[test]
+    fn curves_keep_the_ends() {
+        for curve in [
+            VelocityCurve::Fixed,
+            VelocityCurve::Linear,
+            VelocityCurve::Exponential,
+            VelocityCurve::Logarithmic,
+        ] {
+            let velocity = velocity(curve);
+            assert_eq!(velocity.apply_curve(MAX_VELOCITY), MAX_VELOCITY);
+            assert_eq!(velocity.apply_curve(0), MIN_VELOCITY);
+        }
+    }
+
+    #[test]
+    fn curves_never_send_note_off() {
+        for curve in [VelocityCurve::Linear, VelocityCurve::Exponential] {
+            let velocity = velocity(curve);
+            assert!((0..=MAX_VELOCITY).all(|level| velocity.apply_curve(level) >= MIN_VELOCITY));
+        }
+    }
+
+    #[test]
+    fn curves_bend_the_right_way() {
+        let linear = velocity(VelocityCurve::Linear);
+        let exponential = velocity(VelocityCurve::Exponential);
+        let logarithmic = velocity(VelocityCurve::Logarithmic);
+        assert_eq!(linear.apply_curve(64), 64);
+        assert!(exponential.apply_curve(64) < 64);
+        assert!(logarithmic.apply_curve(64) > 64);
+        // Every curve rises with the level
+        for velocity in [linear, exponential, logarithmic] {
+            assert!((1..=MAX_VELOCITY).all(|l| velocity.apply_curve(l) >= velocity.apply_curve(l - 1)));
+        }
+    }
+
+    #[test]
+    fn decrease_stops_above_note_off() {
+        let mut velocity = velocity(VelocityCurve::Linear);
+        for _ in 0..30 {
+            velocity.decrease();
+            velocity.change_random_min(false);
+        }
+        assert_eq!(velocity.level, MIN_VELOCITY);
+        assert_eq!(velocity.random_min, MIN_VELOCITY);
+        assert_eq!(velocity.value(), MIN_VELOCITY);
+    }
+
+    #[test]
+    fn random_levels_stay_in_range() {
+        let mut velocity = velocity(VelocityCurve::Linear);
+        velocity.random = true;
+        velocity.random_min = 40;
+        velocity.random_max = 60;
+        for distribution in [Distribution::Uniform, Distribution::Triangular, Distribution::Gaussian] {
+            velocity.distribution = distribution;
+            assert!((0..200).all(|_| (40..=60).contains(&velocity.next_velocity())));
+        }
+    }
+}
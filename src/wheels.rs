@@ -0,0 +1,284 @@
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Style,
+    text::Line,
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+use std::time::{Duration, Instant};
+
+use crate::{dds_data::DdsData, midi_utils::{PITCH_BEND_CENTER, PITCH_BEND_RANGE}, serial::ComConfig};
+use crate::theme::Theme;
+
+// Bend added by a single key press
+const BEND_STEP: u16 = 1024;
+// Time without a bend key before the wheel springs back
+const SPRING_DELAY: Duration = Duration::from_millis(150);
+// Time between two spring back steps
+const SPRING_INTERVAL: Duration = Duration::from_millis(10);
+// Mod wheel controller
+const MOD_WHEEL: u8 = 1;
+
+// Pitch bend and mod wheel main struct
+pub struct Wheels {
+    pub bend: u16,          //14 bit pitch bend, 0x2000 is center
+    pub modulation: u8,     //Mod wheel value
+    pub bend_range: u8,     //Bend range in semitones sent with RPN 0
+    last_bend_key: Instant, //Time a bend key was last pressed
+    last_spring: Instant,   //Time of the last spring back step
+}
+
+impl Wheels {
+    // Create new struct with default settings
+    pub fn new() -> Self {
+        Self {
+            bend: PITCH_BEND_CENTER,
+            modulation: 0,
+            bend_range: PITCH_BEND_RANGE as u8,
+            last_bend_key: Instant::now(),
+            last_spring: Instant::now(),
+        }
+    }
+    // Move the bend wheel up or down
+    pub fn bend(&mut self, com_config: &mut ComConfig, dds_config: &DdsData, up: bool) {
+        self.last_bend_key = Instant::now();
+        let bend = if up {
+            (self.bend + BEND_STEP).min(0x3FFF)
+        } else {
+            self.bend.saturating_sub(BEND_STEP)
+        };
+        self.set_bend(com_config, dds_config, bend);
+    }
+    // Let the bend wheel spring back to center once no bend key is held
+    pub fn on_tick(&mut self, com_config: &mut ComConfig, dds_config: &DdsData) {
+        if self.bend == PITCH_BEND_CENTER
+            || self.last_bend_key.elapsed() < SPRING_DELAY
+            || self.last_spring.elapsed() < SPRING_INTERVAL
+        {
+            return;
+        }
+        self.last_spring = Instant::now();
+        let bend = if self.bend > PITCH_BEND_CENTER {
+            self.bend.saturating_sub(BEND_STEP).max(PITCH_BEND_CENTER)
+        } else {
+            (self.bend + BEND_STEP).min(PITCH_BEND_CENTER)
+        };
+        self.set_bend(com_config, dds_config, bend);
+    }
+    // Set a new bend value
+    fn set_bend(&mut self, com_config: &mut ComConfig, dds_config: &DdsData, bend: u16) {
+        if bend != self.bend {
+            self.bend = bend;
+            self.send_bend(com_config, dds_config);
+        }
+    }
+    // Send the bend on every channel in use, on top of the bend that detunes the channel
+    fn send_bend(&self, com_config: &mut ComConfig, dds_config: &DdsData) {
+        let offset = self.bend as i32 - PITCH_BEND_CENTER as i32;
+        for (channel, detune) in dds_config.channel_bends() {
+            let bend = (detune as i32 + offset).clamp(0, 0x3FFF) as u16;
+            com_config.send_midi(0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8);
+        }
+    }
+    // Center the bend and zero the mod wheel without sending, reset all controllers did that
+    pub fn reset(&mut self) {
+        self.bend = PITCH_BEND_CENTER;
+        self.modulation = 0;
+    }
+    // Move the mod wheel up or down
+    pub fn modulate(&mut self, com_config: &mut ComConfig, dds_config: &DdsData, up: bool) {
+        let modulation = if up {
+            (self.modulation + 8).min(127)
+        } else {
+            self.modulation.saturating_sub(8)
+        };
+        if modulation != self.modulation {
+            self.modulation = modulation;
+            for (channel, _) in dds_config.channel_bends() {
+                com_config.send_midi(0xB0 | channel, MOD_WHEEL, modulation);
+            }
+        }
+    }
+    // Change the bend range between 1 and 24 semitones
+    pub fn change_range(&mut self, raise: bool) {
+        self.bend_range = if raise {
+            (self.bend_range + 1).min(24)
+        } else {
+            (self.bend_range - 1).max(1)
+        };
+    }
+    // Send the bend range as RPN 0 on every channel a tone can use and close the RPN afterwards,
+    // the detuned tones are bent again for the new range
+    pub fn send_bend_range(&self, com_config: &mut ComConfig, dds_config: &mut DdsData) {
+        for status in 0xB0..=0xBF {
+            com_config.send_midi(status, 101, 0);
+            com_config.send_midi(status, 100, 0);
+            com_config.send_midi(status, 6, self.bend_range);
+            com_config.send_midi(status, 38, 0);
+            com_config.send_midi(status, 101, 127);
+            com_config.send_midi(status, 100, 127);
+        }
+        dds_config.set_bend_range(self.bend_range as f64);
+        self.send_bend(com_config, dds_config);
+    }
+    // Render the bend and mod wheel next to each other
+    pub fn render_wheels(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::new()
+            .border_type(BorderType::Thick)
+            .borders(Borders::ALL)
+            .title("Wheels");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let wheel_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Percentage(50); 2])
+            .split(inner);
+
+        let bend = (self.bend as f64 - PITCH_BEND_CENTER as f64) / PITCH_BEND_CENTER as f64;
+        render_wheel(
+            frame,
+            wheel_layout[0],
+            format!("PB±{}", self.bend_range),
+            bend,
+            true,
+            theme,
+        );
+        render_wheel(
+            frame,
+            wheel_layout[1],
+            String::from("Mod"),
+            self.modulation as f64 / 127.,
+            false,
+            theme,
+        );
+    }
+}
+
+// Render a vertical wheel, centered wheels fill from the middle (-1 to 1), others from the bottom (0 to 1)
+fn render_wheel(
+    frame: &mut Frame,
+    area: Rect,
+    title: String,
+    value: f64,
+    centered: bool,
+    theme: &Theme,
+) {
+    if area.height < 2 {
+        return;
+    }
+    let height = area.height as usize - 1;
+    // Rows from the top of the wheel
+    let (zero, level) = if centered {
+        let middle = height as f64 / 2.;
+        (middle, middle - value * middle)
+    } else {
+        (height as f64, height as f64 * (1. - value))
+    };
+    let (low, high) = (zero.min(level), zero.max(level));
+    let mut lines = vec![Line::from(title).centered()];
+    for row in 0..height {
+        let center = row as f64 + 0.5;
+        let filled = center >= low && center <= high;
+        lines.push(
+            Line::from(if filled { "███" } else { "│ │" })
+                .centered()
+                .style(if filled {
+                    Style::default().fg(theme.bars)
+                } else {
+                    Style::default()
+                }),
+        );
+    }
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Let the next tick spring the wheel back
+    fn release(wheels: &mut Wheels) {
+        wheels.last_bend_key = Instant::now() - SPRING_DELAY;
+        wheels.last_spring = Instant::now() - SPRING_INTERVAL;
+    }
+
+    #[test]
+    fn bend_is_sent_as_14_bit_value() {
+        let mut wheels = Wheels::new();
+        let dds = DdsData::new();
+        let sent = ComConfig::new().record(|com| {
+            for _ in 0..9 {
+                wheels.bend(com, &dds, true);
+            }
+        });
+        // 0x2400 is 0x00 0x48, the wheel stops at 0x3FFF and no repeat is sent there
+        assert_eq!(sent[0], vec![0xE0, 0x00, 0x48]);
+        assert_eq!(sent.len(), 8);
+        assert_eq!(sent.last(), Some(&vec![0xE0, 0x7F, 0x7F]));
+        let down = ComConfig::new().record(|com| wheels.bend(com, &dds, false));
+        assert_eq!(down, vec![vec![0xE0, 0x7F, 0x77]]);
+    }
+
+    #[test]
+    fn bend_springs_back_to_center() {
+        let mut wheels = Wheels::new();
+        let dds = DdsData::new();
+        let sent = ComConfig::new().record(|com| {
+            wheels.bend(com, &dds, false);
+            wheels.bend(com, &dds, false);
+            for _ in 0..4 {
+                release(&mut wheels);
+                wheels.on_tick(com, &dds);
+            }
+        });
+        assert_eq!(sent, vec![
+            vec![0xE0, 0x00, 0x38],
+            vec![0xE0, 0x00, 0x30],
+            vec![0xE0, 0x00, 0x38],
+            vec![0xE0, 0x00, 0x40],
+        ]);
+        assert_eq!(wheels.bend, PITCH_BEND_CENTER);
+    }
+
+    #[test]
+    fn bend_range_is_sent_as_rpn_0_on_every_channel() {
+        let mut wheels = Wheels::new();
+        let mut dds = DdsData::new();
+        wheels.bend_range = 12;
+        let sent = ComConfig::new().record(|com| wheels.send_bend_range(com, &mut dds));
+        for channel in 0..16 {
+            let status = 0xB0 | channel as u8;
+            assert_eq!(sent[channel * 6..channel * 6 + 6], [
+                vec![status, 101, 0],
+                vec![status, 100, 0],
+                vec![status, 6, 12],
+                vec![status, 38, 0],
+                vec![status, 101, 127],
+                vec![status, 100, 127],
+            ]);
+        }
+        // The centered wheel is sent again for the new range
+        assert_eq!(sent[96..], [vec![0xE0, 0x00, 0x40]]);
+    }
+
+    #[test]
+    fn detuned_channels_follow_the_wheel() {
+        let mut wheels = Wheels::new();
+        let mut dds = DdsData::new();
+        // A quarter of a semitone above A4 gets its own channel
+        let freq = 440.0 * 2f64.powf(0.25 / 12.);
+        ComConfig::new().record(|com| dds.toggle_key(com, 1, &[freq], 100));
+        let (channel, detune) = dds.channel_bends()[1];
+        let sent = ComConfig::new().record(|com| wheels.bend(com, &dds, true));
+        let bend = detune + BEND_STEP;
+        assert_eq!(sent, vec![
+            vec![0xE0, 0x00, 0x48],
+            vec![0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8],
+        ]);
+        // With a wider range the same detune needs less bend
+        wheels.bend_range = 12;
+        ComConfig::new().record(|com| wheels.send_bend_range(com, &mut dds));
+        assert!(dds.channel_bends()[1].1 < detune);
+    }
+}